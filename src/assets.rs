@@ -0,0 +1,245 @@
+/* ******************************************************************************* */
+/* Assets: the images the program draws, by name */
+/* ******************************************************************************* */
+/*
+ * img/images.ini names each image file, e.g.
+ *	[Images]
+ *	stick_0 = 00.svg			file names are from the manifest's own directory
+ *	stick_centre = 99.svg
+ * A device's section picks its images by these names:
+ *	image = stick_1									its picture, moved by x and y, turned by z
+ *	centre_image = stick_centre						drawn over the picture, in the middle
+ *	button_images = button_on, button_off, button_primary		pressed, released, and button 1 pressed
+ * Without them, a device is drawn with stick_<col> (stick_0 if there is none), and the
+ * other names below.  Without a manifest, the images shipped in img/ are used.
+ * Only the names and files are here; loading them is left to the program (see main.rs).
+ */
+use crate::ini_file;
+use std::collections::{BTreeMap, HashMap, };
+use std::path::Path;
+
+/* ******************************************************************************* */
+/* Constants */
+
+pub const MANIFEST_FILE: &str = "img/images.ini";
+
+pub const STICK_CENTRE: &str = "stick_centre";
+pub const BUTTON_ON: &str = "button_on";
+pub const BUTTON_OFF: &str = "button_off";
+pub const BUTTON_PRIMARY: &str = "button_primary";
+
+// the images shipped in img/, for when there is no manifest
+const SHIPPED: [(&str, &str); 7] = [
+	("stick_0", "00.svg"),
+	("stick_1", "01.svg"),
+	(STICK_CENTRE, "99.svg"),
+	("button_spare", "b_02.svg"),
+	(BUTTON_PRIMARY, "b_10.svg"),
+	(BUTTON_ON, "b_13.svg"),
+	(BUTTON_OFF, "b_99.svg"),
+];
+
+/* ******************************************************************************* */
+/* Structures */
+/* ******************************************************************************* */
+
+/// Every image, by name: the [Images] section of the manifest
+#[derive(Clone, Debug, PartialEq)]
+pub struct Manifest {
+	files: BTreeMap<String, String>,		// name, lower case, and the file's path
+}
+
+impl Default for Manifest {
+	/// the images shipped in img/
+	fn default() -> Self {
+		Manifest::from_config( Path::new( "img" ),
+			SHIPPED.iter().map( |(name, file)| (name.to_string(), Some( file.to_string() )) ).collect() ).0
+	}
+}
+
+impl Manifest {
+	/// read a manifest file; without one, the images shipped in img/
+	/// returns the manifest, and an error for each line that could not be used
+	pub fn load_file( path: &str ) -> (Manifest, Vec<String>) {
+		if !Path::new( path ).exists() {
+			return (Manifest::default(), vec![ format!("{}: not found, using the images in img/", path) ]);
+		}
+		let mut config = ini_file::parser();
+		match config.load( path ) {
+			Ok( mut sections ) => {
+				let dir = Path::new( path ).parent().unwrap_or( Path::new( "" ) );
+				let mut errors: Vec<String> = sections.keys()
+					.filter( |s| s.as_str() != "images" && s.as_str() != "comment" )
+					.map( |s| format!("{}: unknown section [{}]", path, s) )
+					.collect();
+				let (manifest, mut more) = Manifest::from_config( dir, sections.remove( "images" ).unwrap_or_default() );
+				errors.append( &mut more );
+				(manifest, errors)
+			}
+			Err( err ) => (Manifest::default(), vec![ format!("{}: {}, using the images in img/", path, err) ]),
+		}
+	}
+
+	/// the [Images] section's keys: a name for each file, files found from dir
+	pub fn from_config( dir: &Path, values: HashMap<String, Option<String>> ) -> (Manifest, Vec<String>) {
+		let mut files: BTreeMap<String, String> = BTreeMap::new();
+		let mut errors: Vec<String> = Vec::new();
+		for (name, file) in values {
+			let name = name.trim().to_lowercase();
+			if name == "comment" {
+				continue;
+			}
+			match file.map( |f| f.trim().to_string() ).filter( |f| !f.is_empty() ) {
+				Some( file ) => {
+					let path = dir.join( &file );
+					if !path.is_file() {
+						errors.push( format!("[Images] {}: no file {}", name, path.display()) );
+						continue;
+					}
+					files.insert( name, path.to_string_lossy().to_string() );
+				}
+				None => {	errors.push( format!("[Images] {}: needs a file name", name) );	}
+			}
+		}
+		(Manifest { files }, errors)
+	}
+
+	/// a manifest without any images
+	pub fn empty() -> Manifest {
+		Manifest { files: BTreeMap::new() }
+	}
+
+	/// every image, by name, with its file's path
+	pub fn files( &self ) -> impl Iterator<Item = (&str, &str)> {
+		self.files.iter().map( |(name, path)| (name.as_str(), path.as_str()) )
+	}
+
+	/// whether there is an image of this name
+	pub fn contains( &self, name: &str ) -> bool {
+		self.files.contains_key( &name.to_lowercase() )
+	}
+}
+
+/* ******************************************************************************* */
+/// The images one device is drawn with, by name
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct DeviceImages {
+	pub body: Option<String>,		// None: stick_<col>, else stick_0
+	pub centre: String,
+	pub button_on: String,
+	pub button_off: String,
+	pub button_primary: String,		// button 1 (usually the trigger), pressed
+}
+
+impl Default for DeviceImages {
+	fn default() -> Self {
+		DeviceImages {
+			body: None,
+			centre: STICK_CENTRE.to_string(),
+			button_on: BUTTON_ON.to_string(),
+			button_off: BUTTON_OFF.to_string(),
+			button_primary: BUTTON_PRIMARY.to_string(),
+		}
+	}
+}
+
+impl DeviceImages {
+	/// button_images: the pressed, released and (optional) button 1 pressed images' names
+	pub fn set_buttons( &mut self, text: &str ) -> Result<(), String> {
+		let names: Vec<String> = text.split( ',' ).map( |n| n.trim().to_lowercase() ).collect();
+		if names.iter().any( |n| n.is_empty() ) || !(2..=3).contains( &names.len() ) {
+			return Err( "should be the pressed and released images' names, and button 1's pressed image if it differs".to_string() );
+		}
+		self.button_on = names[ 0 ].clone();
+		self.button_off = names[ 1 ].clone();
+		self.button_primary = names.get( 2 ).unwrap_or( &names[ 0 ] ).clone();
+		Ok( () )
+	}
+
+	/// the device's picture: its image key, else stick_<col> if the manifest has one, else stick_0
+	pub fn body( &self, col: usize, manifest: &Manifest ) -> String {
+		match &self.body {
+			Some( name ) => name.clone(),
+			None if manifest.contains( &format!("stick_{}", col) ) => format!("stick_{}", col),
+			None => "stick_0".to_string(),
+		}
+	}
+
+	/// every name given by hand that the manifest does not have
+	pub fn missing( &self, manifest: &Manifest ) -> Vec<String> {
+		let names = [ self.body.as_ref(), Some( &self.centre ), Some( &self.button_on ), Some( &self.button_off ), Some( &self.button_primary ) ];
+		let mut ret: Vec<String> = Vec::new();
+		for name in names.into_iter().flatten() {
+			if !manifest.contains( name ) && !ret.contains( name ) {
+				ret.push( name.clone() );
+			}
+		}
+		ret
+	}
+}
+
+/* ******************************************************************************* */
+/* Tests */
+/* ******************************************************************************* */
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_dir::TestDir;
+
+	#[test]
+	fn shipped() {
+		let (manifest, errors) = Manifest::load_file( MANIFEST_FILE );
+		assert!( errors.is_empty(), "{:?}", errors );
+		for (name, _) in SHIPPED {
+			assert!( manifest.contains( name ), "{}", name );
+		}
+		assert_eq!( manifest.files().count(), Manifest::default().files().count() );
+		assert!( Manifest::default().contains( "Stick_1" ) );
+
+		let (manifest, errors) = Manifest::load_file( "img/nothing.ini" );
+		assert_eq!( (manifest, errors.len()), (Manifest::default(), 1) );
+	}
+
+	#[test]
+	fn manifest_file() {
+		let dir = TestDir::new( "assets" );
+		dir.write( "hornet.svg", "<svg/>" );
+		let path = dir.write( "images.ini", "[Images]\nStick_Hornet = hornet.svg\nstick_2 = gone.svg\nstick_3 =\n[Extras]\nx = 1\n" );
+		let (manifest, errors) = Manifest::load_file( &path );
+		assert_eq!( errors.len(), 3, "{:?}", errors );
+		assert!( errors.iter().any( |e| e.starts_with( "[Images] stick_2: no file " ) ) );
+		assert!( errors.iter().any( |e| e == "[Images] stick_3: needs a file name" ) );
+		assert!( errors.iter().any( |e| e.ends_with( ": unknown section [extras]" ) ) );
+		let files: Vec<(&str, &str)> = manifest.files().collect();
+		assert_eq!( files.len(), 1 );
+		assert_eq!( files[ 0 ].0, "stick_hornet" );
+		assert!( files[ 0 ].1.ends_with( "hornet.svg" ) );
+	}
+
+	#[test]
+	fn device_images() {
+		let mut images = DeviceImages::default();
+		for text in [ "on", "on, off, primary, spare", "on, , primary" ] {
+			assert!( images.set_buttons( text ).is_err(), "{}", text );
+		}
+		images.set_buttons( "Lit, Dark" ).unwrap();
+		assert_eq!( (images.button_on.as_str(), images.button_off.as_str(), images.button_primary.as_str()), ("lit", "dark", "lit") );
+		images.set_buttons( "lit, dark, red" ).unwrap();
+		assert_eq!( images.button_primary, "red" );
+
+		let manifest = Manifest::default();
+		assert_eq!( images.body( 1, &manifest ), "stick_1" );
+		assert_eq!( images.body( 5, &manifest ), "stick_0" );
+		assert_eq!( images.missing( &manifest ), vec![ "lit", "dark", "red" ] );
+		images.body = Some( "hornet".to_string() );
+		images.button_primary = "lit".to_string();
+		assert_eq!( images.body( 1, &manifest ), "hornet" );
+		assert_eq!( images.missing( &manifest ), vec![ "hornet", "lit", "dark" ] );
+		assert!( DeviceImages::default().missing( &manifest ).is_empty() );
+		assert_eq!( DeviceImages::default().missing( &Manifest::empty() ).len(), 4 );
+	}
+}
+
+/* ******************************************************************************* *
+ *		*** End ***
+ * ******************************************************************************* */
@@ -0,0 +1,593 @@
+/* ******************************************************************************* */
+/* Input backends */
+/* ******************************************************************************* */
+/*
+ * The device layer talks to joysticks through these traits, so that the way devices
+ * are found and read can be swapped:
+ *	HidApiBackend	the hidapi library (all platforms, the default)
+ *	HidrawBackend	Linux /dev/hidraw* nodes, read directly
+ *	EvdevBackend	Linux /dev/input/event* nodes, see evdev.rs
+ *	mock::MockBackend	scripted, in-memory devices; no hardware needed
+ */
+use hidapi::{HidApi, HidDevice, };
+use std::ffi::CString;
+use std::fmt;
+
+/* ******************************************************************************* */
+/* Structures */
+/* ******************************************************************************* */
+
+/// A device found by a backend, enough to choose it and open it
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DeviceEntry {
+	pub vid: u16,
+	pub pid: u16,
+	pub usage_page: u16,
+	pub path: String,		// backend specific, passed back to open()
+	pub name: Option<String>,	// product name, if known without opening the device
+	pub phys: Option<String>,	// physical location (e.g. usb-0000:00:14.0-2/input0), if known
+	pub serial: Option<String>,
+	pub interface_number: Option<i32>,
+}
+
+/* ******************************************************************************* */
+/// Identifies one configured device: its vid and pid, plus whatever else the .ini file
+/// gives to tell it apart from an identical device
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct DeviceKey {
+	pub vid: u16,
+	pub pid: u16,
+	pub serial: Option<String>,
+	pub path: Option<String>,			// as given by the backend, e.g. /dev/hidraw3
+	pub interface_number: Option<i32>,
+	pub name: Option<String>,			// evdev_name
+	pub phys: Option<String>,			// evdev_phys
+}
+
+impl DeviceKey {
+	pub fn new( vid: u16, pid: u16 ) -> DeviceKey {
+		DeviceKey { vid, pid, ..DeviceKey::default() }
+	}
+
+	/// is entry the device this key describes?
+	/// when found by name or phys, vid and pid of zero match any device
+	pub fn matches( &self, entry: &DeviceEntry ) -> bool {
+		let by_name = self.name.is_some() || self.phys.is_some();
+		(entry.vid == self.vid || (by_name && self.vid == 0))
+			&& (entry.pid == self.pid || (by_name && self.pid == 0))
+			&& (by_name || entry.usage_page < 8)		// No, need device with usage_page == 1
+			&& (self.serial.is_none() || entry.serial == self.serial)
+			&& (self.path.is_none() || self.path.as_ref() == Some( &entry.path ))
+			&& (self.interface_number.is_none() || entry.interface_number == self.interface_number)
+			&& (self.name.is_none() || entry.name == self.name)
+			&& (self.phys.is_none() || entry.phys == self.phys)
+	}
+
+	/// as written in an echo setting: hex vid and pid, then any of
+	/// serial=..., path=..., interface=..., e.g. "3344 01F8 serial=A1B2"
+	pub fn parse( value: &str ) -> Result<DeviceKey, String> {
+		let mut ret = DeviceKey::default();
+		for (n, val) in value.split_whitespace().enumerate() {
+			match (n, val.split_once( '=' )) {
+				(0, None) => {	ret.vid = u16::from_str_radix( val, 16 ).map_err( |err| format!("vid {} {}", val, err) )?;	}
+				(1, None) => {	ret.pid = u16::from_str_radix( val, 16 ).map_err( |err| format!("pid {} {}", val, err) )?;	}
+				(_, Some( (k, v) )) => {	ret.set_field( k, v )?;	}
+				(_, None) => {	return Err( format!("Unexpected '{}' in '{}'", val, value) );	}
+			}
+		}
+		Ok( ret )
+	}
+
+	/// set one of the optional fields, by its name in the .ini file
+	pub fn set_field( &mut self, key: &str, value: &str ) -> Result<(), String> {
+		let value = value.trim().to_string();
+		match key.trim() {
+			"vid" => {	self.vid = u16::from_str_radix( &value, 16 ).map_err( |err| err.to_string() )?;	}
+			"pid" => {	self.pid = u16::from_str_radix( &value, 16 ).map_err( |err| err.to_string() )?;	}
+			"serial" => {	self.serial = Some( value );	}
+			"path" => {	self.path = Some( value );	}
+			"interface" | "interface_number" => {
+				self.interface_number = Some( value.parse::<i32>().map_err( |err| err.to_string() )? );
+			}
+			"name" | "evdev_name" => {	self.name = Some( value );	}
+			"phys" | "evdev_phys" => {	self.phys = Some( value );	}
+			other => {	return Err( format!("Unknown device key field: {}", other) );	}
+		}
+		Ok( () )
+	}
+
+	/// every field, one "name=value" a line; read back with from_fields
+	pub fn to_fields( &self ) -> String {
+		let mut ret = format!("vid={:04x}\npid={:04x}", self.vid, self.pid);
+		let optional = [
+			("serial", self.serial.clone()),
+			("path", self.path.clone()),
+			("interface", self.interface_number.map( |n| n.to_string() )),
+			("name", self.name.clone()),
+			("phys", self.phys.clone()),
+		];
+		for (k, v) in optional {
+			if let Some( v ) = v {
+				ret = format!("{}\n{}={}", ret, k, v);
+			}
+		}
+		ret
+	}
+
+	pub fn from_fields( fields: &str ) -> Result<DeviceKey, String> {
+		let mut ret = DeviceKey::default();
+		for line in fields.lines() {
+			if let Some( (k, v) ) = line.split_once( '=' ) {
+				ret.set_field( k, v )?;
+			}
+		}
+		Ok( ret )
+	}
+}
+
+impl fmt::Display for DeviceKey {
+	fn fmt( &self, f: &mut fmt::Formatter ) -> fmt::Result {
+		write!( f, "{:04x} {:04x}", self.vid, self.pid )?;
+		if let Some( serial ) = &self.serial {	write!( f, " serial={}", serial )?;	}
+		if let Some( path ) = &self.path {	write!( f, " path={}", path )?;	}
+		if let Some( n ) = self.interface_number {	write!( f, " interface={}", n )?;	}
+		if let Some( name ) = &self.name {	write!( f, " name={}", name )?;	}
+		if let Some( phys ) = &self.phys {	write!( f, " phys={}", phys )?;	}
+		Ok( () )
+	}
+}
+
+/// An open device
+pub trait InputDevice: Send {
+	fn product_string( &self ) -> Result<Option<String>, String>;
+	/// read one report into buff, waiting up to timeout milliseconds (-1, wait for ever)
+	/// returns the number of bytes read, zero if nothing arrived in time
+	fn read_timeout( &self, buff: &mut [u8], timeout: i32 ) -> Result<usize, String>;
+	/// the raw HID report descriptor
+	fn report_descriptor( &self, buff: &mut [u8] ) -> Result<usize, String>;
+}
+
+/// A way of finding and opening devices
+pub trait InputBackend: Send {
+	/// look again for devices, e.g. after one has been plugged in
+	fn refresh( &mut self ) -> Result<(), String>;
+	fn devices( &self ) -> Vec<DeviceEntry>;
+	fn open( &self, entry: &DeviceEntry ) -> Result<Box<dyn InputDevice>, String>;
+}
+
+/* ******************************************************************************* */
+/// Choose a backend by name, as used in the .ini file
+pub fn backend_from_name( name: &str ) -> Result<Box<dyn InputBackend>, String> {
+	match name.trim().to_lowercase().as_str() {
+		"hidapi" => {	Ok( Box::new( HidApiBackend::new()? ) )	}
+		#[cfg(target_os = "linux")]
+		"hidraw" => {	Ok( Box::new( HidrawBackend::new() ) )	}
+		#[cfg(target_os = "linux")]
+		"evdev" => {	Ok( Box::new( crate::evdev::EvdevBackend::new() ) )	}
+		other => {	Err( format!("Unknown backend: {}", other) )	}
+	}
+}
+
+/* ******************************************************************************* */
+/* hidapi */
+/* ******************************************************************************* */
+pub struct HidApiBackend {
+	hid_api: HidApi,
+}
+
+impl HidApiBackend {
+	pub fn new() -> Result<HidApiBackend, String> {
+		match HidApi::new() {
+			Ok( hid_api ) => {	Ok( HidApiBackend { hid_api } )	}
+			Err( err ) => {	Err( format!("HidError {}", err) )	}
+		}
+	}
+}
+
+impl InputBackend for HidApiBackend {
+	fn refresh( &mut self ) -> Result<(), String> {
+		self.hid_api.refresh_devices().map_err( |err| err.to_string() )
+	}
+
+	fn devices( &self ) -> Vec<DeviceEntry> {
+		self.hid_api.device_list()
+			.map( |info| DeviceEntry {
+				vid: info.vendor_id(),
+				pid: info.product_id(),
+				usage_page: info.usage_page(),
+				path: info.path().to_string_lossy().to_string(),
+				name: info.product_string().map( |name| name.to_string() ),
+				phys: None,
+				serial: info.serial_number().map( |serial| serial.to_string() ),
+				interface_number: Some( info.interface_number() ).filter( |n| *n >= 0 ),
+			})
+			.collect()
+	}
+
+	fn open( &self, entry: &DeviceEntry ) -> Result<Box<dyn InputDevice>, String> {
+		let path = CString::new( entry.path.clone() ).map_err( |err| err.to_string() )?;
+		match self.hid_api.open_path( &path ) {
+			Ok( device ) => {	Ok( Box::new( device ) )	}
+			Err( err ) => {	Err( err.to_string() )	}
+		}
+	}
+}
+
+impl InputDevice for HidDevice {
+	fn product_string( &self ) -> Result<Option<String>, String> {
+		self.get_product_string().map_err( |err| err.to_string() )
+	}
+
+	fn read_timeout( &self, buff: &mut [u8], timeout: i32 ) -> Result<usize, String> {
+		HidDevice::read_timeout( self, buff, timeout ).map_err( |err| err.to_string() )
+	}
+
+	fn report_descriptor( &self, buff: &mut [u8] ) -> Result<usize, String> {
+		self.get_report_descriptor( buff ).map_err( |err| err.to_string() )
+	}
+}
+
+/* ******************************************************************************* */
+/* hidraw, Linux only */
+/* ******************************************************************************* */
+#[cfg(target_os = "linux")]
+pub use hidraw::HidrawBackend;
+
+#[cfg(target_os = "linux")]
+mod hidraw {
+	use super::{DeviceEntry, InputBackend, InputDevice, };
+	use std::fs::File;
+	use std::io::Read;
+	use std::os::unix::io::AsRawFd;
+
+	const SYS_CLASS: &str = "/sys/class/hidraw";
+
+	pub struct HidrawBackend {
+		devices: Vec<DeviceEntry>,
+	}
+
+	impl HidrawBackend {
+		pub fn new() -> HidrawBackend {
+			let mut ret = HidrawBackend { devices: Vec::new() };
+			let _ = ret.refresh();
+			ret
+		}
+	}
+
+	impl Default for HidrawBackend {
+		fn default() -> Self {
+			Self::new()
+		}
+	}
+
+	impl InputBackend for HidrawBackend {
+		fn refresh( &mut self ) -> Result<(), String> {
+			self.devices = Vec::new();
+			let dir = std::fs::read_dir( SYS_CLASS ).map_err( |err| format!("{} {}", SYS_CLASS, err) )?;
+			for entry in dir.flatten() {
+				let node = entry.file_name().to_string_lossy().to_string();
+				let Some( (vid, pid) ) = uevent_ids( &node ) else { continue; };
+				let desc = std::fs::read( format!("{}/{}/device/report_descriptor", SYS_CLASS, node) )
+					.unwrap_or_default();
+				self.devices.push( DeviceEntry {
+					vid,
+					pid,
+					usage_page: first_usage_page( &desc ),
+					path: format!("/dev/{}", node),
+					name: uevent_value( &node, "HID_NAME" ),
+					phys: uevent_value( &node, "HID_PHYS" ),
+					serial: uevent_value( &node, "HID_UNIQ" ).filter( |s| !s.is_empty() ),
+					interface_number: interface_number( &node ),
+				});
+			}
+			self.devices.sort_by( |a, b| a.path.cmp( &b.path ) );
+			Ok( () )
+		}
+
+		fn devices( &self ) -> Vec<DeviceEntry> {
+			self.devices.clone()
+		}
+
+		fn open( &self, entry: &DeviceEntry ) -> Result<Box<dyn InputDevice>, String> {
+			let file = File::open( &entry.path ).map_err( |err| format!("{} {}", entry.path, err) )?;
+			let node = entry.path.trim_start_matches( "/dev/" ).to_string();
+			Ok( Box::new( HidrawDevice { file, node } ) )
+		}
+	}
+
+	struct HidrawDevice {
+		file: File,
+		node: String,		// e.g. hidraw3
+	}
+
+	impl InputDevice for HidrawDevice {
+		fn product_string( &self ) -> Result<Option<String>, String> {
+			Ok( uevent_value( &self.node, "HID_NAME" ) )
+		}
+
+		fn read_timeout( &self, buff: &mut [u8], timeout: i32 ) -> Result<usize, String> {
+			let mut fds = libc::pollfd {
+				fd: self.file.as_raw_fd(),
+				events: libc::POLLIN,
+				revents: 0,
+			};
+			// safety: fds is a single, valid pollfd for the duration of the call
+			let ready = unsafe { libc::poll( &mut fds, 1, timeout ) };
+			if ready < 0 {
+				return Err( std::io::Error::last_os_error().to_string() );
+			}
+			if ready == 0 {
+				return Ok( 0 );
+			}
+			if fds.revents & (libc::POLLERR | libc::POLLHUP | libc::POLLNVAL) != 0 {
+				return Err( format!("/dev/{} disconnected", self.node) );
+			}
+			(&self.file).read( buff ).map_err( |err| err.to_string() )
+		}
+
+		fn report_descriptor( &self, buff: &mut [u8] ) -> Result<usize, String> {
+			let desc = std::fs::read( format!("{}/{}/device/report_descriptor", SYS_CLASS, self.node) )
+				.map_err( |err| err.to_string() )?;
+			let len = desc.len().min( buff.len() );
+			buff[ ..len ].copy_from_slice( &desc[ ..len ] );
+			Ok( len )
+		}
+	}
+
+	/// HID_ID=0003:00003344:0000C0CC -> (0x3344, 0xC0CC)
+	fn uevent_ids( node: &str ) -> Option<(u16, u16)> {
+		let id = uevent_value( node, "HID_ID" )?;
+		let parts: Vec<&str> = id.split( ':' ).collect();
+		if parts.len() != 3 { return None; }
+		let vid = u32::from_str_radix( parts[ 1 ], 16 ).ok()?;
+		let pid = u32::from_str_radix( parts[ 2 ], 16 ).ok()?;
+		Some( (vid as u16, pid as u16) )
+	}
+
+	fn uevent_value( node: &str, key: &str ) -> Option<String> {
+		let uevent = std::fs::read_to_string( format!("{}/{}/device/uevent", SYS_CLASS, node) ).ok()?;
+		uevent.lines()
+			.filter_map( |line| line.split_once( '=' ) )
+			.find( |(k, _)| *k == key )
+			.map( |(_, v)| v.to_string() )
+	}
+
+	/// USB devices sit below their interface, e.g. .../1-2:1.0/0003:3344:C0CC.0005
+	fn interface_number( node: &str ) -> Option<i32> {
+		let device = std::fs::canonicalize( format!("{}/{}/device", SYS_CLASS, node) ).ok()?;
+		let interface = device.parent()?.file_name()?.to_string_lossy().to_string();
+		if !interface.contains( ':' ) { return None; }
+		interface.rsplit( '.' ).next()?.parse::<i32>().ok()
+	}
+
+	/// the top level usage page, which is how hidapi reports it on Linux
+	fn first_usage_page( desc: &[u8] ) -> u16 {
+		match desc {
+			[0x05, page, ..] => *page as u16,
+			[0x06, lo, hi, ..] => u16::from_le_bytes( [*lo, *hi] ),
+			_ => 0,
+		}
+	}
+}
+
+/* ******************************************************************************* */
+/* Mock, for testing */
+/* ******************************************************************************* */
+pub mod mock {
+	use super::{DeviceEntry, InputBackend, InputDevice, };
+	use std::collections::VecDeque;
+	use std::sync::{Arc, Mutex, };
+	#[cfg(not(feature = "single-threaded"))]
+	use std::thread;
+	#[cfg(not(feature = "single-threaded"))]
+	use std::time::{Duration, Instant, };
+
+	/// Reports waiting to be read from a mock device.  Clones share the same queue,
+	/// so a test can keep one and feed the device after it has been opened.
+	#[derive(Clone, Debug, Default)]
+	pub struct MockScript {
+		state: Arc<Mutex<MockState>>,
+	}
+
+	#[derive(Debug, Default)]
+	struct MockState {
+		reports: VecDeque<Vec<u8>>,
+		error: Option<String>,
+	}
+
+	impl MockScript {
+		pub fn push_report( &self, report: &[u8] ) {
+			self.state.lock().unwrap().reports.push_back( report.to_vec() );
+		}
+
+		/// make every read fail (e.g. "device unplugged"), None to recover
+		pub fn set_error( &self, error: Option<String> ) {
+			self.state.lock().unwrap().error = error;
+		}
+
+		pub fn pending( &self ) -> usize {
+			self.state.lock().unwrap().reports.len()
+		}
+
+		/// wait for a reader thread to take the reports pushed, and send them on; without
+		/// reader threads they are read by the next poll, so there is nothing to wait for
+		pub fn settle( &self ) {
+			#[cfg(not(feature = "single-threaded"))] {
+				let started = Instant::now();
+				while self.pending() > 0 && started.elapsed() < Duration::from_secs( 1 ) {
+					thread::sleep( Duration::from_millis( 1 ) );
+				}
+				thread::sleep( Duration::from_millis( 10 ) );
+			}
+		}
+	}
+
+	#[derive(Clone, Debug)]
+	pub struct MockDevice {
+		pub entry: DeviceEntry,
+		pub product: Option<String>,
+		pub descriptor: Vec<u8>,
+		pub script: MockScript,
+	}
+
+	impl MockDevice {
+		pub fn new( vid: u16, pid: u16, product: &str ) -> MockDevice {
+			MockDevice {
+				entry: DeviceEntry {
+					vid,
+					pid,
+					usage_page: 1,
+					path: format!("mock:{:04x}:{:04x}", vid, pid),
+					name: Some( product.to_string() ),
+					phys: None,
+					serial: None,
+					interface_number: None,
+				},
+				product: Some( product.to_string() ),
+				descriptor: Vec::new(),
+				script: MockScript::default(),
+			}
+		}
+
+		pub fn with_descriptor( mut self, descriptor: &[u8] ) -> MockDevice {
+			self.descriptor = descriptor.to_vec();
+			self
+		}
+
+		/// a serial number, to tell identical mock devices apart
+		pub fn with_serial( mut self, serial: &str ) -> MockDevice {
+			self.entry.serial = Some( serial.to_string() );
+			self.entry.path = format!("{}:{}", self.entry.path, serial);
+			self
+		}
+	}
+
+	impl InputDevice for MockDevice {
+		fn product_string( &self ) -> Result<Option<String>, String> {
+			Ok( self.product.clone() )
+		}
+
+		fn read_timeout( &self, buff: &mut [u8], _timeout: i32 ) -> Result<usize, String> {
+			let mut state = self.script.state.lock().unwrap();
+			if let Some( err ) = &state.error {
+				return Err( err.clone() );
+			}
+			match state.reports.pop_front() {
+				Some( report ) => {
+					let len = report.len().min( buff.len() );
+					buff[ ..len ].copy_from_slice( &report[ ..len ] );
+					Ok( len )
+				}
+				None => {	Ok( 0 )	}		// nothing waiting, as a timeout
+			}
+		}
+
+		fn report_descriptor( &self, buff: &mut [u8] ) -> Result<usize, String> {
+			if self.descriptor.is_empty() {
+				return Err( "no report descriptor".to_string() );
+			}
+			let len = self.descriptor.len().min( buff.len() );
+			buff[ ..len ].copy_from_slice( &self.descriptor[ ..len ] );
+			Ok( len )
+		}
+	}
+
+	#[derive(Clone, Debug, Default)]
+	pub struct MockBackend {
+		pub devices: Vec<MockDevice>,
+	}
+
+	impl MockBackend {
+		pub fn new() -> MockBackend {
+			MockBackend::default()
+		}
+
+		/// add a device, returns the script used to feed it reports
+		pub fn add_device( &mut self, device: MockDevice ) -> MockScript {
+			let script = device.script.clone();
+			self.devices.push( device );
+			script
+		}
+	}
+
+	impl InputBackend for MockBackend {
+		fn refresh( &mut self ) -> Result<(), String> {
+			Ok( () )
+		}
+
+		fn devices( &self ) -> Vec<DeviceEntry> {
+			self.devices.iter().map( |d| d.entry.clone() ).collect()
+		}
+
+		fn open( &self, entry: &DeviceEntry ) -> Result<Box<dyn InputDevice>, String> {
+			match self.devices.iter().find( |d| d.entry.path == entry.path ) {
+				Some( device ) => {	Ok( Box::new( device.clone() ) )	}
+				None => {	Err( format!("{} not found", entry.path) )	}
+			}
+		}
+	}
+}
+
+/* ******************************************************************************* */
+/* Tests */
+/* ******************************************************************************* */
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn entry( serial: &str, path: &str ) -> DeviceEntry {
+		DeviceEntry {
+			vid: 0x3344,
+			pid: 0x01F8,
+			usage_page: 1,
+			path: path.to_string(),
+			serial: Some( serial.to_string() ),
+			interface_number: Some( 0 ),
+			..DeviceEntry::default()
+		}
+	}
+
+	#[test]
+	fn parse_and_show() {
+		let key = DeviceKey::parse( "3344 01F8 serial=A1B2 interface=2" ).unwrap();
+		assert_eq!( (key.vid, key.pid, key.serial.as_deref(), key.interface_number), (0x3344, 0x01F8, Some( "A1B2" ), Some( 2 )) );
+		assert_eq!( key.to_string(), "3344 01f8 serial=A1B2 interface=2" );
+		assert!( DeviceKey::parse( "3344 01F8 A1B2" ).is_err() );
+		assert!( DeviceKey::parse( "3344 01F8 colour=red" ).is_err() );
+		assert!( DeviceKey::parse( "33X4 01F8" ).is_err() );
+	}
+
+	#[test]
+	fn fields_round_trip() {
+		let key = DeviceKey {
+			path: Some( "/dev/hidraw3".to_string() ),
+			name: Some( "VPC Stick = MT-50".to_string() ),
+			..DeviceKey::new( 0x3344, 0x01F8 )
+		};
+		assert_eq!( DeviceKey::from_fields( &key.to_fields() ), Ok( key ) );
+	}
+
+	#[test]
+	fn matching() {
+		let a = entry( "A1", "/dev/hidraw1" );
+		let b = entry( "B2", "/dev/hidraw2" );
+		let any = DeviceKey::new( 0x3344, 0x01F8 );
+		assert!( any.matches( &a ) && any.matches( &b ) );
+		let by_serial = DeviceKey { serial: Some( "B2".to_string() ), ..any.clone() };
+		assert!( !by_serial.matches( &a ) && by_serial.matches( &b ) );
+		let by_path = DeviceKey { path: Some( "/dev/hidraw1".to_string() ), ..any.clone() };
+		assert!( by_path.matches( &a ) && !by_path.matches( &b ) );
+		let other_interface = DeviceKey { interface_number: Some( 1 ), ..any.clone() };
+		assert!( !other_interface.matches( &a ) );
+
+		// found by name, with any vid and pid; a vendor defined usage page is still a match
+		let named = DeviceKey { name: Some( "Pedals".to_string() ), ..DeviceKey::default() };
+		let pedals = DeviceEntry { name: Some( "Pedals".to_string() ), usage_page: 0xFF00, ..a.clone() };
+		assert!( named.matches( &pedals ) && !named.matches( &a ) );
+		assert!( !any.matches( &DeviceEntry { usage_page: 0xFF00, ..a } ) );
+	}
+}
+
+/* ******************************************************************************* *
+ *		*** End ***
+ * ******************************************************************************* */
@@ -0,0 +1,220 @@
+/* ******************************************************************************* */
+/* Buttons: where each is in a device's reports, its name, and the row it is shown in */
+/* ******************************************************************************* */
+/*
+ * A device's buttons are numbered from 1: eight to each byte of the buttons key, button 1
+ * in the lowest bit of the first byte, or in the order of the report descriptor (auto_map).
+ * Any button can be given by hand, e.g.
+ *	button_1 = name=Trigger, group=stick		button 1, wherever it is, with a name
+ *	button_33 = byte=40, bit=3, name=Pinky		byte 40, bit 3 (bit may be 8 or more, for the bytes after)
+ * Buttons are shown in rows, one for each group, in the order the groups are first used;
+ * a button without a group is in the row of its eight (group 1 for buttons 1 to 8, ...).
+ */
+use crate::descriptor::ReportLayout;
+
+/* ******************************************************************************* */
+/* Structures */
+/* ******************************************************************************* */
+
+/// Where a button's bit is
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ButtonSource {
+	Bit{ byte: usize, bit: usize },		// bit counts up from the lowest bit of byte, on into the next bytes
+	Descriptor( usize ),				// the report descriptor's buttons, from 0
+}
+
+/* ******************************************************************************* */
+/// One button, as given by a button_<n> key
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ButtonDef {
+	pub number: usize,					// n, from the key's name
+	pub source: Option<ButtonSource>,	// None: button n of the buttons key, or the descriptor
+	pub name: Option<String>,
+	pub group: Option<String>,
+}
+
+impl ButtonDef {
+	/// a button from an .ini file: number is n, from button_<n>
+	pub fn parse( number: usize, text: &str ) -> Result<ButtonDef, String> {
+		if number == 0 {
+			return Err( "buttons are numbered from 1".to_string() );
+		}
+		let mut def = ButtonDef { number, ..ButtonDef::default() };
+		let mut byte: Option<usize> = None;
+		let mut bit: Option<usize> = None;
+		for field in text.split( ',' ).map( |f| f.trim() ).filter( |f| !f.is_empty() ) {
+			let Some( (name, value) ) = field.split_once( '=' ) else {
+				return Err( format!("'{}' should be name=value", field) );
+			};
+			let value = value.trim();
+			match name.trim().to_lowercase().as_str() {
+				"byte" => {	byte = Some( value.parse::<usize>().map_err( |e| format!("byte {}", e) )? );	}
+				"bit" => {	bit = Some( value.parse::<usize>().map_err( |e| format!("bit {}", e) )? );	}
+				"name" => {	def.name = Some( value.to_string() ).filter( |v| !v.is_empty() );	}
+				"group" | "row" => {	def.group = Some( value.to_string() ).filter( |v| !v.is_empty() );	}
+				other => {	return Err( format!("unknown field '{}'", other) );	}
+			}
+		}
+		def.source = match (byte, bit) {
+			(Some( byte ), bit) => Some( ButtonSource::Bit{ byte, bit: bit.unwrap_or( 0 ) } ),
+			(None, Some( _ )) => {	return Err( "bit= needs a byte=".to_string() );	}
+			(None, None) => None,
+		};
+		Ok( def )
+	}
+}
+
+/* ******************************************************************************* */
+/// A button in a device's report
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Button {
+	pub number: usize,
+	pub name: Option<String>,
+	pub group: String,
+	pub pressed: bool,		// at any time since the last frame, so short taps are seen
+}
+
+impl Button {
+	/// the name if given, else "Button n"
+	pub fn label( &self ) -> String {
+		match &self.name {
+			Some( name ) => format!("{}: {}", self.number, name),
+			None => format!("Button {}", self.number),
+		}
+	}
+}
+
+/* ******************************************************************************* */
+/// every button of a device, by number: those of the buttons key's bytes (or the report
+/// descriptor's, when there are none), with those given by button_<n> keys in place or added
+pub fn sources( bytes: &[usize], defs: &[ButtonDef], layout: Option<&ReportLayout> ) -> Vec<(usize, ButtonSource)> {
+	let mut ret: Vec<(usize, ButtonSource)> = match (bytes.is_empty(), layout) {
+		(false, _) => bytes.iter().enumerate()
+			.flat_map( |(n, byte)| (0..8).map( move |bit| (n * 8 + bit + 1, ButtonSource::Bit{ byte: *byte, bit }) ) )
+			.collect(),
+		(true, Some( layout )) => (0..layout.buttons().len()).map( |n| (n + 1, ButtonSource::Descriptor( n )) ).collect(),
+		(true, None) => Vec::new(),
+	};
+	for def in defs {
+		if let Some( source ) = def.source {
+			match ret.iter_mut().find( |(n, _)| *n == def.number ) {
+				Some( (_, s) ) => {	*s = source;	}
+				None => {	ret.push( (def.number, source) );	}
+			}
+		}
+	}
+	ret.sort_by_key( |(n, _)| *n );
+	ret
+}
+
+/// whether each button is pressed, in the order of sources; None for a descriptor's button
+/// that is not in this report (it is in a report with another id), which is as it was
+pub fn read( sources: &[(usize, ButtonSource)], layout: Option<&ReportLayout>, buff: &[u8] ) -> Vec<Option<bool>> {
+	let fields = layout.map( |l| l.buttons() ).unwrap_or_default();
+	let uses_report_ids = layout.is_some_and( |l| l.uses_report_ids );
+	sources.iter().map( |(_, source)| match *source {
+		ButtonSource::Bit{ byte, bit } => {
+			Some( buff.get( byte + bit / 8 ).is_some_and( |b| b & (1 << (bit % 8)) != 0 ) )
+		}
+		ButtonSource::Descriptor( n ) => {
+			match fields.get( n ) {
+				Some( f ) => f.read( buff, uses_report_ids ).map( |v| v != 0 ),
+				None => Some( false ),
+			}
+		}
+	}).collect()
+}
+
+/// a button, named and grouped as in the button_<n> keys
+pub fn button( number: usize, defs: &[ButtonDef], pressed: bool ) -> Button {
+	let def = defs.iter().find( |d| d.number == number );
+	Button {
+		number,
+		name: def.and_then( |d| d.name.clone() ),
+		group: def.and_then( |d| d.group.clone() ).unwrap_or_else( || ((number - 1) / 8 + 1).to_string() ),
+		pressed,
+	}
+}
+
+/// the groups of a list of buttons, in the order they are first used: the rows to show
+pub fn groups( buttons: &[Button] ) -> Vec<&str> {
+	let mut ret: Vec<&str> = Vec::new();
+	for b in buttons {
+		if !ret.contains( &b.group.as_str() ) {
+			ret.push( &b.group );
+		}
+	}
+	ret
+}
+
+/* ******************************************************************************* */
+/* Tests */
+/* ******************************************************************************* */
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// report 1 has buttons 1 to 8, report 2 buttons 9 to 12 and a hat
+	const TWO_REPORTS: &[u8] = &[
+		0x05, 0x01, 0x09, 0x04, 0xA1, 0x01,
+		0x85, 0x01, 0x05, 0x09, 0x19, 0x01, 0x29, 0x08, 0x15, 0x00, 0x25, 0x01, 0x75, 0x01, 0x95, 0x08, 0x81, 0x02,
+		0x85, 0x02, 0x05, 0x09, 0x19, 0x09, 0x29, 0x0C, 0x15, 0x00, 0x25, 0x01, 0x75, 0x01, 0x95, 0x04, 0x81, 0x02,
+		0x05, 0x01, 0x09, 0x39, 0x15, 0x00, 0x25, 0x07, 0x75, 0x04, 0x95, 0x01, 0x81, 0x42,
+		0xC0,
+	];
+
+	#[test]
+	fn parse() {
+		let def = ButtonDef::parse( 33, "byte=40, bit=3, name=Pinky, row=stick" ).unwrap();
+		assert_eq!( def, ButtonDef {
+			number: 33,
+			source: Some( ButtonSource::Bit{ byte: 40, bit: 3 } ),
+			name: Some( "Pinky".to_string() ),
+			group: Some( "stick".to_string() ),
+		} );
+		assert_eq!( ButtonDef::parse( 1, "name=Trigger" ).unwrap().source, None );
+		for text in [ "bit=3", "byte=x", "colour=red", "name" ] {
+			assert!( ButtonDef::parse( 1, text ).is_err(), "{}", text );
+		}
+		assert!( ButtonDef::parse( 0, "" ).is_err() );
+	}
+
+	#[test]
+	fn sources_and_labels() {
+		let defs = vec![
+			ButtonDef::parse( 2, "byte=9, bit=12" ).unwrap(),
+			ButtonDef::parse( 20, "byte=7, bit=0, name=Pinky" ).unwrap(),
+		];
+		let sources = sources( &[ 5, 6 ], &defs, None );
+		assert_eq!( sources.len(), 17 );
+		assert_eq!( sources[ 0 ], (1, ButtonSource::Bit{ byte: 5, bit: 0 }) );
+		assert_eq!( sources[ 1 ], (2, ButtonSource::Bit{ byte: 9, bit: 12 }) );
+		assert_eq!( sources[ 16 ], (20, ButtonSource::Bit{ byte: 7, bit: 0 }) );
+
+		let pressed = read( &sources, None, &[ 1, 0, 0, 0, 0, 0b1, 0b1000_0000, 0b1, 0, 0, 0b1_0000 ] );
+		let pressed: Vec<usize> = sources.iter().zip( pressed ).filter( |(_, p)| *p == Some( true ) ).map( |((n, _), _)| *n ).collect();
+		assert_eq!( pressed, vec![ 1, 2, 16, 20 ] );
+
+		let buttons: Vec<Button> = [ 1, 9, 20 ].iter().map( |n| button( *n, &defs, false ) ).collect();
+		let labels: Vec<String> = buttons.iter().map( |b| b.label() ).collect();
+		assert_eq!( labels, vec![ "Button 1", "Button 9", "20: Pinky" ] );
+		assert_eq!( groups( &buttons ), vec![ "1", "2", "3" ] );
+	}
+
+	#[test]
+	fn buttons_in_other_reports_are_kept() {
+		let layout = ReportLayout::parse( TWO_REPORTS ).unwrap();
+		let sources = sources( &[], &[], Some( &layout ) );
+		assert_eq!( sources.len(), 12 );
+		let first = read( &sources, Some( &layout ), &[ 1, 0b1000_0001 ] );
+		assert_eq!( first[ ..8 ], [ Some( true ), Some( false ), Some( false ), Some( false ), Some( false ), Some( false ), Some( false ), Some( true ) ] );
+		assert_eq!( first[ 8.. ], [ None, None, None, None ] );
+		let second = read( &sources, Some( &layout ), &[ 2, 0b0000_0010 ] );
+		assert_eq!( second[ ..8 ], [ None; 8 ] );
+		assert_eq!( second[ 8.. ], [ Some( false ), Some( true ), Some( false ), Some( false ) ] );
+	}
+}
+
+/* ******************************************************************************* *
+ *		*** End ***
+ * ******************************************************************************* */
@@ -0,0 +1,348 @@
+/* ******************************************************************************* */
+/* Calibration wizard: finds an axis' range, and a device's buttons, by watching it */
+/* ******************************************************************************* */
+/*
+ * The user is asked, step by step, to:
+ *	leave the stick centred				centre of each axis, and which bits are noise
+ *	move every axis through its range	min and max of each axis
+ *	twist fully left and right			min and max of the twist axis (and any other)
+ *	press each button in turn			which byte and bit each button sets
+ * The results are written to the device's [section] of the .ini file, keeping
+ * anything else already set there (deadzones, curves, button names, comments, ...):
+ *	x_calibration = min=12, centre=515, max=1010
+ *	button_1 = byte=5, bit=0					numbered in the order pressed
+ */
+use crate::device::{AxisSample, DeviceInfo, DeviceManager, Message, };
+use crate::ini_file;
+
+/* ******************************************************************************* */
+/* Constants */
+
+// an axis must move at least this part of its range to be calibrated
+const MIN_TRAVEL: f64 = 0.05;
+
+/* ******************************************************************************* */
+/* Structures */
+/* ******************************************************************************* */
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Step {
+	Centre,
+	Range,
+	Twist,
+	Buttons,
+	Review,
+}
+
+/* ******************************************************************************* */
+/// What has been seen of one axis
+#[derive(Clone, Debug)]
+pub struct AxisRange {
+	pub axis: &'static str,
+	pub value: i32,
+	pub min: i32,
+	pub max: i32,
+	centred: bool,
+	logical_min: i32,
+	logical_max: i32,
+	centre_sum: i64,
+	centre_count: i64,
+}
+
+impl AxisRange {
+	fn new( sample: &AxisSample ) -> AxisRange {
+		AxisRange {
+			axis: sample.axis,
+			value: sample.value,
+			min: sample.value,
+			max: sample.value,
+			centred: sample.centred,
+			logical_min: sample.min,
+			logical_max: sample.max,
+			centre_sum: 0,
+			centre_count: 0,
+		}
+	}
+
+	pub fn centre( &self ) -> Option<i32> {
+		match self.centre_count {
+			0 => None,
+			n => Some( (self.centre_sum / n) as i32 ),
+		}
+	}
+
+	/// moved far enough to be worth calibrating
+	pub fn moved( &self ) -> bool {
+		let range = self.logical_max as f64 - self.logical_min as f64;
+		range > 0.0 && (self.max as f64 - self.min as f64) / range >= MIN_TRAVEL
+	}
+}
+
+/* ******************************************************************************* */
+/// Calibration of one device, a step at a time
+pub struct Wizard {
+	pub device: DeviceInfo,
+	pub step: Step,
+	pub axes: Vec<AxisRange>,
+	pub buttons: Vec<(usize, u8)>,	// byte, bit; in the order pressed
+	baseline: Vec<u8>,				// report with the stick centred, nothing pressed
+	noise: Vec<u8>,					// bits that changed before the buttons step, not buttons
+}
+
+impl Wizard {
+	pub fn new( device: DeviceInfo ) -> Wizard {
+		Wizard {
+			device,
+			step: Step::Centre,
+			axes: Vec::new(),
+			buttons: Vec::new(),
+			baseline: Vec::new(),
+			noise: Vec::new(),
+		}
+	}
+
+	pub fn instruction( &self ) -> &'static str {
+		match self.step {
+			Step::Centre => "Leave the stick centred, and press nothing, then click Next",
+			Step::Range => "Move every axis through its full range, several times, then click Next",
+			Step::Twist => "Twist fully left, then fully right, then click Next",
+			Step::Buttons => "Press each button in turn, in the order you number them, then click Next",
+			Step::Review => "Check the results, then click Save",
+		}
+	}
+
+	/// look at the device's latest report; call every frame
+	pub fn sample( &mut self, devices: &DeviceManager ) {
+		let report = match devices.device( &self.device.key ) {
+			Some( dr ) => {	dr.report.clone()	}
+			None => {	return;	}
+		};
+		if report.is_empty() { return; }
+		self.sample_axes( &devices.raw_axes( &self.device.key ) );
+		self.sample_report( &report );
+	}
+
+	fn sample_axes( &mut self, samples: &[AxisSample] ) {
+		for sample in samples {
+			let axis = match self.axes.iter_mut().find( |a| a.axis == sample.axis ) {
+				Some( a ) => a,
+				None => {
+					self.axes.push( AxisRange::new( sample ) );
+					self.axes.last_mut().unwrap()
+				}
+			};
+			axis.value = sample.value;
+			match self.step {
+				Step::Centre => {
+					axis.centre_sum += sample.value as i64;
+					axis.centre_count += 1;
+					axis.min = sample.value;
+					axis.max = sample.value;
+				}
+				Step::Range | Step::Twist => {
+					axis.min = axis.min.min( sample.value );
+					axis.max = axis.max.max( sample.value );
+				}
+				_ => {}
+			}
+		}
+	}
+
+	fn sample_report( &mut self, report: &[u8] ) {
+		match self.step {
+			Step::Centre | Step::Range | Step::Twist => {
+				if self.baseline.len() != report.len() {
+					self.baseline = report.to_vec();
+					self.noise = vec![0; report.len()];
+				}
+				for (n, b) in report.iter().enumerate() {
+					self.noise[ n ] |= b ^ self.baseline[ n ];
+				}
+			}
+			Step::Buttons => {
+				// a different report (length, or report id) says nothing about these buttons
+				if report.len() != self.baseline.len() || report.first() != self.baseline.first() {
+					return;
+				}
+				for (n, b) in report.iter().enumerate() {
+					let changed = (b ^ self.baseline[ n ]) & !self.noise[ n ];
+					for bit in 0..8u8 {
+						if changed & (1 << bit) != 0 && !self.buttons.contains( &(n, bit) ) {
+							self.buttons.push( (n, bit) );
+						}
+					}
+				}
+			}
+			Step::Review => {}
+		}
+	}
+
+	pub fn next( &mut self ) {
+		self.step = match self.step {
+			Step::Centre => Step::Range,
+			Step::Range => Step::Twist,
+			Step::Twist => Step::Buttons,
+			_ => Step::Review,
+		};
+	}
+
+	/// the .ini file keys and values found, merged with those already in the devices' .ini file
+	pub fn results( &self, devices: &DeviceManager ) -> Vec<(String, String)> {
+		let mut ret: Vec<(String, String)> = Vec::new();
+		for axis in self.axes.iter().filter( |a| a.moved() ) {
+			let key = format!("{}_calibration", axis.axis);
+			let mut fields = vec![ format!("min={}", axis.min) ];
+			if axis.centred {
+				if let Some( centre ) = axis.centre() {
+					fields.push( format!("centre={}", centre.clamp( axis.min, axis.max )) );
+				}
+			}
+			fields.push( format!("max={}", axis.max) );
+			// keep deadzones, curves, ... already set by hand
+			fields.append( &mut self.kept( devices, &key, &["min", "centre", "center", "max"] ) );
+			ret.push( (key, fields.join( ", " )) );
+		}
+		// numbered in the order pressed, keeping names and groups already given
+		for (n, (byte, bit)) in self.buttons.iter().enumerate() {
+			let key = format!("button_{}", n + 1);
+			let mut fields = vec![ format!("byte={}", byte), format!("bit={}", bit) ];
+			fields.append( &mut self.kept( devices, &key, &["byte", "bit"] ) );
+			ret.push( (key, fields.join( ", " )) );
+		}
+		ret
+	}
+
+	/// the fields of key already in the .ini file, other than those the wizard sets
+	fn kept( &self, devices: &DeviceManager, key: &str, replaced: &[&str] ) -> Vec<String> {
+		let Some( old ) = devices.config_file().and_then( |path| ini_file::get_value( path, &self.device.section, key ).ok().flatten() ) else {
+			return Vec::new();
+		};
+		old.split( ',' )
+			.map( |f| f.trim() )
+			.filter( |f| !f.is_empty() )
+			.filter( |f| !replaced.contains( &f.split( '=' ).next().unwrap_or( "" ).trim().to_lowercase().as_str() ) )
+			.map( String::from )
+			.collect()
+	}
+
+	/// write the results to the .ini file the devices were loaded from, and use them straight away
+	pub fn save( &self, devices: &mut DeviceManager ) -> Vec<Message> {
+		let mut ret: Vec<Message> = Vec::new();
+		let Some( path ) = devices.config_file().map( String::from ) else {
+			return vec![ Message::Err( "The devices were not loaded from a file, nothing saved".to_string() ) ];
+		};
+		for (key, value) in self.results( devices ) {
+			if let Err( err ) = ini_file::set_value( &path, &self.device.section, &key, &value ) {
+				ret.push( Message::Err( err ) );
+				continue;
+			}
+			ret.append( &mut devices.set_config_value( &self.device.key, &key, &value ) );
+		}
+		ret
+	}
+}
+
+/* ******************************************************************************* */
+/* Tests */
+/* ******************************************************************************* */
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::backend::mock::{MockBackend, MockDevice, };
+	use crate::hotplug::NoSource;
+	use crate::test_dir::TestDir;
+
+	const INI: &str = "[Stick]
+	vid = 3344
+	pid = 0001
+	auto_map = false
+	x_calibration = min=0, max=10, deadzone=0.05
+	button_2 = name=Pinky, group=hat
+";
+
+	/// devices loaded from their own copy of INI, kept while the TestDir is
+	fn devices( name: &str ) -> (DeviceManager, TestDir, String) {
+		let dir = TestDir::new( name );
+		let path = dir.write( "joystick_monitor.ini", INI );
+		let mut backend = MockBackend::new();
+		backend.add_device( MockDevice::new( 0x3344, 0x0001, "Stick" ) );
+		let mut devices = DeviceManager::new();
+		devices.set_backend( Box::new( backend ) );
+		devices.set_hotplug( Box::new( NoSource ) );
+		devices.load_file( &path );
+		(devices, dir, path)
+	}
+
+	fn sample( axis: &'static str, value: i32 ) -> AxisSample {
+		AxisSample { axis, value, min: 0, max: 1023, centred: true }
+	}
+
+	/// a wizard that has been through every step
+	fn wizard( devices: &DeviceManager ) -> Wizard {
+		let mut wizard = Wizard::new( devices.devices()[ 0 ].clone() );
+		for value in [ 500, 510, 520 ] {
+			wizard.sample_axes( &[ sample( "x", value ), sample( "y", 512 ) ] );
+		}
+		// byte 1 jitters
+		wizard.sample_report( &[ 1, 0x80, 0, 0 ] );
+		wizard.sample_report( &[ 1, 0x81, 0, 0 ] );
+		wizard.next();
+		for value in [ 100, 900, 512 ] {
+			wizard.sample_axes( &[ sample( "x", value ), sample( "y", 515 ) ] );
+		}
+		wizard.next();
+		wizard.next();
+		assert_eq!( wizard.step, Step::Buttons );
+		wizard.sample_report( &[ 1, 0x80, 0, 0b100 ] );
+		wizard.sample_report( &[ 1, 0x81, 0b1, 0b100 ] );
+		// another report id says nothing about these buttons
+		wizard.sample_report( &[ 2, 0xFF, 0xFF, 0xFF ] );
+		wizard.next();
+		wizard
+	}
+
+	#[test]
+	fn steps() {
+		let (devices, _dir, _) = devices( "calibrate_steps" );
+		let wizard = wizard( &devices );
+		assert_eq!( wizard.step, Step::Review );
+		let x = &wizard.axes[ 0 ];
+		assert_eq!( (x.min, x.centre(), x.max, x.moved()), (100, Some( 510 ), 900, true) );
+		assert!( !wizard.axes[ 1 ].moved() );
+		assert_eq!( wizard.buttons, vec![ (3, 2), (2, 0) ] );
+	}
+
+	#[test]
+	fn results_keep_what_is_there() {
+		let (devices, _dir, _) = devices( "calibrate_results" );
+		let wizard = wizard( &devices );
+		let results = wizard.results( &devices );
+		assert_eq!( results, vec![
+			("x_calibration".to_string(), "min=100, centre=510, max=900, deadzone=0.05".to_string()),
+			("button_1".to_string(), "byte=3, bit=2".to_string()),
+			("button_2".to_string(), "byte=2, bit=0, name=Pinky, group=hat".to_string()),
+		] );
+	}
+
+	#[test]
+	fn save() {
+		let (mut devices, _dir, path) = devices( "calibrate_save" );
+		let wizard = wizard( &devices );
+		let errors: Vec<Message> = wizard.save( &mut devices ).into_iter().filter( |m| matches!( m, Message::Err( _ ) ) ).collect();
+		let saved = ini_file::get_value( &path, "Stick", "button_1" );
+		let text = std::fs::read_to_string( &path ).unwrap();
+		assert!( errors.is_empty() );
+		assert_eq!( saved, Ok( Some( "byte=3, bit=2".to_string() ) ) );
+		assert!( text.contains( "\tbutton_2 = byte=2, bit=0, name=Pinky, group=hat\n" ) );
+		let defs = devices.joysticks()[ 0 ].button_defs();
+		assert_eq!( defs.iter().find( |d| d.number == 2 ).and_then( |d| d.name.as_deref() ), Some( "Pinky" ) );
+
+		// nowhere to save to
+		let mut devices = DeviceManager::new();
+		assert!( matches!( wizard.save( &mut devices ).as_slice(), [ Message::Err( _ ) ] ) );
+	}
+}
+
+/* ******************************************************************************* *
+ *		*** End ***
+ * ******************************************************************************* */
@@ -0,0 +1,443 @@
+/* ******************************************************************************* */
+/* Capture files: record raw reports, and replay them */
+/* ******************************************************************************* */
+/*
+ * File layout, all numbers little endian:
+ *	header:	"JSMCAP" then a version byte
+ *	then records, each:
+ *		kind	u8		0 report, 1 report descriptor, 2 product name
+ *		time	u64		microseconds since recording started
+ *		vid		u16
+ *		pid		u16
+ *		len		u16
+ *		data	len bytes
+ *	from version 2, vid and pid are followed by the rest of the device key:
+ *		key_len	u16
+ *		key		key_len bytes, "name=value" lines (see DeviceKey::to_fields)
+ *	so identical devices, told apart by serial or path, replay as two devices
+ *
+ * Replay is an input backend, so recorded reports go through the same path as live
+ * ones (read_device, make_device_report, do_routes) and the screen redraws as it did.
+ */
+use crate::backend::{DeviceEntry, DeviceKey, InputBackend, InputDevice, };
+use std::fs::File;
+use std::io::{BufWriter, Read, Write, };
+use std::sync::{Arc, Mutex, };
+use std::time::{Duration, Instant, };
+
+/* ******************************************************************************* */
+/* Constants */
+
+const MAGIC: &[u8; 6] = b"JSMCAP";
+const VERSION: u8 = 2;
+const RECORD_HEADER_LEN: usize = 15;		// version 1, and version 2 up to the key
+
+const KIND_REPORT: u8 = 0;
+const KIND_DESCRIPTOR: u8 = 1;
+const KIND_NAME: u8 = 2;
+
+/* ******************************************************************************* */
+/* Structures */
+/* ******************************************************************************* */
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct CaptureRecord {
+	kind: u8,
+	time: u64,			// microseconds
+	key: DeviceKey,
+	data: Vec<u8>,
+}
+
+/* ******************************************************************************* */
+/// Writes a capture file
+pub struct Recorder {
+	file: BufWriter<File>,
+	start: Instant,
+}
+
+impl Recorder {
+	pub fn create( path: &str ) -> Result<Recorder, String> {
+		let file = File::create( path ).map_err( |err| format!("{} {}", path, err) )?;
+		let mut ret = Recorder { file: BufWriter::new( file ), start: Instant::now() };
+		ret.write_bytes( MAGIC )?;
+		ret.write_bytes( &[VERSION] )?;
+		Ok( ret )
+	}
+
+	/// a report, with when it was read from the device (not when it reached the recorder)
+	pub fn report( &mut self, key: &DeviceKey, at: Instant, report: &[u8] ) -> Result<(), String> {
+		self.record( KIND_REPORT, key, at, report )
+	}
+
+	pub fn descriptor( &mut self, key: &DeviceKey, descriptor: &[u8] ) -> Result<(), String> {
+		self.record( KIND_DESCRIPTOR, key, Instant::now(), descriptor )
+	}
+
+	pub fn name( &mut self, key: &DeviceKey, name: &str ) -> Result<(), String> {
+		self.record( KIND_NAME, key, Instant::now(), name.as_bytes() )
+	}
+
+	pub fn flush( &mut self ) -> Result<(), String> {
+		self.file.flush().map_err( |err| err.to_string() )
+	}
+
+	fn record( &mut self, kind: u8, key: &DeviceKey, at: Instant, data: &[u8] ) -> Result<(), String> {
+		let len = data.len().min( u16::MAX as usize );
+		let time = at.saturating_duration_since( self.start ).as_micros() as u64;
+		let fields = key.to_fields();
+		let key_len = fields.len().min( u16::MAX as usize );
+		let mut header: Vec<u8> = Vec::with_capacity( RECORD_HEADER_LEN + key_len + 2 );
+		header.push( kind );
+		header.extend_from_slice( &time.to_le_bytes() );
+		header.extend_from_slice( &key.vid.to_le_bytes() );
+		header.extend_from_slice( &key.pid.to_le_bytes() );
+		header.extend_from_slice( &(key_len as u16).to_le_bytes() );
+		header.extend_from_slice( &fields.as_bytes()[ ..key_len ] );
+		header.extend_from_slice( &(len as u16).to_le_bytes() );
+		self.write_bytes( &header )?;
+		self.write_bytes( &data[ ..len ] )
+	}
+
+	fn write_bytes( &mut self, bytes: &[u8] ) -> Result<(), String> {
+		self.file.write_all( bytes ).map_err( |err| err.to_string() )
+	}
+}
+
+/* ******************************************************************************* */
+/// Replays a capture file, one device for each device key found in it
+pub struct ReplayBackend {
+	devices: Vec<ReplayDevice>,
+}
+
+impl ReplayBackend {
+	/// speed: 1.0 plays at the recorded speed, 2.0 twice as fast, ...
+	/// looped: start again from the beginning at the end of the file
+	pub fn load( path: &str, speed: f64, looped: bool ) -> Result<ReplayBackend, String> {
+		if speed <= 0.0 {
+			return Err( format!("Replay speed must be more than zero, not {}", speed) );
+		}
+		let mut bytes: Vec<u8> = Vec::new();
+		File::open( path )
+			.and_then( |mut f| f.read_to_end( &mut bytes ) )
+			.map_err( |err| format!("{} {}", path, err) )?;
+		let records = parse( &bytes ).map_err( |err| format!("{} {}", path, err) )?;
+
+		let clock = Arc::new( ReplayClock {
+			start: Instant::now(),
+			speed,
+			looped,
+			length: records.iter().map( |r| r.time ).max().unwrap_or( 0 ),
+		});
+
+		let mut devices: Vec<ReplayDevice> = Vec::new();
+		for record in records {
+			let n = match devices.iter().position( |d| d.key == record.key ) {
+				Some( n ) => n,
+				None => {
+					devices.push( ReplayDevice {
+						key: record.key.clone(),
+						number: devices.len(),
+						name: None,
+						descriptor: Vec::new(),
+						reports: Vec::new(),
+						clock: clock.clone(),
+						state: Arc::new( Mutex::new( ReplayState::default() ) ),
+					});
+					devices.len() - 1
+				}
+			};
+			let device = &mut devices[ n ];
+			match record.kind {
+				KIND_REPORT => {	device.reports.push( (record.time, record.data) );	}
+				KIND_DESCRIPTOR => {	device.descriptor = record.data;	}
+				KIND_NAME => {	device.name = Some( String::from_utf8_lossy( &record.data ).to_string() );	}
+				_ => {}		// from a later version, skip
+			}
+		}
+		// devices read on threads of their own are not recorded in time order
+		for device in &mut devices {
+			device.reports.sort_by_key( |(time, _)| *time );
+		}
+		Ok( ReplayBackend { devices } )
+	}
+}
+
+impl InputBackend for ReplayBackend {
+	fn refresh( &mut self ) -> Result<(), String> {
+		Ok( () )
+	}
+
+	fn devices( &self ) -> Vec<DeviceEntry> {
+		self.devices.iter()
+			.map( |d| DeviceEntry {
+				vid: d.key.vid,
+				pid: d.key.pid,
+				usage_page: 1,
+				path: d.path(),
+				// evdev devices were found by name and phys; the name read from the device
+				// is recorded separately, so the key's is kept for matching
+				name: d.key.name.clone().or( d.name.clone() ),
+				phys: d.key.phys.clone(),
+				serial: d.key.serial.clone(),
+				interface_number: d.key.interface_number,
+			})
+			.collect()
+	}
+
+	fn open( &self, entry: &DeviceEntry ) -> Result<Box<dyn InputDevice>, String> {
+		match self.devices.iter().find( |d| d.path() == entry.path ) {
+			Some( device ) => {	Ok( Box::new( device.clone() ) )	}
+			None => {	Err( format!("{} not in capture", entry.path) )	}
+		}
+	}
+}
+
+/* ******************************************************************************* */
+/// Time, as seen by every device in one replay
+#[derive(Debug)]
+struct ReplayClock {
+	start: Instant,
+	speed: f64,
+	looped: bool,
+	length: u64,		// microseconds, time of the latest record
+}
+
+impl ReplayClock {
+	/// position in the recording, and how many times it has looped
+	fn now( &self ) -> (u64, u64) {
+		let pos = (self.start.elapsed().as_micros() as f64 * self.speed) as u64;
+		if self.looped && self.length > 0 {
+			return (pos % (self.length + 1), pos / (self.length + 1));
+		}
+		(pos, 0)
+	}
+
+	/// how long until the recording reaches time
+	fn until( &self, time: u64 ) -> Duration {
+		let (pos, _) = self.now();
+		Duration::from_micros( (time.saturating_sub( pos ) as f64 / self.speed) as u64 )
+	}
+}
+
+#[derive(Debug, Default)]
+struct ReplayState {
+	next: usize,		// next report to send
+	lap: u64,
+}
+
+#[derive(Clone, Debug)]
+struct ReplayDevice {
+	key: DeviceKey,
+	number: usize,		// order first seen in the capture
+	name: Option<String>,
+	descriptor: Vec<u8>,
+	reports: Vec<(u64, Vec<u8>)>,	// time, report
+	clock: Arc<ReplayClock>,
+	state: Arc<Mutex<ReplayState>>,
+}
+
+impl ReplayDevice {
+	/// a device recorded with a path gets it back, so a section pinned by path still finds it
+	fn path( &self ) -> String {
+		match &self.key.path {
+			Some( path ) => {	path.clone()	}
+			None => {	format!("replay:{:04x}:{:04x}:{}", self.key.vid, self.key.pid, self.number)	}
+		}
+	}
+}
+
+impl InputDevice for ReplayDevice {
+	fn product_string( &self ) -> Result<Option<String>, String> {
+		Ok( self.name.clone() )
+	}
+
+	/// the report due now (or within timeout), as a live device would send it
+	fn read_timeout( &self, buff: &mut [u8], timeout: i32 ) -> Result<usize, String> {
+		let waited = Instant::now();
+		loop {
+			let wait: Duration;
+			{
+				let clock = &self.clock;
+				let mut state = self.state.lock().map_err( |err| err.to_string() )?;
+				let (pos, lap) = clock.now();
+				if lap != state.lap {
+					state.lap = lap;
+					state.next = 0;
+				}
+				let Some( (time, report) ) = self.reports.get( state.next ) else {
+					return Ok( 0 );		// end of the recording
+				};
+				if *time <= pos {
+					state.next += 1;
+					let len = report.len().min( buff.len() );
+					buff[ ..len ].copy_from_slice( &report[ ..len ] );
+					return Ok( len );
+				}
+				wait = clock.until( *time );
+			}
+			let left = match timeout {
+				t if t < 0 => wait,
+				t => Duration::from_millis( t as u64 ).saturating_sub( waited.elapsed() ),
+			};
+			if left.is_zero() {
+				return Ok( 0 );
+			}
+			std::thread::sleep( wait.min( left ) );
+		}
+	}
+
+	fn report_descriptor( &self, buff: &mut [u8] ) -> Result<usize, String> {
+		if self.descriptor.is_empty() {
+			return Err( "no report descriptor in capture".to_string() );
+		}
+		let len = self.descriptor.len().min( buff.len() );
+		buff[ ..len ].copy_from_slice( &self.descriptor[ ..len ] );
+		Ok( len )
+	}
+}
+
+/* ******************************************************************************* */
+
+fn parse( bytes: &[u8] ) -> Result<Vec<CaptureRecord>, String> {
+	if bytes.len() < MAGIC.len() + 1 || &bytes[ ..MAGIC.len() ] != MAGIC {
+		return Err( "is not a capture file".to_string() );
+	}
+	let version = bytes[ MAGIC.len() ];
+	if version > VERSION {
+		return Err( format!("capture version {} is newer than this program", version) );
+	}
+
+	let mut ret: Vec<CaptureRecord> = Vec::new();
+	let mut pos = MAGIC.len() + 1;
+	// each field is read with the length that comes before it; None when the file is cut short,
+	// e.g. the program was stopped while recording
+	let field = |pos: &mut usize| -> Option<Vec<u8>> {
+		if *pos + 2 > bytes.len() { return None; }
+		let len = u16::from_le_bytes( [bytes[ *pos ], bytes[ *pos + 1 ]] ) as usize;
+		let start = *pos + 2;
+		if start + len > bytes.len() { return None; }
+		*pos = start + len;
+		Some( bytes[ start..start + len ].to_vec() )
+	};
+	while pos + RECORD_HEADER_LEN <= bytes.len() {
+		let h = &bytes[ pos..pos + RECORD_HEADER_LEN - 2 ];
+		let kind = h[ 0 ];
+		let time = u64::from_le_bytes( [h[ 1 ], h[ 2 ], h[ 3 ], h[ 4 ], h[ 5 ], h[ 6 ], h[ 7 ], h[ 8 ]] );
+		let mut key = DeviceKey::new( u16::from_le_bytes( [h[ 9 ], h[ 10 ]] ),
+										u16::from_le_bytes( [h[ 11 ], h[ 12 ]] ) );
+		let mut next = pos + RECORD_HEADER_LEN - 2;
+		if version >= 2 {
+			let Some( fields ) = field( &mut next ) else { break; };
+			key = DeviceKey::from_fields( &String::from_utf8_lossy( &fields ) )?;
+		}
+		let Some( data ) = field( &mut next ) else { break; };
+		ret.push( CaptureRecord { kind, time, key, data } );
+		pos = next;
+	}
+	Ok( ret )
+}
+
+/* ******************************************************************************* */
+/* Tests */
+/* ******************************************************************************* */
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_dir::TestDir;
+
+	fn serial_key() -> DeviceKey {
+		DeviceKey { serial: Some( "A1".to_string() ), ..DeviceKey::new( 0x3344, 0x0001 ) }
+	}
+
+	#[test]
+	fn reports_keep_the_time_they_were_read() {
+		let dir = TestDir::new( "times" );
+		let path = dir.file( "times.cap" );
+		let mut recorder = Recorder::create( &path ).unwrap();
+		let start = recorder.start;
+		recorder.name( &serial_key(), "Test Stick" ).unwrap();
+		recorder.report( &serial_key(), start + Duration::from_millis( 5 ), &[ 1, 2, 3 ] ).unwrap();
+		recorder.report( &DeviceKey::new( 0x3344, 0x0001 ), start + Duration::from_millis( 2 ), &[ 4 ] ).unwrap();
+		recorder.flush().unwrap();
+		let bytes = std::fs::read( &path ).unwrap();
+
+		let records = parse( &bytes ).unwrap();
+		assert_eq!( records.len(), 3 );
+		assert_eq!( (records[ 0 ].kind, records[ 0 ].data.as_slice()), (KIND_NAME, "Test Stick".as_bytes()) );
+		assert_eq!( records[ 1 ], CaptureRecord { kind: KIND_REPORT, time: 5000, key: serial_key(), data: vec![ 1, 2, 3 ] } );
+		assert_eq!( (records[ 2 ].time, records[ 2 ].key.serial.clone()), (2000, None) );
+	}
+
+	#[test]
+	fn broken_files() {
+		assert_eq!( parse( b"JSM" ), Err( "is not a capture file".to_string() ) );
+		assert_eq!( parse( b"JSMCAP\x09" ), Err( "capture version 9 is newer than this program".to_string() ) );
+
+		// version 1: no device key; the last record is cut short, as when the program is stopped
+		let mut bytes: Vec<u8> = b"JSMCAP\x01".to_vec();
+		for len in [ 2u16, 4 ] {
+			bytes.push( KIND_REPORT );
+			bytes.extend_from_slice( &7u64.to_le_bytes() );
+			bytes.extend_from_slice( &[ 0x44, 0x33, 0x01, 0x00 ] );
+			bytes.extend_from_slice( &len.to_le_bytes() );
+			bytes.extend_from_slice( &[ 9, 9 ] );
+		}
+		let records = parse( &bytes ).unwrap();
+		assert_eq!( records, vec![ CaptureRecord { kind: KIND_REPORT, time: 7, key: DeviceKey::new( 0x3344, 0x0001 ), data: vec![ 9, 9 ] } ] );
+	}
+
+	#[test]
+	fn replay() {
+		let dir = TestDir::new( "replay" );
+		let path = dir.file( "replay.cap" );
+		let mut recorder = Recorder::create( &path ).unwrap();
+		let start = recorder.start;
+		recorder.descriptor( &serial_key(), &[ 0x05, 0x01 ] ).unwrap();
+		recorder.report( &serial_key(), start, &[ 1, 2 ] ).unwrap();
+		recorder.report( &serial_key(), start + Duration::from_secs( 60 ), &[ 3, 4 ] ).unwrap();
+		recorder.report( &DeviceKey::new( 0x3344, 0x0001 ), start, &[ 5 ] ).unwrap();
+		recorder.flush().unwrap();
+		drop( recorder );
+
+		assert!( ReplayBackend::load( &path, 0.0, false ).is_err() );
+		let replay = ReplayBackend::load( &path, 1.0, false ).unwrap();
+		let entries = replay.devices();
+		assert_eq!( entries.len(), 2 );
+		assert_eq!( (entries[ 0 ].serial.clone(), entries[ 1 ].serial.clone()), (Some( "A1".to_string() ), None) );
+
+		let device = replay.open( &entries[ 0 ] ).unwrap();
+		let mut buff = [0u8; 8];
+		assert_eq!( device.report_descriptor( &mut buff ), Ok( 2 ) );
+		assert_eq!( device.read_timeout( &mut buff, 0 ), Ok( 2 ) );
+		assert_eq!( buff[ ..2 ], [ 1, 2 ] );
+		// the next is a minute away
+		assert_eq!( device.read_timeout( &mut buff, 0 ), Ok( 0 ) );
+	}
+
+	#[test]
+	fn out_of_order() {
+		let dir = TestDir::new( "out_of_order" );
+		let path = dir.file( "out_of_order.cap" );
+		let mut recorder = Recorder::create( &path ).unwrap();
+		let start = recorder.start;
+		recorder.report( &serial_key(), start + Duration::from_secs( 60 ), &[ 2 ] ).unwrap();
+		recorder.report( &serial_key(), start, &[ 1 ] ).unwrap();
+		recorder.report( &DeviceKey::new( 0x3344, 0x0001 ), start + Duration::from_secs( 30 ), &[ 3 ] ).unwrap();
+		recorder.flush().unwrap();
+		drop( recorder );
+
+		// loops after the latest report, not the last written
+		let replay = ReplayBackend::load( &path, 1.0, true ).unwrap();
+		assert_eq!( replay.devices[ 0 ].clock.length, 60_000_000 );
+		let times: Vec<u64> = replay.devices[ 0 ].reports.iter().map( |(time, _)| *time ).collect();
+		assert_eq!( times, vec![ 0, 60_000_000 ] );
+		let device = replay.open( &replay.devices()[ 0 ] ).unwrap();
+		let mut buff = [0u8; 8];
+		assert_eq!( device.read_timeout( &mut buff, 0 ), Ok( 1 ) );
+		assert_eq!( buff[ 0 ], 1 );
+	}
+}
+
+/* ******************************************************************************* *
+ *		*** End ***
+ * ******************************************************************************* */
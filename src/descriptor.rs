@@ -0,0 +1,443 @@
+/* ******************************************************************************* */
+/* HID report descriptor parsing */
+/* ******************************************************************************* */
+/*
+ * Reads the report descriptor a HID device publishes about itself, and works out
+ * where each input (axis, button, hat) lives in the device's input reports.
+ * Only the parts of the HID specification needed to locate inputs are handled;
+ * output and feature reports are skipped (but still parsed, to keep report ids right).
+ */
+use std::collections::HashMap;
+
+/* ******************************************************************************* */
+/* Constants */
+
+pub const USAGE_PAGE_GENERIC_DESKTOP: u16 = 0x01;
+pub const USAGE_PAGE_BUTTON: u16 = 0x09;
+
+pub const USAGE_X: u16 = 0x30;
+pub const USAGE_Y: u16 = 0x31;
+pub const USAGE_Z: u16 = 0x32;
+pub const USAGE_RX: u16 = 0x33;
+pub const USAGE_RY: u16 = 0x34;
+pub const USAGE_RZ: u16 = 0x35;
+pub const USAGE_SLIDER: u16 = 0x36;
+pub const USAGE_HAT_SWITCH: u16 = 0x39;
+
+// item types
+const TYPE_MAIN: u8 = 0;
+const TYPE_GLOBAL: u8 = 1;
+const TYPE_LOCAL: u8 = 2;
+
+// main item tags
+const MAIN_INPUT: u8 = 0x8;
+const MAIN_OUTPUT: u8 = 0x9;
+const MAIN_FEATURE: u8 = 0xB;
+
+// global item tags
+const GLOBAL_USAGE_PAGE: u8 = 0x0;
+const GLOBAL_LOGICAL_MIN: u8 = 0x1;
+const GLOBAL_LOGICAL_MAX: u8 = 0x2;
+const GLOBAL_REPORT_SIZE: u8 = 0x7;
+const GLOBAL_REPORT_ID: u8 = 0x8;
+const GLOBAL_REPORT_COUNT: u8 = 0x9;
+const GLOBAL_PUSH: u8 = 0xA;
+const GLOBAL_POP: u8 = 0xB;
+
+// local item tags
+const LOCAL_USAGE: u8 = 0x0;
+const LOCAL_USAGE_MIN: u8 = 0x1;
+const LOCAL_USAGE_MAX: u8 = 0x2;
+
+const LONG_ITEM: u8 = 0xFE;
+
+// no report is anywhere near this long; a descriptor that says otherwise is broken
+const MAX_REPORT_BITS: usize = 0x10000 * 8;
+
+/* ******************************************************************************* */
+/* Structures */
+/* ******************************************************************************* */
+
+/// One input value found in the descriptor
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReportField {
+	pub report_id: u8,		// 0 when the device does not number its reports
+	pub usage_page: u16,
+	pub usage: u16,
+	pub bit_offset: usize,	// from the start of the report's data (after any report id)
+	pub bit_size: usize,
+	pub logical_min: i32,
+	pub logical_max: i32,
+}
+
+impl ReportField {
+	/// The raw value of this field in buff, or None if buff holds a different report.
+	/// buff is a report as read from the device, including the report id byte (if used)
+	pub fn read( &self, buff: &[u8], uses_report_ids: bool ) -> Option<i32> {
+		let mut start = 0;
+		if uses_report_ids {
+			if buff.is_empty() || buff[ 0 ] != self.report_id { return None; }
+			start = 8;
+		}
+		if self.bit_size == 0 || self.bit_size > 32 { return None; }
+		let first = start + self.bit_offset;
+		if (first + self.bit_size).div_ceil(8) > buff.len() { return None; }
+
+		// HID data is little endian, and fields need not start on a byte boundary
+		let mut raw: u32 = 0;
+		for n in 0..self.bit_size {
+			let bit = first + n;
+			if buff[ bit / 8 ] & (1 << (bit % 8)) != 0 {
+				raw |= 1 << n;
+			}
+		}
+
+		if self.logical_min < 0 && self.bit_size < 32 && raw & (1 << (self.bit_size - 1)) != 0 {
+			// sign extend
+			return Some( (raw | (u32::MAX << self.bit_size)) as i32 );
+		}
+		Some( raw as i32 )
+	}
+}
+
+/* ******************************************************************************* */
+/// Where the inputs of one device are found
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ReportLayout {
+	pub uses_report_ids: bool,
+	pub fields: Vec<ReportField>,	// input fields, in descriptor order
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct GlobalState {
+	usage_page: u16,
+	logical_min: i32,
+	logical_max: i32,
+	report_size: usize,
+	report_id: u8,
+	report_count: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+struct LocalState {
+	usages: Vec<u32>,		// extended usages: page in the high word
+	usage_min: Option<u32>,
+	usage_max: Option<u32>,
+}
+
+impl ReportLayout {
+	/// Parse a raw report descriptor
+	pub fn parse( desc: &[u8] ) -> Result<ReportLayout, String> {
+		let mut layout = ReportLayout::default();
+		let mut global = GlobalState::default();
+		let mut global_stack: Vec<GlobalState> = Vec::new();
+		let mut local = LocalState::default();
+		// bit offsets are kept separately for each report id, and each kind of report
+		let mut offsets: HashMap<(u8, u8), usize> = HashMap::new();
+
+		let mut pos = 0;
+		while pos < desc.len() {
+			let prefix = desc[ pos ];
+			if prefix == LONG_ITEM {
+				// long items are reserved, none are defined: skip them
+				if pos + 1 >= desc.len() {
+					return Err( format!("Truncated long item at byte {}", pos) );
+				}
+				pos += 3 + desc[ pos + 1 ] as usize;
+				continue;
+			}
+
+			let size = match prefix & 0x03 { 3 => 4, s => s as usize };
+			let item_type = (prefix >> 2) & 0x03;
+			let tag = prefix >> 4;
+			if pos + 1 + size > desc.len() {
+				return Err( format!("Truncated item 0x{:02x} at byte {}", prefix, pos) );
+			}
+			let data = &desc[ pos + 1..pos + 1 + size ];
+			let unsigned = item_unsigned( data );
+			let signed = item_signed( data );
+			pos += 1 + size;
+
+			match item_type {
+				TYPE_MAIN => {
+					match tag {
+						MAIN_INPUT | MAIN_OUTPUT | MAIN_FEATURE => {
+							let offset = offsets.entry( (tag, global.report_id) ).or_insert( 0 );
+							let end = global.report_size.checked_mul( global.report_count )
+								.and_then( |bits| offset.checked_add( bits ) )
+								.filter( |end| *end <= MAX_REPORT_BITS && global.report_count <= MAX_REPORT_BITS )
+								.ok_or( format!("Report {} too long at byte {}", global.report_id, pos - 1 - size) )?;
+							if tag == MAIN_INPUT {
+								layout.add_input( &global, &local, unsigned, *offset );
+							}
+							*offset = end;
+						}
+						_ => {}		// collections only group items, they do not move offsets
+					}
+					local = LocalState::default();
+				}
+				TYPE_GLOBAL => {
+					match tag {
+						GLOBAL_USAGE_PAGE => {	global.usage_page = unsigned as u16;	}
+						GLOBAL_LOGICAL_MIN => {	global.logical_min = signed;	}
+						GLOBAL_LOGICAL_MAX => {	global.logical_max = signed;	}
+						GLOBAL_REPORT_SIZE => {	global.report_size = unsigned as usize;	}
+						GLOBAL_REPORT_ID => {
+							global.report_id = unsigned as u8;
+							layout.uses_report_ids = true;
+						}
+						GLOBAL_REPORT_COUNT => {	global.report_count = unsigned as usize;	}
+						GLOBAL_PUSH => {	global_stack.push( global );	}
+						GLOBAL_POP => {
+							match global_stack.pop() {
+								Some( g ) => {	global = g;	}
+								None => {
+									return Err( format!("Pop without push at byte {}", pos) );
+								}
+							}
+						}
+						_ => {}
+					}
+				}
+				TYPE_LOCAL => {
+					let usage = extended_usage( unsigned, size, global.usage_page );
+					match tag {
+						LOCAL_USAGE => {	local.usages.push( usage );	}
+						LOCAL_USAGE_MIN => {	local.usage_min = Some( usage );	}
+						LOCAL_USAGE_MAX => {	local.usage_max = Some( usage );	}
+						_ => {}
+					}
+				}
+				_ => {}
+			}
+		}
+		Ok( layout )
+	}
+
+	fn add_input( &mut self, global: &GlobalState, local: &LocalState, flags: u32, offset: usize ) {
+		let constant = flags & 0x01 != 0;
+		let variable = flags & 0x02 != 0;
+		if constant || !variable {
+			// padding, or an array of selectors (keyboard style), neither are axes or buttons
+			return;
+		}
+
+		// a logical maximum such as 0xFF in one byte reads as -1, when the minimum is not negative
+		// the maximum must have been meant as unsigned
+		let mut logical_max = global.logical_max;
+		if global.logical_min >= 0 && logical_max < global.logical_min && global.report_size < 32 {
+			logical_max = (logical_max as u32 & ((1u32 << global.report_size) - 1)) as i32;
+		}
+
+		for n in 0..global.report_count {
+			let usage = match ( local.usages.get( n ), local.usage_min, local.usage_max ) {
+				( Some( u ), _, _ ) => Some( *u ),
+				( None, Some( min ), Some( max ) ) => match min.checked_add( n as u32 ) {
+					Some( u ) if u <= max => Some( u ),
+					_ => local.usages.last().copied(),
+				},
+				_ => local.usages.last().copied(),
+			};
+			let Some( usage ) = usage else { continue; };
+			self.fields.push( ReportField {
+				report_id: global.report_id,
+				usage_page: (usage >> 16) as u16,
+				usage: (usage & 0xFFFF) as u16,
+				bit_offset: offset + n * global.report_size,
+				bit_size: global.report_size,
+				logical_min: global.logical_min,
+				logical_max,
+			});
+		}
+	}
+
+	/// The n-th (from zero) input with the given usage
+	pub fn find( &self, usage_page: u16, usage: u16, nth: usize ) -> Option<&ReportField> {
+		self.fields.iter()
+			.filter( |f| f.usage_page == usage_page && f.usage == usage )
+			.nth( nth )
+	}
+
+	/// Button inputs, ordered by button number
+	pub fn buttons( &self ) -> Vec<&ReportField> {
+		let mut ret: Vec<&ReportField> = self.fields.iter()
+			.filter( |f| f.usage_page == USAGE_PAGE_BUTTON && f.bit_size == 1 )
+			.collect();
+		ret.sort_by_key( |f| f.usage );
+		ret
+	}
+
+	/// Longest input report, in bytes, including the report id
+	pub fn report_len( &self ) -> usize {
+		let bits = self.fields.iter()
+			.map( |f| f.bit_offset + f.bit_size )
+			.max()
+			.unwrap_or( 0 );
+		bits.div_ceil( 8 ) + self.uses_report_ids as usize
+	}
+
+	/// Leave out the inputs that end past len bytes (including the report id), as a
+	/// buffer of len bytes cannot hold them; returns those left out
+	pub fn truncate( &mut self, len: usize ) -> Vec<ReportField> {
+		let bits = len.saturating_sub( self.uses_report_ids as usize ) * 8;
+		let (kept, dropped) = std::mem::take( &mut self.fields ).into_iter()
+			.partition( |f| f.bit_offset + f.bit_size <= bits );
+		self.fields = kept;
+		dropped
+	}
+
+	/// The report id of the first input, 0 when reports are not numbered
+	pub fn first_report_id( &self ) -> u8 {
+		self.fields.first().map( |f| f.report_id ).unwrap_or( 0 )
+	}
+}
+
+/* ******************************************************************************* */
+
+fn extended_usage( value: u32, size: usize, usage_page: u16 ) -> u32 {
+	if size == 4 {
+		// a four byte usage carries its own usage page
+		return value;
+	}
+	(usage_page as u32) << 16 | (value & 0xFFFF)
+}
+
+/* ******************************************************************************* */
+
+fn item_signed( data: &[u8] ) -> i32 {
+	match data.len() {
+		1 => data[ 0 ] as i8 as i32,
+		2 => i16::from_le_bytes( [data[ 0 ], data[ 1 ]] ) as i32,
+		4 => i32::from_le_bytes( [data[ 0 ], data[ 1 ], data[ 2 ], data[ 3 ]] ),
+		_ => 0,
+	}
+}
+
+/* ******************************************************************************* */
+
+fn item_unsigned( data: &[u8] ) -> u32 {
+	let mut ret: u32 = 0;
+	for (n, b) in data.iter().enumerate() {
+		ret |= (*b as u32) << (8 * n);
+	}
+	ret
+}
+
+/* ******************************************************************************* */
+/* Tests */
+/* ******************************************************************************* */
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// a plain gamepad: X and Y in a byte each, a hat, twelve buttons, no report ids
+	const GAMEPAD: &[u8] = &[
+		0x05, 0x01, 0x09, 0x05, 0xA1, 0x01,
+		0x15, 0x00, 0x26, 0xFF, 0x00, 0x75, 0x08, 0x95, 0x02, 0x09, 0x30, 0x09, 0x31, 0x81, 0x02,
+		0x09, 0x39, 0x15, 0x00, 0x25, 0x07, 0x35, 0x00, 0x46, 0x3B, 0x01, 0x65, 0x14, 0x75, 0x04, 0x95, 0x01, 0x81, 0x42,
+		0x75, 0x04, 0x95, 0x01, 0x81, 0x03,
+		0x05, 0x09, 0x19, 0x01, 0x29, 0x0C, 0x15, 0x00, 0x25, 0x01, 0x75, 0x01, 0x95, 0x0C, 0x81, 0x02,
+		0x75, 0x04, 0x95, 0x01, 0x81, 0x03,
+		0xC0, 0x00,
+	];
+
+	// laid out as a Virpil stick is: report 1 has seven 16 bit axes, 32 buttons and a hat,
+	// report 2 is a feature report for the lights
+	const STICK: &[u8] = &[
+		0x05, 0x01, 0x09, 0x04, 0xA1, 0x01, 0x85, 0x01,
+		0x16, 0x00, 0x00, 0x26, 0x00, 0x40, 0x75, 0x10, 0x95, 0x03, 0x09, 0x30, 0x09, 0x31, 0x09, 0x32, 0x81, 0x02,
+		0x09, 0x33, 0x09, 0x34, 0x09, 0x35, 0x09, 0x36, 0x95, 0x04, 0x81, 0x02,
+		0x05, 0x09, 0x19, 0x01, 0x29, 0x20, 0x15, 0x00, 0x25, 0x01, 0x75, 0x01, 0x95, 0x20, 0x81, 0x02,
+		0x05, 0x01, 0x09, 0x39, 0x15, 0x00, 0x25, 0x07, 0x75, 0x04, 0x95, 0x01, 0x81, 0x42,
+		0x75, 0x04, 0x95, 0x01, 0x81, 0x01,
+		0x85, 0x02, 0x06, 0x00, 0xFF, 0x09, 0x01, 0x75, 0x08, 0x95, 0x10, 0xB1, 0x02,
+		0xC0,
+	];
+
+	#[test]
+	fn gamepad() {
+		let layout = ReportLayout::parse( GAMEPAD ).unwrap();
+		assert!( !layout.uses_report_ids );
+		let y = layout.find( USAGE_PAGE_GENERIC_DESKTOP, USAGE_Y, 0 ).unwrap();
+		assert_eq!( (y.bit_offset, y.bit_size, y.logical_min, y.logical_max), (8, 8, 0, 255) );
+		let hat = layout.find( USAGE_PAGE_GENERIC_DESKTOP, USAGE_HAT_SWITCH, 0 ).unwrap();
+		assert_eq!( (hat.bit_offset, hat.bit_size, hat.logical_max), (16, 4, 7) );
+
+		let buttons = layout.buttons();
+		assert_eq!( buttons.len(), 12 );
+		assert_eq!( (buttons[ 0 ].usage, buttons[ 0 ].bit_offset), (1, 24) );
+		assert_eq!( (buttons[ 11 ].usage, buttons[ 11 ].bit_offset), (12, 35) );
+		assert_eq!( layout.report_len(), 5 );
+
+		let report = [ 0x80, 0xFF, 0x03, 0x01, 0x08 ];
+		assert_eq!( y.read( &report, false ), Some( 255 ) );
+		assert_eq!( hat.read( &report, false ), Some( 3 ) );
+		assert_eq!( buttons[ 0 ].read( &report, false ), Some( 1 ) );
+		assert_eq!( buttons[ 11 ].read( &report, false ), Some( 1 ) );
+		assert_eq!( buttons[ 1 ].read( &report, false ), Some( 0 ) );
+	}
+
+	#[test]
+	fn stick_with_report_ids() {
+		let layout = ReportLayout::parse( STICK ).unwrap();
+		assert!( layout.uses_report_ids );
+		assert!( layout.fields.iter().all( |f| f.report_id == 1 ) );
+		let slider = layout.find( USAGE_PAGE_GENERIC_DESKTOP, USAGE_SLIDER, 0 ).unwrap();
+		assert_eq!( (slider.bit_offset, slider.bit_size, slider.logical_max), (96, 16, 0x4000) );
+		assert_eq!( layout.buttons().len(), 32 );
+		let hat = layout.find( USAGE_PAGE_GENERIC_DESKTOP, USAGE_HAT_SWITCH, 0 ).unwrap();
+		assert_eq!( hat.bit_offset, 144 );
+		assert_eq!( layout.report_len(), 20 );
+
+		let mut report = [ 0u8; 20 ];
+		report[ 0 ] = 1;
+		report[ 1..3 ].copy_from_slice( &0x2000u16.to_le_bytes() );
+		let x = layout.find( USAGE_PAGE_GENERIC_DESKTOP, USAGE_X, 0 ).unwrap();
+		assert_eq!( x.read( &report, true ), Some( 0x2000 ) );
+		report[ 0 ] = 2;
+		assert_eq!( x.read( &report, true ), None );
+		assert_eq!( layout.first_report_id(), 1 );
+	}
+
+	#[test]
+	fn truncated() {
+		let mut layout = ReportLayout::parse( STICK ).unwrap();
+		// the report id, then seven axes: the buttons and hat do not fit
+		let dropped = layout.truncate( 15 );
+		assert_eq!( (layout.fields.len(), layout.report_len()), (7, 15) );
+		assert_eq!( dropped.len(), 33 );
+		assert!( dropped.iter().all( |f| f.bit_offset >= 112 ) );
+		assert!( layout.truncate( 15 ).is_empty() );
+		assert_eq!( ReportLayout::default().first_report_id(), 0 );
+	}
+
+	#[test]
+	fn signed_fields() {
+		let field = ReportField { report_id: 0, usage_page: 1, usage: USAGE_X, bit_offset: 4, bit_size: 8, logical_min: -127, logical_max: 127 };
+		assert_eq!( field.read( &[ 0xF0, 0x0F ], false ), Some( -1 ) );
+		assert_eq!( field.read( &[ 0x10, 0x00 ], false ), Some( 1 ) );
+		assert_eq!( field.read( &[ 0x10 ], false ), None );
+	}
+
+	#[test]
+	fn broken_descriptors() {
+		assert!( ReportLayout::parse( &GAMEPAD[ ..GAMEPAD.len() - 4 ] ).is_ok() );
+		assert_eq!( ReportLayout::parse( &[ 0x05, 0x01, 0x26, 0xFF ] ), Err( "Truncated item 0x26 at byte 2".to_string() ) );
+		assert_eq!( ReportLayout::parse( &[ 0xFE ] ), Err( "Truncated long item at byte 0".to_string() ) );
+		assert_eq!( ReportLayout::parse( &[ 0xB4 ] ), Err( "Pop without push at byte 1".to_string() ) );
+
+		// 32 bits, four thousand million times
+		let huge = [ 0x05, 0x01, 0x09, 0x30, 0x75, 0x20, 0x97, 0xFF, 0xFF, 0xFF, 0xFF, 0x81, 0x02 ];
+		assert_eq!( ReportLayout::parse( &huge ), Err( "Report 0 too long at byte 11".to_string() ) );
+		// nothing in each field, but still too many of them
+		let empty = [ 0x75, 0x00, 0x97, 0xFF, 0xFF, 0xFF, 0x00, 0x81, 0x02 ];
+		assert!( ReportLayout::parse( &empty ).is_err() );
+		// usages counted up from near the top of the range
+		let top = [ 0x75, 0x01, 0x95, 0x04, 0x1B, 0xFE, 0xFF, 0xFF, 0xFF, 0x2B, 0xFF, 0xFF, 0xFF, 0xFF, 0x81, 0x02 ];
+		assert_eq!( ReportLayout::parse( &top ).unwrap().fields.len(), 2 );
+	}
+}
+
+/* ******************************************************************************* *
+ *		*** End ***
+ * ******************************************************************************* */
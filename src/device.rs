@@ -1,10 +1,20 @@
-use configparser::ini::Ini;
-use hidapi::{HidApi, HidDevice,};
+use crate::backend::{self, DeviceEntry, DeviceKey, HidApiBackend, InputBackend, InputDevice, };
+use crate::assets::DeviceImages;
+use crate::buttons::{self, Button, ButtonDef, };
+use crate::capture::{Recorder, ReplayBackend, };
+use crate::descriptor::{self, ReportLayout, };
+use crate::display::{AxisWidget, Display, Pedals, Throttle, };
+use crate::hats::{self, Hat, HatDef, };
+use crate::hotplug::{ArrivalSource, IntervalSource, NoSource, };
+use crate::ini_file;
+use crate::layout::Layout;
 #[cfg(not(feature = "single-threaded"))]
-use once_cell::sync::Lazy;
-#[cfg(feature = "single-threaded")]
-use once_cell::unsync::Lazy;
+use crate::reader::{Reader, ReaderEvent, };
+use crate::routing::{self, Route, };
+use hidapi::MAX_REPORT_DESCRIPTOR_SIZE;
 use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant, };
 
 #[cfg(feature = "logging")]
 use log:: info;
@@ -13,45 +23,46 @@ use ::function_name::named;
 /* ******************************************************************************* */
 /* Constants */
 
-const DEV_BUF_LEN: usize = 40;		// Virpil devices use a buff length of 37 bytes
+/// where devices are set up, and calibration is saved
+pub const CONFIG_FILE: &str = "./config/joystick_monitor.ini";
+pub(crate) const DEV_BUF_LEN: usize = 64;		// Virpil devices use a buff length of 37 bytes, 64 is a full USB packet
+const READ_LEN: usize = DEV_BUF_LEN + 1;	// one byte more, so a report too long for DEV_BUF_LEN is seen
 /// The max value for a joystick's axis
-pub static mut JS_MAX: u16 = 0xEA60;		// decimal 60,000; nearly 0xffff
-fn js_mid() -> u16 { unsafe{ JS_MAX / 2 } }
-fn js_max_f() -> f32 { unsafe { JS_MAX as f32 } }
+pub const JS_MAX: u16 = 0xEA60;		// decimal 60,000; nearly 0xffff
+fn js_mid() -> u16 { JS_MAX / 2 }
+fn js_max_f() -> f32 { JS_MAX as f32 }
+/// the axes a device can have, as named in the .ini file
+pub const AXES: [&str; 8] = ["x", "y", "z", "rx", "ry", "rz", "slider_0", "slider_1"];
+// each axis' usage in a HID report descriptor, and which of that usage it is, in AXES order
+const AXIS_USAGES: [(u16, usize); 8] = [
+	(descriptor::USAGE_X, 0), (descriptor::USAGE_Y, 0), (descriptor::USAGE_Z, 0),
+	(descriptor::USAGE_RX, 0), (descriptor::USAGE_RY, 0), (descriptor::USAGE_RZ, 0),
+	(descriptor::USAGE_SLIDER, 0), (descriptor::USAGE_SLIDER, 1),
+];
+/// an axis of AXES, by its name in any case
+pub(crate) fn find_axis( name: &str ) -> Result<&'static str, String> {
+	let name = name.trim().to_lowercase();
+	AXES.iter().find( |a| **a == name ).copied().ok_or( format!("unknown axis '{}'", name) )
+}
 
 pub enum Message {
 	Err(String),
 	None,
 }
 
-static mut API: Lazy<Api> = Lazy::new(|| {	Api::new()	});
-pub static mut DEVICES_REPORTS: Lazy<HashMap<u32, DeviceReport>> = Lazy::new(|| {
-	let m = HashMap::new();
-    m
-});
-static mut JOYSTICKS: Vec<Joystick> = Vec::new();
-static mut JS_DEVICES: Lazy<HashMap<u32, hidapi::HidDevice>> = Lazy::new(|| {
-	let m = HashMap::new();
-    m
-});
+// an open device: read on the UI thread, or by its own thread
+#[cfg(feature = "single-threaded")]
+type OpenDevice = Box<dyn InputDevice>;
+#[cfg(not(feature = "single-threaded"))]
+type OpenDevice = Reader;
 
-pub static mut TIME_OUT: i32 = -1;
+// the most reports read from one device in one frame; more are left for the next frame
+const MAX_DRAIN: usize = 1024;
+const RESCAN_INTERVAL: u64 = 2000;		// milliseconds
 
 /* ******************************************************************************* */
 /* Structures */
 /* ******************************************************************************* */
-struct Api {
-	hid_api: HidApi,
-}
-
-impl Api {
-	fn new() -> Api {
-		// if this fails, we have a major problem
-		Api { hid_api: HidApi::new()
-			.expect( "HidError "), }
-	}
-}
-
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub struct AxisReport {
 	pub axis: String,
@@ -59,12 +70,43 @@ pub struct AxisReport {
 	pub calibrate: i128,
 }
 
+/* ******************************************************************************* */
+/// whether a device is there, and since when (hh:mm:ss)
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Connection {
+	NotFound,
+	Connected( String ),
+	Disconnected( String ),
+	Reconnected( String ),
+	Virtual,			// made up from other devices, never opened
+}
+
+impl Connection {
+	fn now() -> String {
+		chrono::Local::now().format("%H:%M:%S").to_string()
+	}
+}
+
+impl fmt::Display for Connection {
+	fn fmt( &self, f: &mut fmt::Formatter ) -> fmt::Result {
+		match self {
+			Connection::NotFound => {	write!( f, "not found" )	}
+			Connection::Connected( at ) => {	write!( f, "connected at {}", at )	}
+			Connection::Disconnected( at ) => {	write!( f, "disconnected at {}", at )	}
+			Connection::Reconnected( at ) => {	write!( f, "reconnected at {}", at )	}
+			Connection::Virtual => {	write!( f, "virtual" )	}
+		}
+	}
+}
+
 /* ******************************************************************************* */
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub struct DeviceReport {
 	pub col: usize,
 	pub name: String,
+	pub section: String,	// the device's [section] in the .ini file
 	pub error: bool,		// possible that device is sleeping/inactive
+	pub connection: Connection,
 	// ** eight axies, these are the most common options available to joystick or throttle (say)
 	pub x: u16,
 	pub y: u16,
@@ -74,8 +116,25 @@ pub struct DeviceReport {
 	pub rz: u16,
 	pub slider_0: u16,
 	pub slider_1: u16,
-	pub buttons: Vec<u8>,
-	// calibration, for local adjustments
+	pub buttons: Vec<Button>,	// by number, pressed at any time since the last frame, so short taps are seen
+	pub hats: Vec<Hat>,		// as in the newest report
+	pub display: Display,	// a stick, a throttle, or rudder pedals
+	pub widgets: Vec<AxisWidget>,	// axes shown as bars, dials or ministicks
+	pub images: DeviceImages,	// the images it is drawn with, by name
+	pub skin: Option<String>,	// the skin it is drawn with, in place of its image
+	pub report: Vec<u8>,	// the last report read, as the device sent it
+	pub drained: usize,		// reports read in the last frame, for diagnostics
+	// the same axes before calibration, as the device sends them
+	pub x_raw: u16,
+	pub y_raw: u16,
+	pub z_raw: u16,
+	pub rx_raw: u16,
+	pub ry_raw: u16,
+	pub rz_raw: u16,
+	pub slider_0_raw: u16,
+	pub slider_1_raw: u16,
+	// each axis key's field 5, moving the drawn image: pixels for x and y, radians for z
+	// applied after any *_calibration, which corrects the values above
 	pub x_calibrate: i128,
 	pub y_calibrate: i128,
 	pub z_calibrate: i128,
@@ -89,9 +148,11 @@ pub struct DeviceReport {
 impl DeviceReport {
 	fn new( js: &Joystick ) -> DeviceReport {
 		DeviceReport {
-			name : js.name.clone(),
+			name : js.name().to_string(),
+			section : js.section.clone(),
 			col : js.col,
 			error: true,
+			connection: Connection::NotFound,
 			// Axies default values, used zero, could reasonably have used js_mid()
 			x : 0,
 			y : 0,
@@ -102,6 +163,21 @@ impl DeviceReport {
 			slider_0 : 0,
 			slider_1 : 0,
 			buttons : Vec::new(),
+			hats : Vec::new(),
+			display : js.display(),
+			widgets : js.report_widgets(),
+			images : js.images.clone(),
+			skin : js.skin.clone(),
+			report : Vec::new(),
+			drained : 0,
+			x_raw : 0,
+			y_raw : 0,
+			z_raw : 0,
+			rx_raw : 0,
+			ry_raw : 0,
+			rz_raw : 0,
+			slider_0_raw : 0,
+			slider_1_raw : 0,
 			x_calibrate: js.x.calibrate,
 			y_calibrate: js.y.calibrate,
 			z_calibrate: js.z.calibrate,
@@ -113,24 +189,72 @@ impl DeviceReport {
 		}
 	}
 
-	///
+	/// a copy showing the axes before calibration, e.g. to compare with what a game sees
+	pub fn as_raw( &self ) -> DeviceReport {
+		DeviceReport {
+			x: self.x_raw,
+			y: self.y_raw,
+			z: self.z_raw,
+			rx: self.rx_raw,
+			ry: self.ry_raw,
+			rz: self.rz_raw,
+			slider_0: self.slider_0_raw,
+			slider_1: self.slider_1_raw,
+			..self.clone()
+		}
+	}
+
+	/// every axis' (calibrated, raw) values, in AXES order
+	fn axes( &self ) -> [(u16, u16); 8] {
+		[
+			(self.x, self.x_raw), (self.y, self.y_raw), (self.z, self.z_raw),
+			(self.rx, self.rx_raw), (self.ry, self.ry_raw), (self.rz, self.rz_raw),
+			(self.slider_0, self.slider_0_raw), (self.slider_1, self.slider_1_raw),
+		]
+	}
+
+	fn axes_mut( &mut self ) -> [(&mut u16, &mut u16); 8] {
+		[
+			(&mut self.x, &mut self.x_raw), (&mut self.y, &mut self.y_raw), (&mut self.z, &mut self.z_raw),
+			(&mut self.rx, &mut self.rx_raw), (&mut self.ry, &mut self.ry_raw), (&mut self.rz, &mut self.rz_raw),
+			(&mut self.slider_0, &mut self.slider_0_raw), (&mut self.slider_1, &mut self.slider_1_raw),
+		]
+	}
+
+	/// an axis' (calibrated, raw) values by name, as in AXES
+	pub fn axis( &self, name: &str ) -> Option<(u16, u16)> {
+		let n = AXES.iter().position( |a| *a == name )?;
+		Some( self.axes()[ n ] )
+	}
+
+	/// an axis as 0.0 to 1.0 by name, as in AXES
+	pub fn axis_f32( &self, name: &str ) -> Option<f32> {
+		self.axis( name ).map( |(value, _)| value as f32 / js_max_f() )
+	}
+
+	fn axis_mut( &mut self, name: &str ) -> Option<(&mut u16, &mut u16)> {
+		let n = AXES.iter().position( |a| *a == name )?;
+		self.axes_mut().into_iter().nth( n )
+	}
+
+	/// axes as 0.0 to 1.0, whatever the device's own encoding (see AxisEncoding)
 	pub fn x_f32( &self ) -> f32 {
 		self.x as f32 / js_max_f()
 	}
 
-	pub  fn y_f32( &self ) -> f32 {
+	pub fn y_f32( &self ) -> f32 {
 		self.y as f32 / js_max_f()
 	}
 
 	pub fn z_f32( &self ) -> f32 {
 		self.z as f32 / js_max_f()
 	}
-	/*
+
 	pub fn rx_f32( &self ) -> f32 {
 		self.rx as f32 / js_max_f()
 	}
 
-	pub  fn ry_f32( &self ) -> f32 {
+	pub fn ry_f32( &self ) -> f32 {
 		self.ry as f32 / js_max_f()
 	}
 
@@ -138,14 +262,13 @@ impl DeviceReport {
 		self.rz as f32 / js_max_f()
 	}
 
-	pub  fn slider_0_f32( &self ) -> f32 {
+	pub fn slider_0_f32( &self ) -> f32 {
 		self.slider_0 as f32 / js_max_f()
 	}
 
 	pub fn slider_1_f32( &self ) -> f32 {
 		self.slider_1 as f32 / js_max_f()
 	}
-	*/
 }
 
 /* ******************************************************************************* */
@@ -155,9 +278,263 @@ pub struct JoystickAxis {
 	a1 : usize,			// in HID data (i.e. a0 and a1 are addresses)
 	label : String,		// a name for the axis
 	invert: bool,		// in case we need to invert the value read at a0, a1
-	calibrate: i128,	// offset to be applied for our program
+	calibrate: i128,	// field 5: moves the drawn image, not the value (that is calibration)
+	encoding: Option<AxisEncoding>,	// None: a0 high byte, a1 low byte, 0 to JS_MAX (Virpil)
+	calibration: Option<AxisCalibration>,	// None: values are shown as read
+}
+
+/* ******************************************************************************* */
+/// How an axis' value is stored in the HID data, for devices not read correctly by default
+#[derive(Debug, Clone, )]
+pub struct AxisEncoding {
+	bits: usize,			// 8, 10, 12, 14, 16, ...
+	little_endian: bool,	// a0 holds the low bits; big endian, a0 holds the high bits
+	signed: bool,
+	bit_offset: usize,		// bits to skip, from the lowest bit of the value's first byte
+	logical_min: Option<i32>,	// default from bits and signed
+	logical_max: Option<i32>,
+}
+
+impl AxisEncoding {
+	fn new() -> AxisEncoding {
+		AxisEncoding {
+			bits: 16,
+			little_endian: true,
+			signed: false,
+			bit_offset: 0,
+			logical_min: None,
+			logical_max: None,
+		}
+	}
+
+	/// read from a comma separated list, e.g. "bits=10, endian=little, signed=false, bit_offset=2, min=0, max=1023"
+	fn config_split_encoding( &mut self, val: &str ) -> Result<(), String> {
+		for v in val.split( "," ) {
+			if v.trim().is_empty() { continue; }
+			let Some( (name, value) ) = v.split_once( "=" ) else {
+				return Err( format!("expected name=value, found '{}'", v.trim()) );
+			};
+			let value = value.trim();
+			let bad = |err: &dyn fmt::Display| format!("{} = {} {}", name.trim(), value, err);
+			match name.trim().to_lowercase().as_str() {
+				"bits" => {	self.bits = value.parse::<usize>().map_err( |e| bad( &e ) )?;	}
+				"endian" => {
+					match value.to_lowercase().as_str() {
+						"little" | "le" => {	self.little_endian = true;	}
+						"big" | "be" => {	self.little_endian = false;	}
+						_ => {	return Err( bad( &"is not little or big" ) );	}
+					}
+				}
+				"signed" => {	self.signed = value.parse::<bool>().map_err( |e| bad( &e ) )?;	}
+				"bit_offset" => {	self.bit_offset = value.parse::<usize>().map_err( |e| bad( &e ) )?;	}
+				"min" => {	self.logical_min = Some( value.parse::<i32>().map_err( |e| bad( &e ) )? );	}
+				"max" => {	self.logical_max = Some( value.parse::<i32>().map_err( |e| bad( &e ) )? );	}
+				other => {	return Err( format!("unknown encoding field '{}'", other) );	}
+			}
+		}
+		if self.bits == 0 || self.bits + self.bit_offset > 16 {
+			return Err( format!("bits ({}) plus bit_offset ({}) must be 1 to 16", self.bits, self.bit_offset) );
+		}
+		let (min, max) = self.range();
+		if min >= max {
+			return Err( format!("min ({}) must be less than max ({})", min, max) );
+		}
+		Ok( () )
+	}
+
+	/// true when the value fits in the byte at a0, so a1 is not needed
+	fn one_byte( &self ) -> bool {
+		self.bits + self.bit_offset <= 8
+	}
+
+	fn read( &self, a0: usize, a1: usize, buff: &[u8] ) -> Option<i32> {
+		let word: u32 = if self.one_byte() {
+			*buff.get( a0 )? as u32
+		} else {
+			let (low, high) = if self.little_endian { (a0, a1) } else { (a1, a0) };
+			*buff.get( low )? as u32 | (*buff.get( high )? as u32) << 8
+		};
+		let raw = (word >> self.bit_offset) & ((1u32 << self.bits) - 1);
+		if self.signed && raw & (1 << (self.bits - 1)) != 0 {
+			// sign extend
+			return Some( (raw | (u32::MAX << self.bits)) as i32 );
+		}
+		Some( raw as i32 )
+	}
+
+	fn range( &self ) -> (i32, i32) {
+		let (min, max) = match self.signed {
+			true => (-(1i32 << (self.bits - 1)), (1i32 << (self.bits - 1)) - 1),
+			false => (0, ((1u32 << self.bits) - 1) as i32),
+		};
+		(self.logical_min.unwrap_or( min ), self.logical_max.unwrap_or( max ))
+	}
+}
+
+/* ******************************************************************************* */
+/// Response curve, applied after deadzones, to the distance from centre (0.0 to 1.0)
+#[derive(Debug, Clone, )]
+pub enum ResponseCurve {
+	Linear,
+	Exponential( f64 ),			// distance to the power of the exponent
+	Points( Vec<(f64, f64)> ),	// (in, out) pairs, straight lines between them
+}
+
+impl ResponseCurve {
+	fn apply( &self, value: f64 ) -> f64 {
+		match self {
+			ResponseCurve::Linear => {	value	}
+			ResponseCurve::Exponential( exponent ) => {	value.powf( *exponent )	}
+			ResponseCurve::Points( points ) => {
+				let Some( first ) = points.first() else { return value; };
+				if value <= first.0 { return first.1; }
+				for pair in points.windows( 2 ) {
+					let ((x0, y0), (x1, y1)) = (pair[ 0 ], pair[ 1 ]);
+					if value <= x1 {
+						return y0 + (value - x0) * (y1 - y0) / (x1 - x0);
+					}
+				}
+				points.last().map( |p| p.1 ).unwrap_or( value )
+			}
+		}
+	}
+}
+
+/* ******************************************************************************* */
+/// Calibration of one axis, in the device's own units (as read, before scaling)
+#[derive(Debug, Clone, )]
+pub struct AxisCalibration {
+	min: Option<i32>,		// default: the lowest value the encoding or descriptor allows
+	centre: Option<i32>,	// default: half way between min and max
+	max: Option<i32>,
+	centred: bool,			// sticks and pedals centre, throttles and sliders do not
+	deadzone: f64,			// inner, a fraction of the travel either side of centre (or above min)
+	outer_deadzone: f64,	// at the ends of the travel
+	curve: ResponseCurve,
+}
+
+impl AxisCalibration {
+	fn new( centred: bool ) -> AxisCalibration {
+		AxisCalibration {
+			min: None,
+			centre: None,
+			max: None,
+			centred,
+			deadzone: 0.0,
+			outer_deadzone: 0.0,
+			curve: ResponseCurve::Linear,
+		}
+	}
+
+	/// read from a comma separated list, e.g.
+	/// "min=12, centre=515, max=1010, deadzone=0.05, outer_deadzone=0.02, curve=exponential, exponent=1.5"
+	fn config_split_calibration( &mut self, val: &str ) -> Result<(), String> {
+		let mut exponent: Option<f64> = None;
+		let mut curve = "linear".to_string();
+		let mut points: Option<Vec<(f64, f64)>> = None;
+		for v in val.split( "," ) {
+			if v.trim().is_empty() { continue; }
+			let Some( (name, value) ) = v.split_once( "=" ) else {
+				return Err( format!("expected name=value, found '{}'", v.trim()) );
+			};
+			let value = value.trim();
+			let bad = |err: &dyn fmt::Display| format!("{} = {} {}", name.trim(), value, err);
+			match name.trim().to_lowercase().as_str() {
+				"min" => {	self.min = Some( value.parse::<i32>().map_err( |e| bad( &e ) )? );	}
+				"centre" | "center" => {	self.centre = Some( value.parse::<i32>().map_err( |e| bad( &e ) )? );	}
+				"max" => {	self.max = Some( value.parse::<i32>().map_err( |e| bad( &e ) )? );	}
+				"centred" | "centered" => {	self.centred = value.parse::<bool>().map_err( |e| bad( &e ) )?;	}
+				"deadzone" => {	self.deadzone = value.parse::<f64>().map_err( |e| bad( &e ) )?;	}
+				"outer_deadzone" => {	self.outer_deadzone = value.parse::<f64>().map_err( |e| bad( &e ) )?;	}
+				"curve" => {	curve = value.to_lowercase();	}
+				"exponent" => {	exponent = Some( value.parse::<f64>().map_err( |e| bad( &e ) )? );	}
+				"points" => {
+					// space separated in:out pairs, e.g. "0:0 0.5:0.25 1:1"
+					let points = points.get_or_insert_with( Vec::new );
+					for pair in value.split_whitespace() {
+						let Some( (x, y) ) = pair.split_once( ":" ) else {
+							return Err( bad( &format!("'{}' is not in:out", pair) ) );
+						};
+						points.push( (x.trim().parse::<f64>().map_err( |e| bad( &e ) )?,
+										y.trim().parse::<f64>().map_err( |e| bad( &e ) )?) );
+					}
+				}
+				other => {	return Err( format!("unknown calibration field '{}'", other) );	}
+			}
+		}
+		// fields for a curve other than the one chosen would be silently ignored
+		let exponential = matches!( curve.as_str(), "exponential" | "exp" );
+		if exponent.is_some() && !exponential {
+			return Err( format!("exponent is for curve=exponential, not curve={}", curve) );
+		}
+		if points.is_some() && curve != "points" {
+			return Err( format!("points are for curve=points, not curve={}", curve) );
+		}
+		self.curve = match curve.as_str() {
+			"linear" => ResponseCurve::Linear,
+			"exponential" | "exp" => {
+				let exponent = exponent.unwrap_or( 2.0 );
+				if exponent <= 0.0 {
+					return Err( format!("exponent ({}) must be more than zero", exponent) );
+				}
+				ResponseCurve::Exponential( exponent )
+			}
+			"points" => {
+				let mut points = points.unwrap_or_default();
+				if points.len() < 2 {
+					return Err( "curve=points needs at least two points".to_string() );
+				}
+				points.sort_by( |a, b| a.0.total_cmp( &b.0 ) );
+				if points.windows( 2 ).any( |p| p[ 0 ].0 == p[ 1 ].0 ) {
+					return Err( "curve=points has two points with the same input".to_string() );
+				}
+				ResponseCurve::Points( points )
+			}
+			other => {	return Err( format!("unknown curve '{}'", other) );	}
+		};
+		if self.deadzone < 0.0 || self.outer_deadzone < 0.0 || self.deadzone + self.outer_deadzone >= 1.0 {
+			return Err( format!("deadzone ({}) and outer_deadzone ({}) must be positive and add up to less than 1",
+								self.deadzone, self.outer_deadzone) );
+		}
+		Ok( () )
+	}
+
+	/// value, in the range min..max, as 0.0 to 1.0
+	fn apply( &self, value: i32, min: i32, max: i32 ) -> f64 {
+		let min = self.min.unwrap_or( min ) as f64;
+		let max = self.max.unwrap_or( max ) as f64;
+		if max <= min { return 0.5; }
+		let value = (value as f64).clamp( min, max );
+
+		// distance from centre, -1.0 to 1.0; or from min, 0.0 to 1.0
+		let travel = match self.centred {
+			true => {
+				let centre = self.centre.map( |c| (c as f64).clamp( min, max ) ).unwrap_or( (min + max) / 2.0 );
+				match value >= centre {
+					true if max > centre => (value - centre) / (max - centre),
+					false if centre > min => (value - centre) / (centre - min),
+					_ => 0.0,
+				}
+			}
+			false => (value - min) / (max - min),
+		};
+
+		let distance = travel.abs();
+		let distance = match distance {
+			d if d <= self.deadzone => 0.0,
+			d if d >= 1.0 - self.outer_deadzone => 1.0,
+			d => (d - self.deadzone) / (1.0 - self.outer_deadzone - self.deadzone),
+		};
+		let distance = self.curve.apply( distance ).clamp( 0.0, 1.0 );
+
+		match self.centred {
+			true => (1.0 + distance.copysign( travel )) / 2.0,
+			false => distance,
+		}
+	}
 }
 
+/* ******************************************************************************* */
 impl JoystickAxis {
 	fn new() -> JoystickAxis {
 		JoystickAxis {
@@ -166,15 +543,61 @@ impl JoystickAxis {
 			label: "".to_string(),
 			invert: false,
 			calibrate: 0,
+			encoding: None,
+			calibration: None,
+		}
+	}
+
+	/// the value for a report, as (raw, calibrated), both 0..JS_MAX and inverted if asked
+	fn report_value( &self, value: i32, min: i32, max: i32 ) -> (u16, u16) {
+		let raw = scale_value( value, min, max );
+		let calibrated = match &self.calibration {
+			Some( c ) => {	(c.apply( value, min, max ) * js_max_f() as f64).round() as u16	}
+			None => {	raw	}
+		};
+		if self.invert {
+			return (JS_MAX - raw, JS_MAX - calibrated);
+		}
+		(raw, calibrated)
+	}
+
+	/// the offsets of the axis' bytes in a report, 0 when not given
+	pub fn offsets( &self ) -> (usize, usize) {
+		(self.a0, self.a1)
+	}
+
+	pub fn label( &self ) -> &str {
+		&self.label
+	}
+
+	pub fn inverted( &self ) -> bool {
+		self.invert
+	}
+
+	/// are the axis' offsets given in the .ini file?
+	pub fn is_mapped( &self ) -> bool {
+		let one_byte = self.encoding.as_ref().is_some_and( |e| e.one_byte() );
+		self.a0 > 0 && (self.a1 > 0 || one_byte)
+	}
+
+	/// the raw value at a0, a1, and the range it is in
+	pub fn read( &self, buff: &[u8] ) -> Option<(i32, i32, i32)> {
+		match &self.encoding {
+			Some( encoding ) => {
+				let (min, max) = encoding.range();
+				Some( (encoding.read( self.a0, self.a1, buff )?, min, max) )
+			}
+			None => {
+				let value = *buff.get( self.a0 )? as i32 * 0x100 + *buff.get( self.a1 )? as i32;
+				Some( (value, 0, JS_MAX as i32) )
+			}
 		}
 	}
 
-	fn config_split_axis(&mut self, val: &String ) {
+	fn config_split_axis(&mut self, val: &str ) {
 		let vals = val.split(",");
-	
-		let mut counter = 0;
 		
-		for v in vals {
+		for (counter, v) in vals.enumerate() {
 			match counter {
 				0 => {
 					match v.trim().parse::<usize>() {
@@ -209,21 +632,39 @@ impl JoystickAxis {
 					}
 				}
 				_ => { // other => {
-					self.label = v.trim().clone().to_owned();
+					self.label = v.trim().to_owned();
 				}
 			}
-			counter += 1;
 		}
 	}
 }
 
 
+/* ******************************************************************************* */
+/// A configured device, as listed for calibration
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeviceInfo {
+	pub key: DeviceKey,
+	pub section: String,	// the device's [section] in the .ini file
+	pub name: String,
+}
+
+/// One axis' value in the device's own units, before scaling and calibration
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AxisSample {
+	pub axis: &'static str,		// x, y, z, rx, ry, rz, slider_0, slider_1
+	pub value: i32,
+	pub min: i32,				// the range the value can take
+	pub max: i32,
+	pub centred: bool,			// returns to a centre, as sticks do
+}
+
+/* ******************************************************************************* */
 #[derive(Debug, Clone, )]
 pub struct Joystick {
-	vid: u16,				// vendor id, usually displayed as hexadecimal
-	pid: u16,				// product id, usually displayed as hexadecimal
+	key: DeviceKey,			// vid and pid, and whatever else tells this device apart from the others
+	section: String,		// name of the .ini file section this device was read from
 	usage_page: u16,
-	hash: u32,				// quick look up, calculated as vid * 0x10000 + pid
 	name: String,			// calculated at run-time
 	x: JoystickAxis,
 	y: JoystickAxis,
@@ -235,20 +676,30 @@ pub struct Joystick {
 	slider_1: JoystickAxis,
 	col: usize,				// display position on screen
 	log_device: bool,		// not needed in release
-	echo_x : u32,			// echo: author used echo_z to merge rudder pedal data
-	echo_y : u32,			// with non-twist joystick
-	echo_z : u32,			// echo_x and _y complete scheme for major axies
+	routes: Vec<Route>,		// axes taken from other devices, e.g. rudder pedals merged with a non-twist joystick
 	buttons : Vec< usize >,	// list of offsets to button reports in Hid data
+	button_defs: Vec<ButtonDef>,	// buttons given one at a time (button_<n>), by bit, with names and groups
+	hat_defs: Vec<HatDef>,			// hats (POV switches), as an angle or as buttons
+	widgets: Vec<AxisWidget>,		// axes shown as bars, dials or ministicks, in AXES order
+	display: String,				// stick, throttle or pedals, as the display key gives it
+	throttle: Throttle,				// levers and detents, for display = throttle
+	pedals: Pedals,					// yaw and toe brakes, for display = pedals
+	images: DeviceImages,			// the images it is drawn with, by name in the manifest
+	skin: Option<String>,			// the skin it is drawn with, by its directory in skins/
+	auto_map: bool,			// use the device's report descriptor for anything not set by hand
+	backend: Option<String>,	// when this device is read differently to the others
+	title: Option<String>,		// shown in place of the product name
+	is_virtual: bool,			// no vid and pid: a device made up of others' axes and buttons
+	buttons_from: Vec<String>,	// for a virtual device, the devices whose buttons it shows, in order
 }
 
 impl Joystick {
 	fn new() -> Joystick {
 		let vec: Vec<usize> = Vec::new();
 		Joystick {
-			vid: 0,
-			pid: 0,
+			key: DeviceKey::default(),
+			section: "".to_string(),
 			usage_page: 1,
-			hash: 0,
 			name: "".to_string(),
 			x: JoystickAxis::new(),
 			y: JoystickAxis::new(),
@@ -258,12 +709,23 @@ impl Joystick {
 			rz: JoystickAxis::new(),
 			slider_0: JoystickAxis::new(),
 			slider_1: JoystickAxis::new(),
-			col: std::usize::MAX,
+			col: usize::MAX,
 			log_device: false,
-			echo_x: 0,
-			echo_y: 0,
-			echo_z: 0,
+			routes: Vec::new(),
 			buttons: vec,
+			button_defs: Vec::new(),
+			hat_defs: Vec::new(),
+			widgets: Vec::new(),
+			display: "stick".to_string(),
+			throttle: Throttle::default(),
+			pedals: Pedals::default(),
+			images: DeviceImages::default(),
+			skin: None,
+			auto_map: true,
+			backend: None,
+			title: None,
+			is_virtual: false,
+			buttons_from: Vec::new(),
 		}
 	}
 
@@ -279,24 +741,62 @@ impl Joystick {
 	fn set_config_values(&mut self, value_map: HashMap<String, Option<String>>) -> Vec<Message> {
 		
 		let mut ret: Vec<Message> = Vec::new();
+		// the key that routes each axis; a second route to the same axis is an error
+		let mut routed: HashMap<&'static str, String> = HashMap::new();
 		for (key, value ) in value_map {
 			match &value {
 				Some( val ) => {
 					let key_s = key.as_str();
 					match key_s {
-						"vid" =>	{	self.vid = hex_to_u16( val );	}		// required
-						"pid" =>	{	self.pid = hex_to_u16( val );	}		// required
+						"vid" =>	{	self.key.vid = hex_to_u16( val );	}		// required
+						"pid" =>	{	self.key.pid = hex_to_u16( val );	}		// required
+						// only needed to tell identical devices apart
+						"serial" | "path" | "interface_number" => {
+										if let Err( err ) = self.key.set_field( key_s, val ) {
+											ret.push( show_error(module_path!(),
+													function_name!(),
+													format!("Error reading '{}' {}", key_s, err)));
+										}
+						}
 						"usage_page" =>	{	self.usage_page = dec_to_u16( val );	}		// default 1
-						"x" =>		{	self.x.config_split_axis( val );	}
-						"y" =>		{	self.y.config_split_axis( val );	}
-						"z" =>		{	self.z.config_split_axis( val );	}
-									//	self.z.invert = !self.z.invert;			// dirty, could do better
-									//}
-						"rx" =>		{	self.rx.config_split_axis( val );	}
-						"ry" =>		{	self.ry.config_split_axis( val );	}
-						"rz" =>		{	self.rz.config_split_axis( val );	}
-						"slider_0" => {	self.slider_0.config_split_axis( val );	}
-						"slider_1" => {	self.slider_1.config_split_axis( val );	}
+						// x, y, ... slider_1
+						other if AXES.contains( &other ) => {
+										if let Some( axis ) = self.axis_mut( other ) {
+											axis.config_split_axis( val );
+										}
+						}
+						// x_encoding, y_encoding, ... slider_1_encoding
+						other if other.ends_with( "_encoding" ) => {
+										let name = other.trim_end_matches( "_encoding" );
+										let mut encoding = AxisEncoding::new();
+										let set = match self.axis_mut( name ) {
+											Some( axis ) => {
+												encoding.config_split_encoding( val ).map( |()| axis.encoding = Some( encoding ) )
+											}
+											None => {	Err( format!("unknown axis '{}'", name) )	}
+										};
+										if let Err( err ) = set {
+											ret.push( show_error(module_path!(),
+													function_name!(),
+													format!("Error reading '{}' {}", key_s, err)));
+										}
+						}
+						// x_calibration, y_calibration, ... slider_1_calibration
+						other if other.ends_with( "_calibration" ) => {
+										let name = other.trim_end_matches( "_calibration" );
+										let mut calibration = AxisCalibration::new( !name.starts_with( "slider" ) );
+										let set = match self.axis_mut( name ) {
+											Some( axis ) => {
+												calibration.config_split_calibration( val ).map( |()| axis.calibration = Some( calibration ) )
+											}
+											None => {	Err( format!("unknown axis '{}'", name) )	}
+										};
+										if let Err( err ) = set {
+											ret.push( show_error(module_path!(),
+													function_name!(),
+													format!("Error reading '{}' {}", key_s, err)));
+										}
+						}
 						"col" =>	{	self.col = num_to_usize( val );	}
 						"log_device" => {
 										match val.parse::<bool>() {
@@ -311,15 +811,124 @@ impl Joystick {
 										}
 						}
 						"buttons" => {	self.set_buttons( value);	}
+						"auto_map" => {
+										match val.parse::<bool>() {
+											Ok( b ) => {
+												self.auto_map = b;
+											}
+											Err( err ) => {
+												ret.push( show_error(module_path!(),
+														function_name!(),
+														format!("Error reading 'auto_map' {}", err)));
+											}
+										}
+						}
 						// if used, echo'd devices should be displayed as two, four digit, hexadecimal numbers
 						// representing the vid and pid (in that order)
 						// e.g. as 3344 01F8
 						// where 3344 is a vendor's id,
 						// and 01F8 is an individual product
-						"echo_x" =>	{	self.set_echo( 'x', val );	}
-						"echo_y" =>	{	self.set_echo( 'y', val );	}
-						"echo_z" =>	{	self.set_echo( 'z', val );	}
+						// followed, for one of two identical devices, by serial=, path= or interface=
+						// echo_x is short for x_route = 3344 01F8:x
+						"echo_x" | "echo_y" | "echo_z" => {
+										let axis = key_s.trim_start_matches( "echo_" );
+										if let Message::Err( err ) = self.set_route( key_s, Route::echo( axis, val ), &mut routed ) {
+											ret.push( Message::Err( err ) );
+										}
+						}
+						// x_route, y_route, ... slider_1_route
+						other if other.ends_with( "_route" ) => {
+										let axis = other.trim_end_matches( "_route" );
+										if let Message::Err( err ) = self.set_route( key_s, Route::parse( axis, val ), &mut routed ) {
+											ret.push( Message::Err( err ) );
+										}
+						}
+						// x_widget, y_widget, ... slider_1_widget
+						other if other.ends_with( "_widget" ) => {
+										let axis = other.trim_end_matches( "_widget" );
+										if let Message::Err( err ) = self.set_widget( key_s, axis, AxisWidget::parse( axis, val ) ) {
+											ret.push( Message::Err( err ) );
+										}
+						}
+						"display" => {
+										match Display::check_name( val ) {
+											Ok( name ) => {	self.display = name;	}
+											Err( err ) => {
+												ret.push( show_error(module_path!(),
+														function_name!(),
+														format!("Error reading 'display' {}", err)));
+											}
+										}
+						}
+						"throttle_levers" | "throttle_detents" => {
+										let set = match key_s {
+											"throttle_levers" => self.throttle.set_levers( val ),
+											_ => self.throttle.set_detents( val ),
+										};
+										if let Err( err ) = set {
+											ret.push( show_error(module_path!(),
+													function_name!(),
+													format!("Error reading '{}' {}", key_s, err)));
+										}
+						}
+						"pedals_yaw" | "pedals_brakes" => {
+										let set = match key_s {
+											"pedals_yaw" => self.pedals.set_yaw( val ),
+											_ => self.pedals.set_brakes( val ),
+										};
+										if let Err( err ) = set {
+											ret.push( show_error(module_path!(),
+													function_name!(),
+													format!("Error reading '{}' {}", key_s, err)));
+										}
+						}
+						"throttle_lock" => {
+										match val.parse::<bool>() {
+											Ok( b ) => {
+												self.throttle.show_lock = b;
+											}
+											Err( err ) => {
+												ret.push( show_error(module_path!(),
+														function_name!(),
+														format!("Error reading 'throttle_lock' {}", err)));
+											}
+										}
+						}
+						"image" =>	{	self.images.body = Some( val.trim().to_lowercase() ).filter( |v| !v.is_empty() );	}
+						"skin" =>	{	self.skin = Some( val.trim().to_string() ).filter( |v| !v.is_empty() );	}
+						"centre_image" =>	{	self.images.centre = val.trim().to_lowercase();	}
+						"button_images" => {
+										if let Err( err ) = self.images.set_buttons( val ) {
+											ret.push( show_error(module_path!(),
+													function_name!(),
+													format!("Error reading 'button_images' {}", err)));
+										}
+						}
+						"backend" =>	{	self.backend = Some( val.trim().to_lowercase() );	}
+						"evdev_name" =>	{	self.key.name = Some( val.trim().to_string() );	}
+						"evdev_phys" =>	{	self.key.phys = Some( val.trim().to_string() );	}
+						"name" =>	{	self.title = Some( val.trim().to_string() );	}
+						"buttons_from" => {
+										self.buttons_from = val.split( ';' )
+											.map( |d| d.trim().to_string() )
+											.filter( |d| !d.is_empty() )
+											.collect();
+						}
 						"comment" =>	{ /* just consume comments */ }
+						// button_1, button_2, ... button_128, ...
+						other if other.strip_prefix( "button_" ).is_some_and( |n| n.parse::<usize>().is_ok() ) => {
+										let number = other.trim_start_matches( "button_" ).parse::<usize>().unwrap_or( 0 );
+										if let Message::Err( err ) = self.set_button( other, ButtonDef::parse( number, val ) ) {
+											ret.push( Message::Err( err ) );
+										}
+						}
+						// hat_1, hat_2, ...
+						other if other.strip_prefix( "hat_" ).is_some_and( |n| n.parse::<usize>().is_ok() ) => {
+										let number = other.trim_start_matches( "hat_" ).parse::<usize>().unwrap_or( 0 );
+										if let Message::Err( err ) = self.set_hat( other, HatDef::parse( number, val ) ) {
+											ret.push( Message::Err( err ) );
+										}
+						}
 						other => {
 							// deal with unexpected fields
 							ret.push( show_error(module_path!(),
@@ -336,7 +945,6 @@ impl Joystick {
 				}
 			}
 		}								
-		self.set_hash();
 		#[cfg(feature = "logging")] {
 			info!("{}::{} has read {}",
 					module_path!(),
@@ -346,293 +954,1048 @@ impl Joystick {
 		ret
 	}
 
-	#[named]
-	fn read_device( &mut self, buff : &mut [  u8 ]) -> Vec<Message> {
-		let mut ret: Vec<Message> = Vec::new();
-		let device: Option< &HidDevice>;
-		unsafe {
-			device =JS_DEVICES.get(&self.hash);
-		}
-		
-		match device {
-			Some( dev ) => {
-				let time_out: i32;
-				unsafe{
-					time_out = TIME_OUT;
-				}
-				match dev.read_timeout(buff, time_out) { //match dev.read(buff) {
-					Ok( bsize ) => {
-						if bsize >= DEV_BUF_LEN {
-							ret.push(
-								show_error(module_path!(), function_name!(),
-									format!(
-										"Device buffer too small (DEV_BUF_LEN = {})",
-										DEV_BUF_LEN) ) );
-						}
-					}
-					Err( err ) => {
-						ret.push( show_error(module_path!(), function_name!(),
-								format!("{} {}",self.vid_pid(), err))) ;
-						self.set_name();
-					}
-				}
-			}
-			None => {
-				ret.push(
-					show_error(	module_path!(),
-								function_name!(),
-								format!("{} Not found: Device might be sleeping", self.vid_pid())));
-				self.set_name();
-			}
-		}
-		
-		ret
+	/// the device is read no more: start its reader thread, or keep it to read each frame
+	#[cfg(feature = "single-threaded")]
+	fn open_device( &self, device: Box<dyn InputDevice> ) -> Result<OpenDevice, String> {
+		Ok( device )
+	}
+
+	#[cfg(not(feature = "single-threaded"))]
+	fn open_device( &self, device: Box<dyn InputDevice> ) -> Result<OpenDevice, String> {
+		Reader::spawn( self.vid_pid(), device, READ_LEN )
 	}
 
 	fn set_buttons( &mut self, value: Option<String>) {
-		match value {
-			Some( values) => {
-				for val in values.split(",") {
-					match val.trim().parse::<usize>() {
-						Ok( v ) => {
-							self.buttons.push(v);
-						}
-						Err(_) => {}
-					}
+		self.buttons = Vec::new();
+		if let Some( values ) = value {
+			for val in values.split(",") {
+				if let Ok( v ) = val.trim().parse::<usize>() {
+					self.buttons.push(v);
 				}
 			}
-			None => {}
 		}
 	}
 
+	/// a button given by hand, in place of any with the same number
 	#[named]
-	fn set_echo( &mut self, axis: char, value: &String ) {
-		let mut echo_hash: u32 = 0;
-		for val in value.split(" ") {
-			echo_hash = echo_hash * 0x10000
-							+ hex_to_u16( &val.to_string() ) as u32;
-		}
-		match axis {
-			'x' => {	self.echo_x = echo_hash;	}
-			'y' => {	self.echo_y = echo_hash;	}
-			'z' => {	self.echo_z = echo_hash;	}
-			_ => {}
-		}
-		#[cfg(feature = "logging")] {
-			info!("{}::{} {} axis from {:04x} {:04x}",
-				module_path!(), function_name!(),
-				// self.vid,		// nice to have, but may not be known yet
-				// self.pid,		// nice to have, but may not be known yet
-				&axis,
-				echo_hash / 0x10000,
-				echo_hash % 0x10000);
+	fn set_button( &mut self, name: &str, def: Result<ButtonDef, String> ) -> Message {
+		match def {
+			Ok( def ) => {
+				self.button_defs.retain( |d| d.number != def.number );
+				self.button_defs.push( def );
+				Message::None
+			}
+			Err( err ) => {
+				show_error(module_path!(), function_name!(),
+							format!("Error reading '{}' {}", name, err))
+			}
 		}
 	}
 
-	fn set_hash(&mut self) {
-		self.hash = self.vid as u32 * 0x10000 + self.pid as u32;
+	/// a hat, in place of any with the same number
+	#[named]
+	fn set_hat( &mut self, name: &str, def: Result<HatDef, String> ) -> Message {
+		match def {
+			Ok( def ) => {
+				self.hat_defs.retain( |d| d.number != def.number );
+				self.hat_defs.push( def );
+				self.hat_defs.sort_by_key( |d| d.number );
+				Message::None
+			}
+			Err( err ) => {
+				show_error(module_path!(), function_name!(),
+							format!("Error reading '{}' {}", name, err))
+			}
+		}
 	}
 
+	/// an axis' widget, in place of any it had; none, to show the axis no more
 	#[named]
-	fn set_name(&mut self ) -> Message {
-		let api: &HidApi;
-		unsafe{ 
-			api = &API.hid_api;
-		}
-		let mut ret: Message = Message::None;
-		// No, need device with usage_page == 1
-		// suggest iterate through devices
-		for device_info in api.device_list() {
-			if (device_info.vendor_id() == self.vid) &&  (device_info.product_id() == self.pid) && (device_info.usage_page() < 8) {
-				match device_info.open_device(&api) {
-					Ok( device ) => {
-						match device.get_product_string() {
-							Ok( pr_string ) => {
-								match pr_string {
-									Some(name) => {
-										self.name = name;
-									}
-									None => {}
-								}
-							}
-							Err( err ) => {
-								ret = show_error(module_path!(), function_name!(), 
-									format!("Error {} - could not name device {}",
-													err, self.vid_pid()) );
-							}
-						}				
-						unsafe {
-							JS_DEVICES.insert(self.hash, device);
-						}
-					}
-					Err( err ) => {
-						ret = show_error(module_path!(), function_name!(),
-										format!("{} - could not open {}",
-													err, self.vid_pid()));
-					}
-				}
-				break
+	fn set_widget( &mut self, name: &str, axis: &str, widget: Result<Option<AxisWidget>, String> ) -> Message {
+		match widget {
+			Ok( widget ) => {
+				self.widgets.retain( |w| w.axis != axis );
+				self.widgets.extend( widget );
+				self.widgets.sort_by_key( |w| AXES.iter().position( |a| *a == w.axis ) );
+				Message::None
+			}
+			Err( err ) => {
+				show_error(module_path!(), function_name!(),
+							format!("Error reading '{}' {}", name, err))
 			}
 		}
-		ret
 	}
 
-	fn vid_pid( &self ) -> String {
-		format!("{:04x} {:04x}", self.vid, self.pid)
+	/// the widgets for a report, labelled with the axes' own labels when not given one
+	fn report_widgets( &self ) -> Vec<AxisWidget> {
+		self.widgets.iter().map( |w| {
+			let mut widget = w.clone();
+			if widget.label.is_empty() {
+				widget.label = match self.axis( w.axis ).map( |a| a.label() ) {
+					Some( label ) if !label.is_empty() => label.to_string(),
+					_ => w.axis.to_string(),
+				};
+			}
+			widget
+		}).collect()
 	}
-}
 
-/* ******************************************************************************* */
+	/// what the device is shown as, from the display key
+	pub fn display( &self ) -> Display {
+		match self.display.as_str() {
+			"throttle" => Display::Throttle( self.throttle.clone() ),
+			"pedals" => Display::Pedals( self.pedals.clone() ),
+			_ => Display::Stick,
+		}
+	}
 
-#[named]
-pub fn check_devices() -> Vec<Message> {
-	let mut ret: Vec<Message> = Vec::new();
-	
-	let mut joysticks: Vec<Joystick>;
-	unsafe {
-		joysticks = JOYSTICKS.clone();
-	}
-	
-	for js in &mut joysticks {
-		let buff: &mut [u8] = &mut [0; DEV_BUF_LEN];
-		ret.append(&mut js.read_device( buff) );
-		
-		if js.log_device {
-			let mut buff_st:String = "".to_string();
-			for n in 0..buff.len() {
-				buff_st = format!("{}\t{}", buff_st, buff[ n ]).to_string();
-			}
-			#[cfg(feature = "logging")] {
-				info!("{}::{} -> {:04x} {:04x}: {}", module_path!(), function_name!(), js.vid, js.pid, buff_st);
+	/// an axis' route, in place of any it had; the devices it reads are found later (DeviceManager::plan_routes)
+	/// routed holds the keys already read in the same go: two routes to one axis (e.g. echo_z and z_route)
+	/// are an error, and neither is used, as which came first depends on the order the keys are read in
+	#[named]
+	fn set_route( &mut self, name: &str, route: Result<Route, String>, routed: &mut HashMap<&'static str, String> ) -> Message {
+		let route = match route {
+			Ok( route ) => {	route	}
+			Err( err ) => {
+				return show_error(module_path!(), function_name!(),
+								format!("Error reading '{}' {}", name, err));
 			}
+		};
+		if let Some( other ) = routed.get( route.target ) {
+			let mut keys = [ other.as_str(), name ];
+			keys.sort();
+			let err = format!("'{}' and '{}' both route {}, neither is used", keys[ 0 ], keys[ 1 ], route.target);
+			self.routes.retain( |r| r.target != route.target );
+			return show_error(module_path!(), function_name!(), err);
+		}
+		routed.insert( route.target, name.to_string() );
+		#[cfg(feature = "logging")] {
+			info!("{}::{} {} from {}",
+				module_path!(), function_name!(),
+				// self.key,		// nice to have, but may not be known yet
+				route.target,
+				route.sources.iter().map( |s| s.to_string() ).collect::<Vec<String>>().join( "; " ));
 		}
-		make_device_report( &js, buff );
+		self.routes.retain( |r| r.target != route.target );
+		self.routes.push( route );
+		Message::None
 	}
-	
-	do_echo(&joysticks);
-	ret
-}
-
-/* ******************************************************************************* */
 
-fn do_echo(joysticks: &Vec<Joystick>) {
-	for js in joysticks {
-		unsafe {
-			match DEVICES_REPORTS.get(&js.hash) {
-				Some( dr ) => {
-					let mut dr = dr.clone();
-					match DEVICES_REPORTS.get(&js.echo_x) {
-						Some( xjs ) => { dr.x = xjs.x; }
-						_ => {}
-					}
-					match DEVICES_REPORTS.get(&js.echo_y) {
-						Some( yjs ) => { dr.y = yjs.y; }
-						_ => {}
-					}
-					match DEVICES_REPORTS.get(&js.echo_z) {
-						Some( zjs ) => { dr.z = zjs.z; }
-						_ => {}
-					}
-					DEVICES_REPORTS.insert(js.hash, dr);
-				}
-				_ => {}
-			}
+	/// a device as set up by one [section] of an .ini file, not yet opened
+	/// returns any error messages, for keys that could not be read
+	pub fn from_config( section: &str, values: HashMap<String, Option<String>> ) -> (Joystick, Vec<Message>) {
+		let mut js = Joystick::new();
+		let ret = js.set_config_values( values );
+		js.section = section.to_string();
+		// nothing to find it by: a virtual device, each has a key of its own
+		if js.key.vid == 0 && js.key.pid == 0 && js.key.name.is_none() && js.key.phys.is_none() {
+			js.is_virtual = true;
+			js.key.path = Some( format!("virtual:{}", section) );
 		}
+		(js, ret)
 	}
-}
 
-/* ******************************************************************************* */
+	pub fn key( &self ) -> &DeviceKey {
+		&self.key
+	}
 
-fn device_report_axis( axis: JoystickAxis, buff : &[u8] ) -> u16 { // a_0 : usize, a_1 : usize, invert: bool, buff : &[u8] ) -> u16 {
-	if buff[ 0 ] == 0	{ return js_mid(); }
-	if axis.a0 <= 0		{ return js_mid(); }
-	if axis.a1 <= 0		{ return js_mid(); }
+	pub fn section( &self ) -> &str {
+		&self.section
+	}
 
-	let len = buff.len();
-	if axis.a0 >= len	{ return js_mid(); }
-	if axis.a1 >= len	{ return js_mid(); }
+	/// the name key, if given; else the product name, once the device has been found; else empty
+	pub fn name( &self ) -> &str {
+		self.title.as_deref().unwrap_or( &self.name )
+	}
 
-	let ret = buff[ axis.a0 ] as u16 * 0x100 + buff[ axis.a1 ] as u16;
+	/// made up of other devices, rather than read
+	pub fn is_virtual( &self ) -> bool {
+		self.is_virtual
+	}
 
-	if axis.invert {
-		unsafe {
-			return JS_MAX - ret;
-		}
+	/// display position on screen, usize::MAX if not shown
+	pub fn col( &self ) -> usize {
+		self.col
 	}
-	ret
-}
 
-/* ******************************************************************************* */
+	/// offsets of the button bytes given in the .ini file
+	pub fn buttons( &self ) -> &[usize] {
+		&self.buttons
+	}
 
-fn dec_to_u16( value: &String) -> u16 {
-	match u16::from_str_radix( value.trim(), 10) {
-		Ok( val) => { val }
-		Err( _e ) => { 0 }
+	/// buttons given one at a time in the .ini file (button_1, button_2, ...)
+	pub fn button_defs( &self ) -> &[ButtonDef] {
+		&self.button_defs
 	}
-}
 
-/* ******************************************************************************* */
+	/// axes shown as bars, dials or ministicks (x_widget, ...), in AXES order
+	pub fn widgets( &self ) -> &[AxisWidget] {
+		&self.widgets
+	}
 
-fn hex_to_u16( value: &String) -> u16 {
-	match u16::from_str_radix( value.trim(), 16) {
-		Ok( val) => { val }
-		Err( _e ) => { 0 }
+	/// the images it is drawn with, by name in the manifest
+	pub fn images( &self ) -> &DeviceImages {
+		&self.images
+	}
+
+	/// the skin it is drawn with, if any
+	pub fn skin( &self ) -> Option<&str> {
+		self.skin.as_deref()
+	}
+
+	/// hats given in the .ini file (hat_1, hat_2, ...)
+	pub fn hat_defs( &self ) -> &[HatDef] {
+		&self.hat_defs
+	}
+
+	/// axes taken from other devices (and echo_x, _y and _z), as read from the .ini file
+	pub fn routes( &self ) -> &[Route] {
+		&self.routes
+	}
+
+	/// an axis by name: x, y, z, rx, ry, rz, slider_0 or slider_1
+	pub fn axis( &self, name: &str ) -> Option<&JoystickAxis> {
+		self.axes().into_iter().find( |(n, ..)| *n == name ).map( |(_, axis, ..)| axis )
+	}
+
+	/// every axis, with its name and where the report descriptor puts it, in AXES order
+	fn axes( &self ) -> [(&'static str, &JoystickAxis, u16, usize); 8] {
+		let axes = [ &self.x, &self.y, &self.z, &self.rx, &self.ry, &self.rz, &self.slider_0, &self.slider_1 ];
+		std::array::from_fn( |n| (AXES[ n ], axes[ n ], AXIS_USAGES[ n ].0, AXIS_USAGES[ n ].1) )
+	}
+
+	/// every axis, in AXES order
+	fn axes_mut( &mut self ) -> [&mut JoystickAxis; 8] {
+		[
+			&mut self.x, &mut self.y, &mut self.z, &mut self.rx, &mut self.ry, &mut self.rz,
+			&mut self.slider_0, &mut self.slider_1,
+		]
+	}
+
+	/// an axis by name, as in AXES
+	fn axis_mut( &mut self, name: &str ) -> Option<&mut JoystickAxis> {
+		let name = find_axis( name ).ok()?;
+		let n = AXES.iter().position( |a| *a == name )?;
+		self.axes_mut().into_iter().nth( n )
+	}
+
+	fn vid_pid( &self ) -> String {
+		self.key.to_string()
+	}
+}
+
+/* ******************************************************************************* */
+/// every report waiting, oldest first, with when it was read, without waiting for more
+/// reports read before an error are kept, they are still worth showing
+#[cfg(feature = "single-threaded")]
+fn read_open_device( device: &OpenDevice, reports: &mut Vec<(Instant, Vec<u8>)> ) -> Result<(), String> {
+	let mut buff = [0u8; READ_LEN];
+	while reports.len() < MAX_DRAIN {
+		match device.read_timeout(&mut buff, 0)? {
+			0 => {	break;	}
+			len => {	reports.push( (Instant::now(), buff[ ..len ].to_vec()) );	}
+		}
 	}
+	Ok( () )
+}
+
+/// every report sent by the device's reader thread since the last frame, stamped as it was read
+#[cfg(not(feature = "single-threaded"))]
+fn read_open_device( device: &OpenDevice, reports: &mut Vec<(Instant, Vec<u8>)> ) -> Result<(), String> {
+	while reports.len() < MAX_DRAIN {
+		match device.try_next() {
+			Some( ReaderEvent::Report( at, report ) ) => {	reports.push( (at, report) );	}
+			Some( ReaderEvent::Error( err ) ) => {	return Err( err );	}
+			None => {	break;	}
+		}
+	}
+	Ok( () )
 }
 
 /* ******************************************************************************* */
+/// The configured devices, the backend they are read with, and their latest reports
+/// owns all of its state, so any number can be made, e.g. one for each test;
+/// and is Send, so it can be moved to another thread
+pub struct DeviceManager {
+	backend: Option<Box<dyn InputBackend>>,				// hidapi, unless another has been set
+	backends: HashMap<String, Box<dyn InputBackend>>,	// backends chosen for single devices, by name
+	joysticks: Vec<Joystick>,
+	reports: HashMap<DeviceKey, DeviceReport>,
+	devices: HashMap<DeviceKey, OpenDevice>,
+	layouts: HashMap<DeviceKey, ReportLayout>,	// read from each device's HID report descriptor
+	// backend paths of the open devices, so two identical devices are not both opened as one
+	paths: HashMap<DeviceKey, String>,
+	recorder: Option<Recorder>,					// when recording, raw reports are written here
+	routes: Vec<(DeviceKey, Route)>,			// every device's routes, in the order they are worked out
+	buttons_from: Vec<(DeviceKey, Vec<DeviceKey>)>,	// virtual devices, and the devices whose buttons they show
+	hotplug: Option<Box<dyn ArrivalSource>>,	// says when to look again for devices that are not open
+	layout: Layout,								// where devices are shown, from the [Layout] section
+	config_file: Option<String>,				// the .ini file read by load_file, where changes are saved
+}
+
+impl Default for DeviceManager {
+	fn default() -> Self {
+		DeviceManager::new()
+	}
+}
+
+impl DeviceManager {
+	/// no devices yet; hidapi is opened when first needed, unless set_backend is called first
+	pub fn new() -> DeviceManager {
+		DeviceManager {
+			backend: None,
+			backends: HashMap::new(),
+			joysticks: Vec::new(),
+			reports: HashMap::new(),
+			devices: HashMap::new(),
+			layouts: HashMap::new(),
+			paths: HashMap::new(),
+			recorder: None,
+			routes: Vec::new(),
+			buttons_from: Vec::new(),
+			hotplug: None,
+			layout: Layout::default(),
+			config_file: None,
+		}
+	}
 
-#[named]
-pub fn load_devices( frame_rate: i32 ) -> Vec<Message> { // (api: &HidApi) -> Vec<Message> {
-	let mut ret: Vec<Message> = Vec::new();
-	let mut config = Ini::new();
+	/// replace the input backend, e.g. with a backend::MockBackend for testing
+	/// call before load; devices already open are closed
+	pub fn set_backend( &mut self, api: Box<dyn InputBackend> ) {
+		self.devices.clear();
+		self.paths.clear();
+		self.backend = Some( api );
+	}
+
+	/// replace the hot-plug source, e.g. with one that reads made up uevents from a pipe
+	pub fn set_hotplug( &mut self, source: Box<dyn ArrivalSource> ) {
+		self.hotplug = Some( source );
+	}
+
+	/* *************************************************************************** */
+	/// set up devices from an .ini file, e.g. CONFIG_FILE
+	#[named]
+	pub fn load_file( &mut self, path: &str ) -> Vec<Message> {
+		let mut ret: Vec<Message> = Vec::new();
+		let mut config = ini_file::parser();
+
+		match config.load( path ) {
+			Ok( res ) => {
+				self.config_file = Some( path.to_string() );
+				ret.append( &mut self.load( res ) );
+			}
+			Err(err) => {
+				ret.push( show_error(module_path!(), function_name!(), err));
+			}
+		}
+		ret
+	}
 
-	match config.load("./config/joystick_monitor.ini") {
-		Ok( res ) => {
-			for (k,v) in res {
-				if k.to_lowercase() == "comment" { continue; }
-				let mut js = Joystick::new();
-				ret.append( &mut js.set_config_values(v) );
-				unsafe {
-					ret.push( js.set_name() );
-					JOYSTICKS.push(js);
+	/* *************************************************************************** */
+	/// set up devices from a parsed .ini file
+	/// the [Monitor] section holds program settings, [Layout] where devices are shown,
+	/// [Comment] sections are ignored, all other sections are devices
+	#[named]
+	pub fn load( &mut self, mut config: HashMap<String, HashMap<String, Option<String>>> ) -> Vec<Message> {
+		let mut ret: Vec<Message> = Vec::new();
+
+		if let Some( settings ) = config.remove( "monitor" ) {
+			let mut replay: Option<String> = None;
+			let mut replay_speed: f64 = 1.0;
+			let mut replay_loop: bool = false;
+			let mut hotplug: String = "interval".to_string();
+			let mut rescan_interval: u64 = RESCAN_INTERVAL;
+			for (key, value) in settings {
+				match (key.as_str(), value) {
+					("backend", Some( name )) => {
+						match backend::backend_from_name( &name ) {
+							Ok( api ) => {	self.set_backend( api );	}
+							Err( err ) => {
+								ret.push( show_error(module_path!(), function_name!(), err));
+							}
+						}
+					}
+					("record", Some( path )) => {
+						match Recorder::create( path.trim() ) {
+							Ok( r ) => {	self.recorder = Some( r );	}
+							Err( err ) => {
+								ret.push( show_error(module_path!(), function_name!(), err));
+							}
+						}
+					}
+					("replay", Some( path )) => {	replay = Some( path.trim().to_string() );	}
+					("replay_speed", Some( val )) => {
+						match val.trim().parse::<f64>() {
+							Ok( speed ) => {	replay_speed = speed;	}
+							Err( err ) => {
+								ret.push( show_error(module_path!(), function_name!(),
+												format!("Error reading 'replay_speed' {}", err)));
+							}
+						}
+					}
+					("replay_loop", Some( val )) => {
+						match val.trim().parse::<bool>() {
+							Ok( b ) => {	replay_loop = b;	}
+							Err( err ) => {
+								ret.push( show_error(module_path!(), function_name!(),
+												format!("Error reading 'replay_loop' {}", err)));
+							}
+						}
+					}
+					("comment", _) => {}
+					("hotplug", Some( val )) => {	hotplug = val.trim().to_lowercase();	}
+					("rescan_interval", Some( val )) => {
+						match val.trim().parse::<u64>() {
+							Ok( ms ) => {	rescan_interval = ms;	}
+							Err( err ) => {
+								ret.push( show_error(module_path!(), function_name!(),
+												format!("Error reading 'rescan_interval' {}", err)));
+							}
+						}
+					}
+					(other, _) => {
+						ret.push( show_error(module_path!(),
+											function_name!(),
+											format!("Unknown key: [Monitor] {}", other) ) );
+					}
 				}
 			}
+			match hotplug.as_str() {
+				"interval" => {
+					self.set_hotplug( Box::new( IntervalSource::new( Duration::from_millis( rescan_interval ) ) ) );
+				}
+				#[cfg(target_os = "linux")]
+				"udev" => {
+					match crate::hotplug::UeventSource::netlink() {
+						Ok( source ) => {	self.set_hotplug( Box::new( source ) );	}
+						Err( err ) => {
+							ret.push( show_error(module_path!(), function_name!(),
+											format!("Could not listen for uevents, {}", err)));
+							self.set_hotplug( Box::new( IntervalSource::new( Duration::from_millis( rescan_interval ) ) ) );
+						}
+					}
+				}
+				"off" => {	self.set_hotplug( Box::new( NoSource ) );	}
+				other => {
+					ret.push( show_error(module_path!(), function_name!(),
+									format!("Unknown hotplug: {}", other)));
+				}
+			}
+			// replay takes the place of any other backend, devices are read from the file
+			if let Some( path ) = replay {
+				match ReplayBackend::load( &path, replay_speed, replay_loop ) {
+					Ok( api ) => {	self.set_backend( Box::new( api ) );	}
+					Err( err ) => {
+						ret.push( show_error(module_path!(), function_name!(), err));
+					}
+				}
+			}
+		}
+
+		if let Some( values ) = config.remove( "layout" ) {
+			let (layout, errors) = Layout::from_config( values );
+			self.layout = layout;
+			for err in errors {
+				ret.push( show_error(module_path!(), function_name!(), err));
+			}
+		}
+
+		// the .ini file's order is lost as it is read, so devices are loaded in order of
+		// their section names: the same order each time, and the same first of two identical devices
+		let mut sections: Vec<(String, HashMap<String, Option<String>>)> = config.into_iter().collect();
+		sections.sort_by( |(a, _), (b, _)| a.cmp( b ) );
+		for (k,v) in sections {
+			if k.to_lowercase() == "comment" { continue; }
+			let (mut js, mut mssgs) = Joystick::from_config( &k, v );
+			ret.append( &mut mssgs );
+			if self.joysticks.iter().any( |other| other.key == js.key ) {
+				// two identical devices, and nothing to tell them apart: only the first is shown
+				ret.push( show_error(module_path!(), function_name!(),
+								format!("[{}] is the same device as another section ({}), add a serial, path or interface_number",
+										k, js.vid_pid())));
+				continue;
+			}
+			if js.is_virtual {
+				self.set_virtual( &js );
+				self.joysticks.push(js);
+				continue;
+			}
+			ret.push( self.open( &mut js ) );
+			if self.devices.contains_key(&js.key) {
+				self.set_connection( &js, Connection::Connected( Connection::now() ) );
+			} else {
+				self.set_connection( &js, Connection::NotFound );
+			}
+			self.joysticks.push(js);
+		}
+		ret.append( &mut self.plan_routes() );
+		let sections: Vec<&str> = self.joysticks.iter().map( |js| js.section.as_str() ).collect();
+		for name in self.layout.unknown( &sections ) {
+			ret.push( show_error(module_path!(), function_name!(),
+							format!("[Layout] {}: no such device section", name)));
+		}
+		ret
+	}
+
+	/* *************************************************************************** */
+	/// read every device, and bring the reports up to date; call once a frame
+	#[named]
+	pub fn poll( &mut self ) -> Vec<Message> {
+		let mut ret: Vec<Message> = Vec::new();
+		if self.hotplug_due() {
+			ret.append( &mut self.rescan() );
 		}
-		Err(err) => {
-			ret.push( show_error(module_path!(), function_name!(), err));
+
+		let joysticks = self.joysticks.clone();
+		for js in joysticks.iter().filter( |js| !js.is_virtual ) {
+			let (reports, mut mssgs) = self.read_device( js );
+			ret.append(&mut mssgs );
+
+			// buttons pressed by any of the reports, not just the newest
+			let mut pressed: Vec<bool> = Vec::new();
+			for (at, buff) in &reports {
+				if js.log_device {
+					let mut buff_st:String = "".to_string();
+					for b in buff {
+						buff_st = format!("{}\t{}", buff_st, b).to_string();
+					}
+					#[cfg(feature = "logging")] {
+						info!("{}::{} -> {}: {}", module_path!(), function_name!(), js.vid_pid(), buff_st);
+					}
+				}
+				ret.push( self.record( |r| r.report( &js.key, *at, buff ) ) );
+				self.make_device_report( js, buff );
+				if let Some( dr ) = self.reports.get( &js.key ) {
+					pressed.resize( pressed.len().max( dr.buttons.len() ), false );
+					for (all, b) in pressed.iter_mut().zip( dr.buttons.iter() ) {
+						*all |= b.pressed;
+					}
+				}
+			}
+			if reports.is_empty() {
+				// nothing waiting: the device has not changed, its newest report still holds
+				// (without any taps that were only shown for the last frame)
+				match self.reports.get( &js.key ) {
+					Some( dr ) if !dr.report.is_empty() => {
+						let newest = dr.report.clone();
+						self.make_device_report( js, &newest );
+					}
+					Some( _ ) => {}
+					None => {	self.make_device_report( js, &[] );	}
+				}
+			}
+			if let Some( dr ) = self.reports.get_mut( &js.key ) {
+				if !reports.is_empty() {
+					for (b, p) in dr.buttons.iter_mut().zip( pressed ) {
+						b.pressed = p;
+					}
+				}
+				dr.drained = reports.len();
+			}
 		}
+		ret.push( self.record( |r| r.flush() ) );
+
+		self.do_routes();
+		self.do_buttons_from();
+		ret
 	}
-	unsafe {
-		TIME_OUT = frame_rate / (JOYSTICKS.len() as i32 + 1);
+
+	/* *************************************************************************** */
+	/// the configured devices, in order of their section names
+	pub fn joysticks( &self ) -> &[Joystick] {
+		&self.joysticks
 	}
-	ret
-}
 
-/* ******************************************************************************* */
+	/// where the devices are shown, from the [Layout] section
+	pub fn layout( &self ) -> &Layout {
+		&self.layout
+	}
+
+	/// the .ini file the devices were loaded from, None if they were not loaded from a file
+	pub fn config_file( &self ) -> Option<&str> {
+		self.config_file.as_deref()
+	}
 
-fn make_device_report(js : &Joystick, buff : &[u8]) {
-	unsafe {
-		match DEVICES_REPORTS.get(&js.hash) {
+	/// the latest report of every configured device
+	pub fn reports( &self ) -> &HashMap<DeviceKey, DeviceReport> {
+		&self.reports
+	}
+
+	/// the latest report of one device
+	pub fn device( &self, key: &DeviceKey ) -> Option<&DeviceReport> {
+		self.reports.get( key )
+	}
+
+	/* *************************************************************************** */
+	/// the configured devices that are read (not virtual), in order of their section names
+	pub fn devices( &self ) -> Vec<DeviceInfo> {
+		self.joysticks.iter()
+			.filter( |js| !js.is_virtual )
+			.map( |js| DeviceInfo {
+				key: js.key.clone(),
+				section: js.section.clone(),
+				name: match js.name().is_empty() {
+					true => js.section.clone(),
+					false => js.name().to_string(),
+				},
+			})
+			.collect()
+	}
+
+	/* *************************************************************************** */
+	/// the axes found in a device's last report, in its own units
+	pub fn raw_axes( &self, key: &DeviceKey ) -> Vec<AxisSample> {
+		let mut ret: Vec<AxisSample> = Vec::new();
+		let Some( js ) = self.joysticks.iter().find( |js| js.key == *key ) else { return ret; };
+		let Some( dr ) = self.reports.get( key ) else { return ret; };
+		let layout = self.layouts.get( key ).filter( |_| js.auto_map );
+		for (name, axis, usage, nth) in js.axes() {
+			let value = match axis.is_mapped() {
+				true => axis.read( &dr.report ),
+				false => layout.and_then( |l| descriptor_axis( axis, l, usage, nth, &dr.report ) ),
+			};
+			if let Some( (value, min, max) ) = value {
+				ret.push( AxisSample { axis: name, value, min, max, centred: !name.starts_with( "slider" ) } );
+			}
+		}
+		ret
+	}
+
+	/* *************************************************************************** */
+	/// change one setting of a running device, as if it had been read from the .ini file
+	#[named]
+	pub fn set_config_value( &mut self, key: &DeviceKey, name: &str, value: &str ) -> Vec<Message> {
+		match self.joysticks.iter_mut().find( |js| js.key == *key ) {
+			Some( js ) => {
+				let mut value_map: HashMap<String, Option<String>> = HashMap::new();
+				value_map.insert( name.to_string(), Some( value.to_string() ) );
+				let mut ret = js.set_config_values( value_map );
+				// a widget, an axis' label, the display, an image or the skin, may have changed
+				let widgets = js.report_widgets();
+				let display = js.display();
+				let images = js.images.clone();
+				let skin = js.skin.clone();
+				if let Some( dr ) = self.reports.get_mut( key ) {
+					dr.widgets = widgets;
+					dr.display = display;
+					dr.images = images;
+					dr.skin = skin;
+				}
+				if name.ends_with( "_route" ) || name.starts_with( "echo_" ) || name == "buttons_from" {
+					ret.append( &mut self.plan_routes() );
+				}
+				// names and groups are set when the buttons are first read, have them set again
+				if name.starts_with( "button" ) {
+					if let Some( dr ) = self.reports.get_mut( key ) {
+						dr.buttons.clear();
+					}
+				}
+				ret
+			}
+			None => {
+				vec![ show_error(module_path!(), function_name!(), format!("No device {}", key)) ]
+			}
+		}
+	}
+
+	/* *************************************************************************** */
+	/// the backend in use, hidapi unless another has been set
+	fn backend( &mut self ) -> Result<&mut dyn InputBackend, String> {
+		if self.backend.is_none() {
+			self.backend = Some( Box::new( HidApiBackend::new()? ) );
+		}
+		match self.backend.as_mut() {
+			Some( b ) => {	Ok( b.as_mut() )	}
+			None => {	Err( "No backend".to_string() )	}
+		}
+	}
+
+	/// a device's own backend, if it has one, else the backend in use
+	fn backend_named( &mut self, name: &Option<String> ) -> Result<&mut dyn InputBackend, String> {
+		match name {
+			None => {	self.backend()	}
+			Some( name ) => {
+				if !self.backends.contains_key( name ) {
+					self.backends.insert( name.clone(), backend::backend_from_name( name )? );
+				}
+				match self.backends.get_mut( name ) {
+					Some( b ) => {	Ok( b.as_mut() )	}
+					None => {	Err( format!("Backend {} not loaded", name) )	}
+				}
+			}
+		}
+	}
+
+	/* *************************************************************************** */
+	/// is this the device described in the .ini file, and not already open for another section?
+	fn is_device( &self, js: &Joystick, device_info: &DeviceEntry ) -> bool {
+		if !js.key.matches( device_info ) {
+			return false;
+		}
+		!self.paths.iter().any( |(key, path)| *key != js.key && *path == device_info.path )
+	}
+
+	/* *************************************************************************** */
+	/// find and open the device, naming it and reading its layout on the way
+	#[named]
+	fn open( &mut self, js: &mut Joystick ) -> Message {
+		let entries = match self.backend_named( &js.backend ) {
+			Ok( api ) => {	api.devices()	}
+			Err( err ) => {
+				return show_error(module_path!(), function_name!(), err);
+			}
+		};
+		let Some( device_info ) = entries.into_iter().find( |e| self.is_device( js, e ) ) else {
+			return Message::None;
+		};
+		let opened = match self.backend_named( &js.backend ) {
+			Ok( api ) => {	api.open( &device_info )	}
+			Err( err ) => {	Err( err )	}
+		};
+		let device = match opened {
+			Ok( device ) => {	device	}
+			Err( err ) => {
+				return show_error(module_path!(), function_name!(),
+								format!("{} - could not open {}",
+											err, js.vid_pid()));
+			}
+		};
+
+		let mut ret: Message = Message::None;
+		match device.product_string() {
+			Ok( pr_string ) => {
+				if let Some( name ) = pr_string {
+					if let Message::Err( err ) = self.record( |r| r.name( &js.key, &name ) ) {
+						ret = Message::Err( err );
+					}
+					js.name = name;
+				}
+			}
+			Err( err ) => {
+				ret = show_error(module_path!(), function_name!(),
+					format!("Error {} - could not name device {}",
+									err, js.vid_pid()) );
+			}
+		}
+		if js.auto_map {
+			if let Message::Err( err ) = self.read_layout( js, device.as_ref() ) {
+				ret = Message::Err( err );
+			}
+		}
+		match js.open_device( device ) {
+			Ok( device ) => {
+				self.devices.insert(js.key.clone(), device);
+				self.paths.insert(js.key.clone(), device_info.path.clone());
+			}
+			Err( err ) => {
+				ret = show_error(module_path!(), function_name!(),
+								format!("{} - could not start reading {}",
+											err, js.vid_pid()));
+			}
+		}
+		ret
+	}
+
+	/* *************************************************************************** */
+	/// fetch and parse the device's report descriptor, so that axes and
+	/// buttons not given in the .ini file can be found automatically
+	#[named]
+	fn read_layout( &mut self, js: &Joystick, device: &dyn InputDevice ) -> Message {
+		let mut buff = [0u8; MAX_REPORT_DESCRIPTOR_SIZE];
+		match device.report_descriptor( &mut buff ) {
+			Ok( len ) => {
+				if let Message::Err( err ) = self.record( |r| r.descriptor( &js.key, &buff[ ..len ] ) ) {
+					return Message::Err( err );
+				}
+				match ReportLayout::parse( &buff[ ..len ] ) {
+					Ok( mut layout ) => {
+						if js.log_device {
+							#[cfg(feature = "logging")] {
+								for field in &layout.fields {
+									info!("{}::{} -> {}: {:?}", module_path!(), function_name!(), js.vid_pid(), field);
+								}
+							}
+						}
+						// keep what fits, the rest cannot be read
+						let mut ret = Message::None;
+						let report_len = layout.report_len();
+						let dropped = layout.truncate( DEV_BUF_LEN );
+						if !dropped.is_empty() {
+							let usages: Vec<String> = dropped.iter()
+								.map( |f| format!("{:02X}:{:02X}", f.usage_page, f.usage) )
+								.collect();
+							ret = show_error(module_path!(), function_name!(),
+								format!("{} reports are {} bytes, buffer is {} (DEV_BUF_LEN), inputs past it are not read (page:usage): {}",
+										js.vid_pid(), report_len, DEV_BUF_LEN, usages.join( ", " )) );
+						}
+						self.layouts.insert(js.key.clone(), layout);
+						ret
+					}
+					Err( err ) => {
+						show_error(module_path!(), function_name!(),
+							format!("{} could not parse report descriptor: {}", js.vid_pid(), err) )
+					}
+				}
+			}
+			Err( err ) => {
+				show_error(module_path!(), function_name!(),
+					format!("{} could not read report descriptor: {}", js.vid_pid(), err) )
+			}
+		}
+	}
+
+	/* *************************************************************************** */
+	/// returns every report waiting (none if nothing was waiting) with when it was read, and any error messages
+	#[named]
+	fn read_device( &mut self, js: &Joystick ) -> (Vec<(Instant, Vec<u8>)>, Vec<Message>) {
+		let mut ret: Vec<Message> = Vec::new();
+		let mut reports: Vec<(Instant, Vec<u8>)> = Vec::new();
+
+		match self.devices.get(&js.key) {
+			Some( dev ) => {
+				match read_open_device(dev, &mut reports) {
+					Ok( () ) => {
+						if reports.iter().any( |(_, r)| r.len() > DEV_BUF_LEN ) {
+							ret.push(
+								show_error(module_path!(), function_name!(),
+									format!(
+										"Device buffer too small (DEV_BUF_LEN = {})",
+										DEV_BUF_LEN) ) );
+						}
+					}
+					Err( err ) => {
+						// drop the handle, the device is looked for again when hot-plug says so
+						ret.push( show_error(module_path!(), function_name!(),
+								format!("{} {}",js.vid_pid(), err))) ;
+						self.devices.remove(&js.key);
+						self.paths.remove(&js.key);
+						self.set_connection( js, Connection::Disconnected( Connection::now() ) );
+					}
+				}
+			}
+			None => {
+				// not open: might be sleeping, or unplugged
+			}
+		}
+
+		(reports, ret)
+	}
+
+	/* *************************************************************************** */
+	/// has the hot-plug source said to look again for devices?
+	fn hotplug_due( &mut self ) -> bool {
+		self.hotplug.get_or_insert_with(|| {
+			Box::new( IntervalSource::new( Duration::from_millis( RESCAN_INTERVAL ) ) )
+		}).poll()
+	}
+
+	/* *************************************************************************** */
+	/// look again for configured devices that are not open, and open any that have arrived
+	#[named]
+	fn rescan( &mut self ) -> Vec<Message> {
+		let mut ret: Vec<Message> = Vec::new();
+		if self.joysticks.iter().all( |js| js.is_virtual || self.devices.contains_key(&js.key) ) {
+			return ret;
+		}
+
+		// only the backends of the devices still missing; one not made yet is made, up to date,
+		// when the device is looked for
+		let mut missing: Vec<Option<String>> = Vec::new();
+		for js in self.joysticks.iter().filter( |js| !js.is_virtual && !self.devices.contains_key(&js.key) ) {
+			if !missing.contains( &js.backend ) {
+				missing.push( js.backend.clone() );
+			}
+		}
+		for name in missing {
+			let api = match &name {
+				None => {	self.backend.as_mut()	}
+				Some( name ) => {	self.backends.get_mut( name )	}
+			};
+			if let Some( Err( err ) ) = api.map( |api| api.refresh() ) {
+				ret.push( show_error(module_path!(), function_name!(), err));
+			}
+		}
+
+		for n in 0..self.joysticks.len() {
+			if self.joysticks[ n ].is_virtual || self.devices.contains_key(&self.joysticks[ n ].key) { continue; }
+			let mut js = self.joysticks[ n ].clone();
+			ret.push( self.open( &mut js ) );
+			if self.devices.contains_key(&js.key) {
+				let was = self.reports.get(&js.key).map( |dr| dr.connection.clone() );
+				match was {
+					Some( Connection::Disconnected( _ ) ) | Some( Connection::Reconnected( _ ) ) => {
+						self.set_connection( &js, Connection::Reconnected( Connection::now() ) );
+					}
+					_ => {
+						self.set_connection( &js, Connection::Connected( Connection::now() ) );
+					}
+				}
+				#[cfg(feature = "logging")] {
+					info!("{}::{} {} found", module_path!(), function_name!(), js.vid_pid());
+				}
+			}
+			self.joysticks[ n ] = js;
+		}
+		ret
+	}
+
+	/* *************************************************************************** */
+	/// record whether a device is there, on its report
+	fn set_connection( &mut self, js: &Joystick, connection: Connection ) {
+		let dr = self.reports.entry(js.key.clone()).or_insert_with( || DeviceReport::new( js ) );
+		dr.name = js.name().to_string();
+		dr.connection = connection;
+	}
+
+	/// a virtual device's report: centred until its routes say otherwise
+	fn set_virtual( &mut self, js: &Joystick ) {
+		self.set_connection( js, Connection::Virtual );
+		if let Some( dr ) = self.reports.get_mut( &js.key ) {
+			dr.error = false;
+			for axis in AXES {
+				if let Some( (value, raw) ) = dr.axis_mut( axis ) {
+					*value = js_mid();
+					*raw = js_mid();
+				}
+			}
+		}
+	}
+
+	/* *************************************************************************** */
+	/// write to the capture file, if recording
+	/// on error, recording stops: the error is reported once, rather than every frame
+	#[named]
+	fn record<F>( &mut self, write: F ) -> Message where F: FnOnce( &mut Recorder ) -> Result<(), String> {
+		if let Some( r ) = &mut self.recorder {
+			if let Err( err ) = write( r ) {
+				self.recorder = None;
+				return show_error(module_path!(), function_name!(),
+									format!("Recording stopped: {}", err) );
+			}
+		}
+		Message::None
+	}
+
+	/* *************************************************************************** */
+
+	/// find the devices every route (and virtual device's buttons_from) reads from,
+	/// and the order to work the routes out in
+	#[named]
+	fn plan_routes( &mut self ) -> Vec<Message> {
+		let routes: Vec<(DeviceKey, Route)> = self.joysticks.iter()
+			.flat_map( |js| js.routes.iter().map( |r| (js.key.clone(), r.clone()) ) )
+			.collect();
+		let sections: Vec<(String, DeviceKey)> = self.joysticks.iter()
+			.map( |js| (js.section.clone(), js.key.clone()) )
+			.collect();
+		let (routes, mut errors) = routing::plan( &routes, &sections );
+		self.routes = routes;
+
+		self.buttons_from = Vec::new();
+		for js in &self.joysticks {
+			if js.buttons_from.is_empty() { continue; }
+			if !js.is_virtual {
+				errors.push( format!("[{}] buttons_from is for virtual devices (no vid and pid)", js.section) );
+				continue;
+			}
+			let mut sources: Vec<DeviceKey> = Vec::new();
+			for device in &js.buttons_from {
+				match routing::find_device( device, &js.key, &sections ) {
+					Some( key ) if self.joysticks.iter().any( |other| other.key == key && !other.is_virtual ) => {
+						sources.push( key );
+					}
+					Some( _ ) => {
+						errors.push( format!("[{}] buttons_from '{}' is not a device that is read", js.section, device) );
+					}
+					None => {
+						errors.push( format!("[{}] buttons_from: no device '{}'", js.section, device) );
+					}
+				}
+			}
+			self.buttons_from.push( (js.key.clone(), sources) );
+		}
+
+		errors.into_iter()
+			.map( |err| show_error(module_path!(), function_name!(), err) )
+			.collect()
+	}
+
+	/// virtual devices' buttons (and hats): those of each device they are made from, one after
+	/// the other, numbered on from the last device's, each device's groups kept apart from the others'
+	fn do_buttons_from( &mut self ) {
+		for (key, sources) in &self.buttons_from {
+			let defs = self.joysticks.iter().find( |js| js.key == *key ).map( |js| js.button_defs.as_slice() ).unwrap_or( &[] );
+			let mut list: Vec<Button> = Vec::new();
+			let mut hat_list: Vec<Hat> = Vec::new();
+			for source in sources {
+				let section = self.joysticks.iter().find( |js| js.key == *source ).map( |js| js.section.as_str() ).unwrap_or( "" );
+				if let Some( dr ) = self.reports.get( source ) {
+					for b in &dr.buttons {
+						// the virtual device's own button_<n> keys may rename or regroup them
+						let mut button = buttons::button( list.len() + 1, defs, b.pressed );
+						if !defs.iter().any( |d| d.number == button.number && d.name.is_some() ) {
+							button.name = b.name.clone();
+						}
+						if !defs.iter().any( |d| d.number == button.number && d.group.is_some() ) {
+							button.group = format!("{} {}", section, b.group);
+						}
+						list.push( button );
+					}
+					for h in &dr.hats {
+						hat_list.push( Hat { number: hat_list.len() + 1, ..h.clone() } );
+					}
+				}
+			}
+			if let Some( dr ) = self.reports.get_mut( key ) {
+				dr.buttons = list;
+				dr.hats = hat_list;
+			}
+		}
+	}
+
+	/// set routed axes from their sources, both calibrated and raw
+	fn do_routes( &mut self ) {
+		for (key, route) in &self.routes {
+			let mut values: Vec<f64> = Vec::new();
+			let mut raws: Vec<f64> = Vec::new();
+			for source in &route.sources {
+				let value = source.key.as_ref()
+					.and_then( |k| self.reports.get( k ) )
+					.and_then( |dr| dr.axis( source.axis ) );
+				if let Some( (value, raw) ) = value {
+					values.push( value as f64 / JS_MAX as f64 );
+					raws.push( raw as f64 / JS_MAX as f64 );
+				}
+			}
+			if values.len() < route.sources.len() { continue; }
+			if let Some( (value, raw) ) = self.reports.get_mut( key ).and_then( |dr| dr.axis_mut( route.target ) ) {
+				*value = (route.apply( &values ) * JS_MAX as f64).round() as u16;
+				*raw = (route.apply( &raws ) * JS_MAX as f64).round() as u16;
+			}
+		}
+	}
+
+	/* *************************************************************************** */
+
+	fn make_device_report( &mut self, js : &Joystick, buff : &[u8] ) {
+		let layout = self.layouts.get(&js.key).filter(|_| js.auto_map);
+		match self.reports.get_mut(&js.key) {
 			None => {
 				let dr = DeviceReport::new( js ) ; // DeviceReport::new( js.name.clone(), js.col, js.x_calibrate, js.y_calibrate, js.z_calibrate);
-				DEVICES_REPORTS.insert(js.hash, dr);
+				self.reports.insert(js.key.clone(), dr);
 			}
 
 			Some( dr ) => {
-				let js = js.clone();
-				let mut dr = dr.clone();
-				if buff[ 0 ] == 0 {
+				if no_data( layout, buff ) {
 					dr.error = true;
 				} else {
-					dr.x = device_report_axis(js.x, buff);	//(js.x.a0, js.x.a1, js.x.invert, buff);
-					dr.y = device_report_axis(js.y, buff);	//(js.y.a0, js.y.a1, js.y.invert, buff);
-					dr.z = device_report_axis(js.z, buff);	//(js.z.a0, js.z.a1, js.z.invert, buff);
-					dr.rx = device_report_axis(js.rx, buff);	//(js.rx.a0, js.rx_1, js.rx.invert, buff);
-					dr.ry = device_report_axis(js.ry, buff);	//(js.ry.a0, js.ry_1, js.ry.invert, buff);
-					dr.rz = device_report_axis(js.rz, buff);	//(js.rz_0, js.rz_1, js.rz.invert, buff);
-					dr.slider_0 = device_report_axis(js.slider_0, buff);	//(js.slider_0_0, js.slider_0_1, js.slider_0_invert, buff);
-					dr.slider_1 = device_report_axis(js.slider_1, buff);	//(js.y_0, js.y_1, js.y_invert, buff);
+					dr.report = buff.to_vec();
+					for ((_, axis, usage, nth), (value, raw)) in js.axes().into_iter().zip( dr.axes_mut() ) {
+						if let Some( (r, v) ) = device_report_mapped_axis( axis, layout, usage, nth, buff ) {
+							*raw = r;
+							*value = v;
+						}
+					}
 
 				//if buff[ 0 ] == 0 {
 				//	dr.error = true;
@@ -642,12 +2005,23 @@ fn make_device_report(js : &Joystick, buff : &[u8]) {
 					}
 				}
 
-				dr.buttons = Vec::new();
-
-				for b in 0..js.buttons.len() {
-					dr.buttons.push(buff[ js.buttons[ b ]]); // .push( buff[ b ]);
+				// the buttons key's bytes (or the descriptor's buttons), and any given one at a time
+				let sources = buttons::sources( &js.buttons, &js.button_defs, layout );
+				let pressed = buttons::read( &sources, layout, buff );
+				let same = dr.buttons.len() == sources.len()
+					&& dr.buttons.iter().zip( sources.iter() ).all( |(b, (n, _))| b.number == *n );
+				if same {
+					for (b, p) in dr.buttons.iter_mut().zip( pressed ) {
+						if let Some( p ) = p {
+							b.pressed = p;
+						}
+					}
+				} else {
+					dr.buttons = sources.iter().zip( pressed )
+						.map( |((n, _), p)| buttons::button( *n, &js.button_defs, p.unwrap_or( false ) ) )
+						.collect();
 				}
-				DEVICES_REPORTS.insert(js.hash, dr);
+				dr.hats = hats::read( &js.hat_defs, layout, buff, &dr.buttons, &dr.hats );
 			}
 		}
 	}
@@ -655,13 +2029,83 @@ fn make_device_report(js : &Joystick, buff : &[u8]) {
 
 /* ******************************************************************************* */
 
-fn num_to_usize( value: &String) -> usize {
-	match i8::from_str_radix( value.as_str(), 10) {
+/// is there nothing in this report? a zero report id, as sleeping devices send
+/// without numbered reports, a zero first byte can be real data
+fn no_data( layout: Option<&ReportLayout>, buff: &[u8] ) -> bool {
+	match layout {
+		Some( l ) if !l.uses_report_ids => buff.is_empty(),
+		_ => buff.is_empty() || buff[ 0 ] == 0,
+	}
+}
+
+/* ******************************************************************************* */
+
+fn device_report_axis( axis: &JoystickAxis, layout: Option<&ReportLayout>, buff : &[u8] ) -> (u16, u16) {
+	if no_data( layout, buff )	{ return (js_mid(), js_mid()); }
+
+	let Some( (value, min, max) ) = axis.read( buff ) else { return (js_mid(), js_mid()); };
+	axis.report_value( value, min, max )
+}
+
+/* ******************************************************************************* */
+/// an axis' value: from the offsets in the .ini file if given, else from the report descriptor
+/// returns (raw, calibrated), or None when the axis is not in this report, the previous value should be kept
+fn device_report_mapped_axis( axis: &JoystickAxis, layout: Option<&ReportLayout>,
+								usage: u16, nth: usize, buff : &[u8] ) -> Option<(u16, u16)> {
+	if axis.is_mapped() {
+		// offsets given by hand are in the first of numbered reports, other reports hold other things
+		if layout.is_some_and( |l| l.uses_report_ids && buff.first() != Some( &l.first_report_id() ) ) {
+			return None;
+		}
+		return Some( device_report_axis( axis, layout, buff ) );
+	}
+	let (value, min, max) = descriptor_axis( axis, layout?, usage, nth, buff )?;
+	Some( axis.report_value( value, min, max ) )
+}
+
+/* ******************************************************************************* */
+/// an axis found from the report descriptor: its value, and the range it is in
+fn descriptor_axis( axis: &JoystickAxis, layout: &ReportLayout,
+					usage: u16, nth: usize, buff : &[u8] ) -> Option<(i32, i32, i32)> {
+	let field = layout.find( descriptor::USAGE_PAGE_GENERIC_DESKTOP, usage, nth )?;
+	// an encoding's min and max, if given, correct a descriptor that has them wrong
+	let (min, max) = match &axis.encoding {
+		Some( e ) => (e.logical_min.unwrap_or( field.logical_min ), e.logical_max.unwrap_or( field.logical_max )),
+		None => (field.logical_min, field.logical_max),
+	};
+	Some( (field.read( buff, layout.uses_report_ids )?, min, max) )
+}
+
+/* ******************************************************************************* */
+/// scale a value in the range min..max to the range 0..JS_MAX
+fn scale_value( value: i32, min: i32, max: i32 ) -> u16 {
+	let range = max as i64 - min as i64;
+	if range <= 0 { return js_mid(); }
+	let value = (value as i64).clamp( min as i64, max as i64 );
+	((value - min as i64) * JS_MAX as i64 / range) as u16
+}
+
+/* ******************************************************************************* */
+
+fn dec_to_u16( value: &str) -> u16 {
+	value.trim().parse::<u16>().unwrap_or( 0 )
+}
+
+/* ******************************************************************************* */
+
+fn hex_to_u16( value: &str) -> u16 {
+	u16::from_str_radix( value.trim(), 16).unwrap_or( 0 )
+}
+
+/* ******************************************************************************* */
+
+fn num_to_usize( value: &str) -> usize {
+	match value.trim().parse::<i64>() {
 		Ok( val) => {
 			if val >= 0 {
 				return val as usize;
 			}
-			return std::usize::MAX;
+			usize::MAX
 		}
 		Err( _e ) => { 0 }
 	}
@@ -677,6 +2121,462 @@ pub fn show_error(module_path: &str, function_name: &str, message: String) -> Me
 	Message::Err( inf )
 }
 
+/* ******************************************************************************* */
+/* Tests */
+/* ******************************************************************************* */
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use crate::backend::mock::{MockBackend, MockDevice, MockScript, };
+	use crate::hotplug::NoSource;
+
+	const STICK: &str = "
+[Stick]
+vid = 3344
+pid = 0001
+x = 1, 2
+y = 3, 4
+buttons = 5
+auto_map = false
+";
+
+	// X and Y in a byte each, then eight buttons, without report ids
+	const GAMEPAD: &[u8] = &[
+		0x05, 0x01, 0x09, 0x05, 0xA1, 0x01,
+		0x15, 0x00, 0x26, 0xFF, 0x00, 0x75, 0x08, 0x95, 0x02, 0x09, 0x30, 0x09, 0x31, 0x81, 0x02,
+		0x05, 0x09, 0x19, 0x01, 0x29, 0x08, 0x15, 0x00, 0x25, 0x01, 0x75, 0x01, 0x95, 0x08, 0x81, 0x02,
+		0xC0,
+	];
+
+	// report 1 has buttons 1 to 8, report 2 buttons 9 to 12 and a hat
+	const TWO_REPORTS: &[u8] = &[
+		0x05, 0x01, 0x09, 0x04, 0xA1, 0x01,
+		0x85, 0x01, 0x05, 0x09, 0x19, 0x01, 0x29, 0x08, 0x15, 0x00, 0x25, 0x01, 0x75, 0x01, 0x95, 0x08, 0x81, 0x02,
+		0x85, 0x02, 0x05, 0x09, 0x19, 0x09, 0x29, 0x0C, 0x15, 0x00, 0x25, 0x01, 0x75, 0x01, 0x95, 0x04, 0x81, 0x02,
+		0x05, 0x01, 0x09, 0x39, 0x15, 0x00, 0x25, 0x07, 0x75, 0x04, 0x95, 0x01, 0x81, 0x42,
+		0xC0,
+	];
+
+	/// a manager reading one mock device, set up by the .ini text
+	fn manager( device: MockDevice, ini: &str ) -> (DeviceManager, MockScript, DeviceKey) {
+		let mut backend = MockBackend::new();
+		let script = backend.add_device( device );
+		let mut devices = DeviceManager::new();
+		devices.set_backend( Box::new( backend ) );
+		devices.set_hotplug( Box::new( NoSource ) );
+		let errors = errors( devices.load( ini_file::parser().read( ini.to_string() ).unwrap() ) );
+		assert!( errors.is_empty(), "{:?}", errors );
+		let key = devices.devices()[ 0 ].key.clone();
+		(devices, script, key)
+	}
+
+	fn errors( mssgs: Vec<Message> ) -> Vec<String> {
+		mssgs.into_iter().filter_map( |m| match m { Message::Err( err ) => Some( err ), Message::None => None } ).collect()
+	}
+
+	/// the report of a virtual device, set up by the .ini text; for the tests of what shows reports
+	pub(crate) fn virtual_report( ini: &str ) -> DeviceReport {
+		let mut devices = DeviceManager::new();
+		let errors = errors( devices.load( ini_file::parser().read( ini.to_string() ).unwrap() ) );
+		assert!( errors.is_empty(), "{:?}", errors );
+		devices.reports().values().next().unwrap().clone()
+	}
+
+	#[test]
+	fn axes() {
+		assert_eq!( find_axis( " Slider_0 " ), Ok( "slider_0" ) );
+		assert!( find_axis( "w" ).is_err() );
+		assert!( find_axis( "" ).is_err() );
+	}
+
+	#[test]
+	fn axis_keys() {
+		let mut values: HashMap<String, Option<String>> = HashMap::new();
+		values.insert( "slider_1".to_string(), Some( "3, 4, Flaps".to_string() ) );
+		values.insert( "w_route".to_string(), Some( "self:x".to_string() ) );
+		values.insert( "w_widget".to_string(), Some( "dial".to_string() ) );
+		let (js, mssgs) = Joystick::from_config( "stick", values );
+		let errors = errors( mssgs );
+		assert_eq!( errors.len(), 2, "{:?}", errors );
+		assert!( errors.iter().any( |e| e.ends_with( "Error reading 'w_route' unknown axis 'w'" ) ), "{:?}", errors );
+		assert!( errors.iter().any( |e| e.ends_with( "Error reading 'w_widget' unknown axis 'w'" ) ), "{:?}", errors );
+		assert!( js.axis( "slider_1" ).is_some_and( |a| a.is_mapped() ) );
+		assert_eq!( crate::learn::TARGETS[ ..AXES.len() ], AXES );
+	}
+
+	#[test]
+	fn mapped_axes_and_buttons() {
+		let (mut devices, script, key) = manager( MockDevice::new( 0x3344, 0x0001, "Test Stick" ), STICK );
+		let dr = devices.device( &key ).unwrap();
+		assert!( matches!( dr.connection, Connection::Connected( _ ) ) );
+		assert_eq!( dr.name, "Test Stick" );
+
+		script.push_report( &[ 0x01, 0x75, 0x30, 0x00, 0x00, 0b0000_0101 ] );
+		script.settle();
+		assert!( errors( devices.poll() ).is_empty() );
+		let dr = devices.device( &key ).unwrap();
+		assert_eq!( (dr.x, dr.y), (JS_MAX / 2, 0) );
+		assert!( !dr.error );
+		let pressed: Vec<usize> = dr.buttons.iter().filter( |b| b.pressed ).map( |b| b.number ).collect();
+		assert_eq!( (dr.buttons.len(), pressed), (8, vec![ 1, 3 ]) );
+
+		// nothing waiting: the newest report still holds
+		assert!( errors( devices.poll() ).is_empty() );
+		let dr = devices.device( &key ).unwrap();
+		assert_eq!( (dr.x, dr.drained), (JS_MAX / 2, 0) );
+	}
+
+	#[test]
+	fn taps_are_kept_for_a_frame() {
+		let (mut devices, script, key) = manager( MockDevice::new( 0x3344, 0x0001, "Test Stick" ), STICK );
+		script.push_report( &[ 0x01, 0, 0, 0, 0, 0b0000_0010 ] );
+		script.push_report( &[ 0x01, 0, 0, 0, 0, 0 ] );
+		script.settle();
+		devices.poll();
+		let dr = devices.device( &key ).unwrap();
+		assert_eq!( dr.drained, 2 );
+		assert!( dr.buttons[ 1 ].pressed );
+		devices.poll();
+		assert!( !devices.device( &key ).unwrap().buttons[ 1 ].pressed );
+	}
+
+	#[test]
+	fn zero_report_id_is_no_data() {
+		let (mut devices, script, key) = manager( MockDevice::new( 0x3344, 0x0001, "Test Stick" ), STICK );
+		script.push_report( &[ 0x00, 0x75, 0x30, 0x75, 0x30, 0 ] );
+		script.settle();
+		devices.poll();
+		assert!( devices.device( &key ).unwrap().error );
+	}
+
+	#[test]
+	fn unplugged_and_back() {
+		let (mut devices, script, key) = manager( MockDevice::new( 0x3344, 0x0001, "Test Stick" ), STICK );
+		script.set_error( Some( "unplugged".to_string() ) );
+		script.settle();
+		assert_eq!( errors( devices.poll() ).len(), 1 );
+		assert!( matches!( devices.device( &key ).unwrap().connection, Connection::Disconnected( _ ) ) );
+
+		// looked for again when the hot-plug source says so
+		script.set_error( None );
+		devices.set_hotplug( Box::new( IntervalSource::new( Duration::ZERO ) ) );
+		script.push_report( &[ 0x01, 0, 0, 0x75, 0x30, 0 ] );
+		devices.poll();
+		// a reader thread, if any, starts as the device is opened
+		script.settle();
+		devices.poll();
+		let dr = devices.device( &key ).unwrap();
+		assert!( matches!( dr.connection, Connection::Reconnected( _ ) ) );
+		assert_eq!( dr.y, JS_MAX / 2 );
+	}
+
+	#[test]
+	#[cfg(target_os = "linux")]
+	fn rescans_leave_hidapi_alone() {
+		let mut devices = DeviceManager::new();
+		devices.set_hotplug( Box::new( IntervalSource::new( Duration::ZERO ) ) );
+		let ini = "[Pedals]\nvid = 3344\npid = 0001\nbackend = hidraw\n[Stick]\nvid = 3344\npid = 0002\nbackend = evdev\n";
+		devices.load( ini_file::parser().read( ini.to_string() ).unwrap() );
+		devices.poll();
+		devices.poll();
+		assert!( devices.backend.is_none() );
+		assert_eq!( devices.backends.len(), 2 );
+	}
+
+	#[test]
+	fn identical_devices_by_serial() {
+		let mut backend = MockBackend::new();
+		let left = backend.add_device( MockDevice::new( 0x3344, 0x0001, "Stick" ).with_serial( "L1" ) );
+		let right = backend.add_device( MockDevice::new( 0x3344, 0x0001, "Stick" ).with_serial( "R2" ) );
+		let mut devices = DeviceManager::new();
+		devices.set_backend( Box::new( backend ) );
+		devices.set_hotplug( Box::new( NoSource ) );
+		let ini = "[Left]\nvid = 3344\npid = 0001\nserial = L1\nx = 1, 2\nauto_map = false\n\
+					[Right]\nvid = 3344\npid = 0001\nserial = R2\nx = 1, 2\nauto_map = false\n\
+					[Again]\nvid = 3344\npid = 0001\nserial = R2\nx = 1, 2\nauto_map = false\n";
+		let errors = errors( devices.load( ini_file::parser().read( ini.to_string() ).unwrap() ) );
+		// [Again] comes first, by name
+		assert_eq!( errors.len(), 1 );
+		assert!( errors[ 0 ].contains( "[right] is the same device as another section" ), "{}", errors[ 0 ] );
+		let sections: Vec<&str> = devices.joysticks().iter().map( |js| js.section() ).collect();
+		assert_eq!( sections, vec![ "again", "left" ] );
+
+		left.push_report( &[ 1, 0, 0 ] );
+		right.push_report( &[ 1, 0xEA, 0x60 ] );
+		left.settle();
+		right.settle();
+		devices.poll();
+		let x = |serial: &str| devices.reports().iter().find( |(k, _)| k.serial.as_deref() == Some( serial ) ).unwrap().1.x;
+		assert_eq!( (x( "L1" ), x( "R2" )), (0, JS_MAX) );
+	}
+
+	fn encoding( text: &str ) -> Result<AxisEncoding, String> {
+		let mut ret = AxisEncoding::new();
+		ret.config_split_encoding( text ).map( |_| ret )
+	}
+
+	#[test]
+	fn encodings() {
+		// 10 bits, little endian, two bits in
+		let e = encoding( "bits=10, endian=little, bit_offset=2" ).unwrap();
+		assert_eq!( (e.range(), e.one_byte()), ((0, 1023), false) );
+		assert_eq!( e.read( 1, 2, &[ 0xFF, 0b1111_1100, 0b0000_0001 ] ), Some( 0x7F ) );
+		assert_eq!( e.read( 1, 9, &[ 0, 0 ] ), None );
+
+		let e = encoding( "bits=16, endian=big, signed=true" ).unwrap();
+		assert_eq!( e.range(), (-32768, 32767) );
+		assert_eq!( e.read( 0, 1, &[ 0xFF, 0xFE ] ), Some( -2 ) );
+		assert_eq!( e.read( 0, 1, &[ 0x01, 0x00 ] ), Some( 256 ) );
+
+		let e = encoding( "bits=4, bit_offset=4, signed=true" ).unwrap();
+		assert!( e.one_byte() );
+		assert_eq!( e.read( 0, 0, &[ 0x9F ] ), Some( -7 ) );
+
+		assert_eq!( encoding( "bits=8, min=10, max=200" ).unwrap().range(), (10, 200) );
+		assert!( encoding( "bits=12, bit_offset=6" ).is_err() );
+		assert!( encoding( "bits=0" ).is_err() );
+		assert!( encoding( "endian=middle" ).is_err() );
+		assert!( encoding( "bits=8, min=9, max=9" ).is_err() );
+		assert!( encoding( "width=8" ).is_err() );
+	}
+
+	#[test]
+	fn encoded_axis() {
+		let mut axis = JoystickAxis::new();
+		axis.config_split_axis( "3, 0, , true" );
+		axis.encoding = Some( encoding( "bits=8" ).unwrap() );
+		assert!( axis.is_mapped() );
+		assert_eq!( axis.read( &[ 1, 0, 0, 255 ] ), Some( (255, 0, 255) ) );
+		// inverted
+		assert_eq!( axis.report_value( 255, 0, 255 ), (0, 0) );
+		assert_eq!( scale_value( 5, 10, 20 ), 0 );
+		assert_eq!( scale_value( 15, 10, 20 ), JS_MAX / 2 );
+		assert_eq!( scale_value( 15, 20, 20 ), JS_MAX / 2 );
+
+		// by the axis' name, any other is an error
+		let mut values: HashMap<String, Option<String>> = HashMap::new();
+		values.insert( "slider_1_encoding".to_string(), Some( "bits=8".to_string() ) );
+		let (js, mssgs) = Joystick::from_config( "stick", values );
+		assert!( errors( mssgs ).is_empty() );
+		assert!( js.slider_1.encoding.as_ref().is_some_and( |e| e.one_byte() ) );
+		assert!( js.x.encoding.is_none() && js.slider_0.encoding.is_none() );
+		let mut values: HashMap<String, Option<String>> = HashMap::new();
+		values.insert( "w_encoding".to_string(), Some( "bits=8".to_string() ) );
+		let (js, mssgs) = Joystick::from_config( "stick", values );
+		let errors = errors( mssgs );
+		assert!( errors.len() == 1 && errors[ 0 ].ends_with( "Error reading 'w_encoding' unknown axis 'w'" ), "{:?}", errors );
+		assert!( js.slider_1.encoding.is_none() );
+	}
+
+	fn calibration( centred: bool, text: &str ) -> Result<AxisCalibration, String> {
+		let mut ret = AxisCalibration::new( centred );
+		ret.config_split_calibration( text ).map( |_| ret )
+	}
+
+	#[test]
+	fn calibrations() {
+		let c = calibration( true, "min=100, centre=500, max=1000" ).unwrap();
+		assert_eq!( c.apply( 500, 0, 1023 ), 0.5 );
+		assert_eq!( c.apply( 100, 0, 1023 ), 0.0 );
+		assert_eq!( c.apply( 1023, 0, 1023 ), 1.0 );
+		// each side of centre is scaled on its own
+		assert_eq!( c.apply( 300, 0, 1023 ), 0.25 );
+		assert_eq!( c.apply( 750, 0, 1023 ), 0.75 );
+
+		let c = calibration( true, "deadzone=0.1, outer_deadzone=0.1" ).unwrap();
+		assert_eq!( c.apply( 54, 0, 100 ), 0.5 );
+		assert_eq!( c.apply( 95, 0, 100 ), 1.0 );
+		assert!( (c.apply( 80, 0, 100 ) - 0.8125).abs() < 1e-9 );
+
+		let c = calibration( false, "curve=exponential, exponent=2" ).unwrap();
+		assert_eq!( c.apply( 50, 0, 100 ), 0.25 );
+		let c = calibration( false, "curve=points, points=1:1 0:0 0.5:0.1" ).unwrap();
+		assert!( (c.apply( 75, 0, 100 ) - 0.55).abs() < 1e-9 );
+		assert_eq!( calibration( false, "min=9, max=9" ).unwrap().apply( 9, 0, 100 ), 0.5 );
+	}
+
+	#[test]
+	fn bad_calibrations() {
+		assert!( calibration( true, "deadzone=0.6, outer_deadzone=0.4" ).is_err() );
+		assert!( calibration( true, "deadzone=-0.1" ).is_err() );
+		assert!( calibration( true, "curve=exponential, exponent=0" ).is_err() );
+		assert!( calibration( true, "curve=points, points=0:0" ).is_err() );
+		assert!( calibration( true, "curve=points, points=0:0 0:1" ).is_err() );
+		assert!( calibration( true, "curve=points, points=0-0 1-1" ).is_err() );
+		assert!( calibration( true, "curve=wobbly" ).is_err() );
+		assert!( calibration( true, "centre" ).is_err() );
+		assert!( calibration( true, "tilt=3" ).is_err() );
+		// fields of a curve not chosen
+		assert!( calibration( true, "points=0:0 1:1" ).is_err() );
+		assert!( calibration( true, "curve=exponential, points=0:0 1:1" ).is_err() );
+		assert!( calibration( true, "exponent=1.5" ).is_err() );
+		assert!( calibration( true, "curve=points, points=0:0 1:1, exponent=1.5" ).is_err() );
+
+		// by the axis' name, any other is an error
+		let mut values: HashMap<String, Option<String>> = HashMap::new();
+		values.insert( "rz_calibration".to_string(), Some( "min=10".to_string() ) );
+		values.insert( "slider_2_calibration".to_string(), Some( "min=10".to_string() ) );
+		let (js, mssgs) = Joystick::from_config( "stick", values );
+		let errors = errors( mssgs );
+		assert!( errors.len() == 1 && errors[ 0 ].ends_with( "Error reading 'slider_2_calibration' unknown axis 'slider_2'" ), "{:?}", errors );
+		assert!( js.rz.calibration.as_ref().is_some_and( |c| c.min == Some( 10 ) ) );
+		assert!( js.slider_1.calibration.is_none() );
+	}
+
+	#[test]
+	fn two_routes_to_one_axis() {
+		let mut values: HashMap<String, Option<String>> = HashMap::new();
+		values.insert( "echo_z".to_string(), Some( "3344 0002".to_string() ) );
+		values.insert( "z_route".to_string(), Some( "pedals:x".to_string() ) );
+		values.insert( "x_route".to_string(), Some( "pedals:y".to_string() ) );
+		let (js, mssgs) = Joystick::from_config( "stick", values );
+		let errors = errors( mssgs );
+		assert!( errors.len() == 1 && errors[ 0 ].ends_with( "'echo_z' and 'z_route' both route z, neither is used" ), "{:?}", errors );
+		assert_eq!( js.routes().iter().map( |r| r.target ).collect::<Vec<&str>>(), vec![ "x" ] );
+	}
+
+	#[test]
+	fn not_found() {
+		let (devices, _, _) = manager( MockDevice::new( 0x3344, 0x0002, "Other" ), "[Stick]\nvid = 3344\npid = 0001\n" );
+		assert_eq!( devices.reports().values().next().unwrap().connection, Connection::NotFound );
+	}
+
+	#[test]
+	fn axes_and_buttons_from_the_descriptor() {
+		let device = MockDevice::new( 0x3344, 0x0001, "Pad" ).with_descriptor( GAMEPAD );
+		let (mut devices, script, key) = manager( device, "[Pad]\nvid = 3344\npid = 0001\n" );
+		script.push_report( &[ 0x00, 0xFF, 0b1000_0001 ] );
+		script.settle();
+		devices.poll();
+		let dr = devices.device( &key ).unwrap();
+		assert_eq!( (dr.x, dr.y), (0, JS_MAX) );
+		assert!( !dr.error );
+		let pressed: Vec<usize> = dr.buttons.iter().filter( |b| b.pressed ).map( |b| b.number ).collect();
+		assert_eq!( pressed, vec![ 1, 8 ] );
+
+		let samples = devices.raw_axes( &key );
+		assert_eq!( samples[ 1 ], AxisSample { axis: "y", value: 255, min: 0, max: 255, centred: true } );
+	}
+
+	#[test]
+	fn mapped_axes_without_report_ids() {
+		let device = MockDevice::new( 0x3344, 0x0001, "Pad" ).with_descriptor( GAMEPAD );
+		let (mut devices, script, key) = manager( device, "[Pad]\nvid = 3344\npid = 0001\ny = 1, 2\n" );
+		// the first byte is X, not a report id
+		script.push_report( &[ 0x00, 0x00, 0x10 ] );
+		script.settle();
+		devices.poll();
+		let dr = devices.device( &key ).unwrap();
+		assert_eq!( (dr.x, dr.y, dr.error), (0, 0x10, false) );
+	}
+
+	#[test]
+	fn mapped_axes_in_the_first_report() {
+		let device = MockDevice::new( 0x3344, 0x0001, "Stick" ).with_descriptor( TWO_REPORTS );
+		let (mut devices, script, key) = manager( device, "[Stick]\nvid = 3344\npid = 0001\nx = 1, 2\n" );
+		script.push_report( &[ 1, 0xEA, 0x60 ] );
+		script.push_report( &[ 2, 0, 0 ] );
+		script.settle();
+		devices.poll();
+		assert_eq!( devices.device( &key ).unwrap().x, JS_MAX );
+	}
+
+	#[test]
+	fn long_reports_keep_what_fits() {
+		// X, 70 bytes of padding, then Y
+		const LONG: &[u8] = &[
+			0x05, 0x01, 0x09, 0x05, 0xA1, 0x01,
+			0x15, 0x00, 0x26, 0xFF, 0x00, 0x75, 0x08, 0x95, 0x01, 0x09, 0x30, 0x81, 0x02,
+			0x95, 0x46, 0x81, 0x01,
+			0x95, 0x01, 0x09, 0x31, 0x81, 0x02,
+			0xC0,
+		];
+		let mut backend = MockBackend::new();
+		let script = backend.add_device( MockDevice::new( 0x3344, 0x0001, "Pad" ).with_descriptor( LONG ) );
+		let mut devices = DeviceManager::new();
+		devices.set_backend( Box::new( backend ) );
+		devices.set_hotplug( Box::new( NoSource ) );
+		let errors = errors( devices.load( ini_file::parser().read( "[Pad]\nvid = 3344\npid = 0001\n".to_string() ).unwrap() ) );
+		assert_eq!( errors.len(), 1, "{:?}", errors );
+		assert!( errors[ 0 ].ends_with( "reports are 72 bytes, buffer is 64 (DEV_BUF_LEN), inputs past it are not read (page:usage): 01:31" ), "{}", errors[ 0 ] );
+
+		script.push_report( &[ 0xFF ] );
+		script.settle();
+		devices.poll();
+		let key = devices.devices()[ 0 ].key.clone();
+		assert_eq!( devices.device( &key ).unwrap().x, JS_MAX );
+	}
+
+	#[test]
+	fn full_reports_fit() {
+		let (mut devices, script, _) = manager( MockDevice::new( 0x3344, 0x0001, "Test Stick" ), STICK );
+		let mut report = [ 0u8; DEV_BUF_LEN ];
+		report[ 0 ] = 1;
+		script.push_report( &report );
+		script.settle();
+		assert!( errors( devices.poll() ).is_empty() );
+		script.push_report( &[ 1u8; DEV_BUF_LEN + 8 ] );
+		script.settle();
+		let errors = errors( devices.poll() );
+		assert_eq!( errors.len(), 1 );
+		assert!( errors[ 0 ].ends_with( "Device buffer too small (DEV_BUF_LEN = 64)" ) );
+	}
+
+	#[test]
+	fn buttons_and_hats_in_other_reports_are_kept() {
+		let device = MockDevice::new( 0x3344, 0x0001, "Stick" ).with_descriptor( TWO_REPORTS );
+		let (mut devices, script, key) = manager( device, "[Stick]\nvid = 3344\npid = 0001\n" );
+		let pressed = |devices: &DeviceManager| -> Vec<usize> {
+			devices.device( &key ).unwrap().buttons.iter().filter( |b| b.pressed ).map( |b| b.number ).collect()
+		};
+		script.push_report( &[ 1, 0b0000_0001 ] );
+		script.settle();
+		devices.poll();
+		script.push_report( &[ 2, 0b0011_0010 ] );
+		script.settle();
+		devices.poll();
+		assert_eq!( pressed( &devices ), vec![ 1, 10 ] );
+		script.push_report( &[ 1, 0 ] );
+		script.settle();
+		devices.poll();
+		assert_eq!( pressed( &devices ), vec![ 10 ] );
+		assert_eq!( devices.device( &key ).unwrap().hats[ 0 ].position, Some( 3 ) );
+	}
+
+	#[test]
+	fn virtual_devices() {
+		let mut backend = MockBackend::new();
+		let stick = backend.add_device( MockDevice::new( 0x3344, 0x0001, "Stick" ) );
+		let pedals = backend.add_device( MockDevice::new( 0x3344, 0x0002, "Pedals" ) );
+		let mut devices = DeviceManager::new();
+		devices.set_backend( Box::new( backend ) );
+		devices.set_hotplug( Box::new( NoSource ) );
+		let ini = "[Stick]\nvid = 3344\npid = 0001\nx = 1, 2\nbuttons = 3\nauto_map = false\n\
+					[Pedals]\nvid = 3344\npid = 0002\nx = 1, 2\nbuttons = 3\nauto_map = false\nbuttons_from = stick\n\
+					[HOTAS]\nx_route = stick:x\nz_route = pedals:x\nbuttons_from = stick; throttle; pedals\nbutton_10 = name=Brake\n";
+		let errors = errors( devices.load( ini_file::parser().read( ini.to_string() ).unwrap() ) );
+		assert_eq!( errors.len(), 2, "{:?}", errors );
+		assert!( errors[ 0 ].ends_with( "[hotas] buttons_from: no device 'throttle'" ), "{}", errors[ 0 ] );
+		assert!( errors[ 1 ].ends_with( "[pedals] buttons_from is for virtual devices (no vid and pid)" ), "{}", errors[ 1 ] );
+
+		stick.push_report( &[ 1, 0xEA, 0x60, 0b0000_0001 ] );
+		pedals.push_report( &[ 1, 0, 0, 0b0000_0010 ] );
+		stick.settle();
+		pedals.settle();
+		devices.poll();
+		let hotas = devices.joysticks().iter().find( |js| js.is_virtual() ).unwrap().key.clone();
+		let dr = devices.device( &hotas ).unwrap();
+		assert_eq!( dr.connection, Connection::Virtual );
+		assert_eq!( (dr.x, dr.y, dr.z), (JS_MAX, js_mid(), 0) );
+		let pressed: Vec<usize> = dr.buttons.iter().filter( |b| b.pressed ).map( |b| b.number ).collect();
+		assert_eq!( (dr.buttons.len(), pressed), (16, vec![ 1, 10 ]) );
+		assert_eq!( (dr.buttons[ 9 ].name.as_deref(), dr.buttons[ 9 ].group.as_str()), (Some( "Brake" ), "pedals 1") );
+		assert_eq!( buttons::groups( &dr.buttons ), vec![ "stick 1", "pedals 1" ] );
+		// not a device that is read
+		assert!( devices.devices().iter().all( |d| d.key != hotas ) );
+	}
+}
+
 /* ******************************************************************************* *
  *		*** End ***
  * ******************************************************************************* */
@@ -0,0 +1,313 @@
+/* ******************************************************************************* */
+/* Display: how a device is shown, and its axes beyond the stick's own image */
+/* ******************************************************************************* */
+/*
+ * Any axis can be given a widget, in its device's section, e.g.
+ *	slider_0_widget = vbar, label=throttle		a bar filling upwards
+ *	rz_widget = dial							a pointer turning from 7 o'clock to 5 o'clock
+ *	rx_widget = ministick, with=ry				a pad with a dot: rx across, ry down
+ * Widgets are shown under the device's image, in the order of AXES.
+ * A device can be shown as a throttle, in place of a stick:
+ *	display = throttle
+ *	throttle_levers = slider_0, slider_1		one or two levers, side by side
+ *	throttle_detents = idle cut-off=0.05, afterburner=0.8
+ * or as rudder pedals:
+ *	display = pedals
+ *	pedals_yaw = x								a centred bar, filling to the side pushed
+ *	pedals_brakes = rx, ry						left and right toe brakes, filling upwards
+ * Only the values are here; drawing them is left to the program (see main.rs).
+ */
+use crate::device::{find_axis, DeviceReport, JS_MAX, };
+
+/* ******************************************************************************* */
+/* Constants */
+
+// two levers closer than this (of their travel) are shown as locked together
+const LOCK_TOLERANCE: f32 = 0.01;
+
+/* ******************************************************************************* */
+/* Structures */
+/* ******************************************************************************* */
+
+/// The ways an axis can be shown
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum WidgetKind {
+	VBar,						// a bar, filling upwards, for throttles and sliders
+	HBar,						// a bar, filling to the right
+	Dial,						// a pointer, turning clockwise as the value goes up
+	Ministick( &'static str ),	// a pad: this axis across, the one given down
+}
+
+/* ******************************************************************************* */
+/// One axis' widget, as given by an <axis>_widget key
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct AxisWidget {
+	pub axis: &'static str,
+	pub kind: WidgetKind,
+	pub label: String,		// the label= field, else the axis' own label, else its name
+}
+
+impl AxisWidget {
+	/// a widget from an .ini file: axis from the key's name, e.g. "rz" for rz_widget
+	/// returns None for "none", to show the axis no more
+	pub fn parse( axis: &str, text: &str ) -> Result<Option<AxisWidget>, String> {
+		let axis = find_axis( axis )?;
+		let mut fields = text.split( ',' ).map( |f| f.trim() );
+		let kind = fields.next().unwrap_or( "" ).to_lowercase();
+		let mut with: Option<&'static str> = None;
+		let mut label = String::new();
+		for field in fields.filter( |f| !f.is_empty() ) {
+			let (name, value) = field.split_once( '=' ).unwrap_or( (field, "") );
+			let value = value.trim();
+			match name.trim().to_lowercase().as_str() {
+				"with" => {	with = Some( find_axis( value )? );	}
+				"label" => {	label = value.to_string();	}
+				other => {	return Err( format!("unknown field '{}'", other) );	}
+			}
+		}
+		let kind = match kind.as_str() {
+			"none" => {	return Ok( None );	}
+			"vbar" => WidgetKind::VBar,
+			"hbar" => WidgetKind::HBar,
+			"dial" => WidgetKind::Dial,
+			"ministick" => match with {
+				Some( other ) => WidgetKind::Ministick( other ),
+				None => {	return Err( "ministick needs with=, the axis shown down the pad".to_string() );	}
+			},
+			other => {	return Err( format!("unknown widget '{}', use vbar, hbar, dial, ministick or none", other) );	}
+		};
+		Ok( Some( AxisWidget { axis, kind, label } ) )
+	}
+}
+
+/* ******************************************************************************* */
+/// What a device is shown as
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Display {
+	Stick,					// its image, moved by x and y, turned by z
+	Throttle( Throttle ),
+	Pedals( Pedals ),
+}
+
+impl Display {
+	/// the display key: stick (the default), throttle or pedals
+	pub fn check_name( name: &str ) -> Result<String, String> {
+		let name = name.trim().to_lowercase();
+		match name.as_str() {
+			"stick" | "throttle" | "pedals" => Ok( name ),
+			other => Err( format!("unknown display '{}', use stick, throttle or pedals", other) ),
+		}
+	}
+}
+
+/* ******************************************************************************* */
+/// A device shown as a throttle: one or two levers, with the detents marked
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Throttle {
+	pub levers: Vec<&'static str>,		// the axes moving each lever, left to right
+	pub detents: Vec<Detent>,
+	pub show_lock: bool,				// say when two levers are together
+}
+
+/// A marked position on a throttle's travel, e.g. idle cut-off or an afterburner gate
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Detent {
+	pub name: String,
+	pub at: u16,						// 0 to JS_MAX, as an axis' value
+}
+
+impl Default for Throttle {
+	fn default() -> Self {
+		Throttle {
+			levers: vec![ "slider_0" ],
+			detents: Vec::new(),
+			show_lock: true,
+		}
+	}
+}
+
+impl Throttle {
+	/// throttle_levers: one or two axes, separated by commas
+	pub fn set_levers( &mut self, text: &str ) -> Result<(), String> {
+		let mut levers: Vec<&'static str> = Vec::new();
+		for name in text.split( ',' ).filter( |n| !n.trim().is_empty() ) {
+			levers.push( find_axis( name )? );
+		}
+		if levers.is_empty() || levers.len() > 2 {
+			return Err( "a throttle has one or two levers".to_string() );
+		}
+		self.levers = levers;
+		Ok( () )
+	}
+
+	/// throttle_detents: name=position pairs, separated by commas, positions from 0.0 to 1.0
+	pub fn set_detents( &mut self, text: &str ) -> Result<(), String> {
+		let mut detents: Vec<Detent> = Vec::new();
+		for field in text.split( ',' ).map( |f| f.trim() ).filter( |f| !f.is_empty() ) {
+			let Some( (name, at) ) = field.rsplit_once( '=' ) else {
+				return Err( format!("'{}' should be name=position", field) );
+			};
+			let at = at.trim().parse::<f32>().map_err( |e| format!("{} {}", name.trim(), e) )?;
+			if !(0.0..=1.0).contains( &at ) {
+				return Err( format!("{} should be from 0 to 1", name.trim()) );
+			}
+			detents.push( Detent { name: name.trim().to_string(), at: (at * JS_MAX as f32).round() as u16 } );
+		}
+		detents.sort_by_key( |d| d.at );
+		self.detents = detents;
+		Ok( () )
+	}
+
+	/// each lever's position, 0.0 (closed) to 1.0 (full), left to right
+	pub fn positions( &self, report: &DeviceReport ) -> Vec<f32> {
+		self.levers.iter().map( |l| report.axis_f32( l ).unwrap_or( 0.0 ).clamp( 0.0, 1.0 ) ).collect()
+	}
+
+	/// two levers, at the same place
+	pub fn locked( &self, report: &DeviceReport ) -> bool {
+		let positions = self.positions( report );
+		self.show_lock && positions.len() == 2 && (positions[ 0 ] - positions[ 1 ]).abs() <= LOCK_TOLERANCE
+	}
+}
+
+impl Detent {
+	/// where the detent is, 0.0 to 1.0
+	pub fn at_f32( &self ) -> f32 {
+		self.at as f32 / JS_MAX as f32
+	}
+}
+
+/* ******************************************************************************* */
+/// A device shown as rudder pedals: a yaw bar, and two toe brakes
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Pedals {
+	pub yaw: &'static str,
+	pub brakes: Option<(&'static str, &'static str)>,	// left and right; None for pedals without
+}
+
+impl Default for Pedals {
+	fn default() -> Self {
+		Pedals {
+			yaw: "x",
+			brakes: Some( ("rx", "ry") ),
+		}
+	}
+}
+
+impl Pedals {
+	/// pedals_yaw: the axis the pedals turn
+	pub fn set_yaw( &mut self, text: &str ) -> Result<(), String> {
+		self.yaw = find_axis( text )?;
+		Ok( () )
+	}
+
+	/// pedals_brakes: the left and right toe brakes' axes, separated by a comma, or none
+	pub fn set_brakes( &mut self, text: &str ) -> Result<(), String> {
+		if text.trim().eq_ignore_ascii_case( "none" ) {
+			self.brakes = None;
+			return Ok( () );
+		}
+		let Some( (left, right) ) = text.split_once( ',' ) else {
+			return Err( "should be the left and right brakes' axes, or none".to_string() );
+		};
+		self.brakes = Some( (find_axis( left )?, find_axis( right )?) );
+		Ok( () )
+	}
+
+	/// yaw, -1.0 (left) to 1.0 (right)
+	pub fn yaw( &self, report: &DeviceReport ) -> f32 {
+		(report.axis_f32( self.yaw ).unwrap_or( 0.5 ).clamp( 0.0, 1.0 ) - 0.5) * 2.0
+	}
+
+	/// the left and right brakes, 0.0 (off) to 1.0 (full), if the pedals have them
+	pub fn brakes( &self, report: &DeviceReport ) -> Option<(f32, f32)> {
+		let (left, right) = self.brakes?;
+		let value = |axis: &str| report.axis_f32( axis ).unwrap_or( 0.0 ).clamp( 0.0, 1.0 );
+		Some( (value( left ), value( right )) )
+	}
+}
+
+/* ******************************************************************************* */
+/* Tests */
+/* ******************************************************************************* */
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::device::tests::virtual_report;
+
+	#[test]
+	fn widgets() {
+		assert_eq!( AxisWidget::parse( "slider_0", "VBar, label=throttle" ),
+					Ok( Some( AxisWidget { axis: "slider_0", kind: WidgetKind::VBar, label: "throttle".to_string() } ) ) );
+		assert_eq!( AxisWidget::parse( "rx", "ministick, with=RY" ).unwrap().unwrap().kind, WidgetKind::Ministick( "ry" ) );
+		assert_eq!( AxisWidget::parse( "rz", "none" ), Ok( None ) );
+		for (axis, text) in [ ("w", "dial"), ("rz", "knob"), ("rx", "ministick"), ("rx", "ministick, with=w"), ("rz", "dial, colour=red") ] {
+			assert!( AxisWidget::parse( axis, text ).is_err(), "{} {}", axis, text );
+		}
+
+		// labelled from the axis when not given
+		let report = virtual_report( "[Stick]\nrz = 0, 0, Twist\nrz_widget = dial\nslider_0_widget = hbar\nslider_1_widget = vbar, label=Flaps\n" );
+		let labels: Vec<(&str, &str)> = report.widgets.iter().map( |w| (w.axis, w.label.as_str()) ).collect();
+		assert_eq!( labels, vec![ ("rz", "Twist"), ("slider_0", "slider_0"), ("slider_1", "Flaps") ] );
+		assert_eq!( report.display, Display::Stick );
+	}
+
+	#[test]
+	fn throttle() {
+		let mut throttle = Throttle::default();
+		assert!( throttle.set_levers( "z, rz, slider_0" ).is_err() );
+		assert!( throttle.set_levers( "" ).is_err() );
+		assert!( throttle.set_levers( "z, w" ).is_err() );
+		assert_eq!( throttle.levers, vec![ "slider_0" ] );
+		throttle.set_levers( " Z , rz" ).unwrap();
+		assert_eq!( throttle.levers, vec![ "z", "rz" ] );
+
+		for text in [ "afterburner", "afterburner=1.5", "afterburner=x" ] {
+			assert!( throttle.set_detents( text ).is_err(), "{}", text );
+		}
+		throttle.set_detents( "afterburner=0.8, idle cut-off=0.05" ).unwrap();
+		let names: Vec<&str> = throttle.detents.iter().map( |d| d.name.as_str() ).collect();
+		assert_eq!( names, vec![ "idle cut-off", "afterburner" ] );
+		assert!( (throttle.detents[ 1 ].at_f32() - 0.8).abs() < 0.0001 );
+
+		let mut report = virtual_report( "[Throttle]\ndisplay = throttle\nthrottle_levers = z, rz\nthrottle_detents = idle=0.1\n" );
+		assert!( matches!( &report.display, Display::Throttle( t ) if t.levers == vec![ "z", "rz" ] && t.detents.len() == 1 ) );
+		(report.z, report.rz) = (JS_MAX, JS_MAX - 100);
+		assert_eq!( throttle.positions( &report )[ 0 ], 1.0 );
+		assert!( throttle.locked( &report ) );
+		report.rz = JS_MAX / 2;
+		assert!( !throttle.locked( &report ) );
+		throttle.set_levers( "z" ).unwrap();
+		report.rz = JS_MAX;
+		assert!( !throttle.locked( &report ) );
+	}
+
+	#[test]
+	fn pedals() {
+		let mut pedals = Pedals::default();
+		assert!( pedals.set_yaw( "w" ).is_err() );
+		assert!( pedals.set_brakes( "rx" ).is_err() );
+		assert!( pedals.set_brakes( "rx, w" ).is_err() );
+		pedals.set_yaw( "RZ" ).unwrap();
+
+		let mut report = virtual_report( "[Pedals]\ndisplay = pedals\npedals_yaw = rz\npedals_brakes = none\n" );
+		assert_eq!( report.display, Display::Pedals( Pedals { yaw: "rz", brakes: None } ) );
+		(report.rz, report.rx, report.ry) = (0, JS_MAX, 0);
+		assert_eq!( pedals.yaw( &report ), -1.0 );
+		report.rz = JS_MAX;
+		assert_eq!( pedals.yaw( &report ), 1.0 );
+		assert_eq!( pedals.brakes( &report ), Some( (1.0, 0.0) ) );
+		pedals.set_brakes( "None" ).unwrap();
+		assert_eq!( pedals.brakes( &report ), None );
+	}
+
+	#[test]
+	fn display_names() {
+		assert_eq!( Display::check_name( " Throttle " ), Ok( "throttle".to_string() ) );
+		assert!( Display::check_name( "yoke" ).is_err() );
+	}
+}
+
+/* ******************************************************************************* *
+ *		*** End ***
+ * ******************************************************************************* */
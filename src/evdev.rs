@@ -0,0 +1,621 @@
+/* ******************************************************************************* */
+/* Linux evdev backend */
+/* ******************************************************************************* */
+/*
+ * Reads devices already claimed by a kernel driver (hid-generic, xpad, ...) through
+ * their /dev/input/event* nodes.
+ * Evdev sends events rather than reports, so each device keeps the current state of its
+ * axes and buttons, and hands that state to the device layer as a report.  A matching
+ * report descriptor is made up from the device's capabilities, so the automatic mapping
+ * (see descriptor.rs) places ABS_X on x, ABS_THROTTLE on slider_0, and so on, scaled by
+ * the kernel's absinfo minimum and maximum.
+ * A report is no longer than the device layer's buffer (DEV_BUF_LEN), buttons that do not
+ * fit after the axes and the hat are left out.
+ */
+use crate::backend::{DeviceEntry, InputBackend, InputDevice, };
+use crate::descriptor;
+use crate::device::DEV_BUF_LEN;
+use std::fs::File;
+use std::io::Read;
+use std::os::unix::io::{AsRawFd, RawFd, };
+use std::sync::Mutex;
+
+#[cfg(feature = "logging")]
+use log::info;
+
+/* ******************************************************************************* */
+/* Constants */
+
+const INPUT_DIR: &str = "/dev/input";
+
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_ABS: u16 = 0x03;
+const SYN_REPORT: u16 = 0x00;
+const SYN_DROPPED: u16 = 0x03;
+
+pub const ABS_X: u16 = 0x00;
+pub const ABS_Y: u16 = 0x01;
+pub const ABS_Z: u16 = 0x02;
+pub const ABS_RX: u16 = 0x03;
+pub const ABS_RY: u16 = 0x04;
+pub const ABS_RZ: u16 = 0x05;
+pub const ABS_THROTTLE: u16 = 0x06;
+pub const ABS_RUDDER: u16 = 0x07;
+pub const ABS_WHEEL: u16 = 0x08;
+pub const ABS_GAS: u16 = 0x09;
+pub const ABS_BRAKE: u16 = 0x0a;
+pub const ABS_HAT0X: u16 = 0x10;
+pub const ABS_HAT0Y: u16 = 0x11;
+const ABS_CNT: usize = 0x40;
+
+const BTN_MISC: u16 = 0x100;	// first of the buttons, keyboard keys come before
+const KEY_CNT: usize = 0x300;
+
+// slider_0 and slider_1, there is nowhere to show any more
+const MAX_SLIDERS: usize = 2;
+
+/// evdev axes in report order, with the HID usage each one is given
+/// sliders are taken in order: the first two present become slider_0 and slider_1, the rest are left out
+const AXIS_USAGES: [(u16, u16); 11] = [
+	(ABS_X, descriptor::USAGE_X),
+	(ABS_Y, descriptor::USAGE_Y),
+	(ABS_Z, descriptor::USAGE_Z),
+	(ABS_RX, descriptor::USAGE_RX),
+	(ABS_RY, descriptor::USAGE_RY),
+	(ABS_RZ, descriptor::USAGE_RZ),
+	(ABS_THROTTLE, descriptor::USAGE_SLIDER),
+	(ABS_RUDDER, descriptor::USAGE_SLIDER),
+	(ABS_WHEEL, descriptor::USAGE_SLIDER),
+	(ABS_GAS, descriptor::USAGE_SLIDER),
+	(ABS_BRAKE, descriptor::USAGE_SLIDER),
+];
+
+// ioctl requests, see linux/input.h
+const fn ioc_read( nr: u64, size: u64 ) -> u64 {
+	(2 << 30) | (size << 16) | ((b'E' as u64) << 8) | nr
+}
+const EVIOCGID: u64 = ioc_read( 0x02, 8 );
+const fn eviocgname( len: u64 ) -> u64 { ioc_read( 0x06, len ) }
+const fn eviocgphys( len: u64 ) -> u64 { ioc_read( 0x07, len ) }
+const fn eviocguniq( len: u64 ) -> u64 { ioc_read( 0x08, len ) }
+const fn eviocgkey( len: u64 ) -> u64 { ioc_read( 0x18, len ) }
+const fn eviocgbit( ev: u16, len: u64 ) -> u64 { ioc_read( 0x20 + ev as u64, len ) }
+const fn eviocgabs( abs: u16 ) -> u64 { ioc_read( 0x40 + abs as u64, 24 ) }
+
+/* ******************************************************************************* */
+/* Structures */
+/* ******************************************************************************* */
+
+/// One absolute axis, with the range the kernel reports for it
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct EvdevAxis {
+	pub code: u16,		// ABS_...
+	pub min: i32,
+	pub max: i32,
+	pub value: i32,		// starting value
+}
+
+/// What a device can send; from the device itself, or made up for a recording
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EvdevCaps {
+	pub name: String,
+	pub phys: String,
+	pub uniq: Option<String>,	// usually the serial number, if the device has one
+	pub vid: u16,
+	pub pid: u16,
+	pub axes: Vec<EvdevAxis>,	// any order, only those in AXIS_USAGES are used
+	pub keys: Vec<u16>,			// BTN_... codes
+	pub hat: bool,				// has ABS_HAT0X and ABS_HAT0Y
+}
+
+impl EvdevCaps {
+	/// ask the kernel what an event node can do
+	pub fn query( fd: RawFd ) -> Result<EvdevCaps, String> {
+		let mut id = [0u16; 4];		// bustype, vendor, product, version
+		ioctl( fd, EVIOCGID, id.as_mut_ptr() as *mut u8 )?;
+
+		let mut caps = EvdevCaps {
+			name: ioctl_string( fd, eviocgname( 256 ) ),
+			phys: ioctl_string( fd, eviocgphys( 256 ) ),
+			uniq: Some( ioctl_string( fd, eviocguniq( 256 ) ) ).filter( |s| !s.is_empty() ),
+			vid: id[ 1 ],
+			pid: id[ 2 ],
+			..EvdevCaps::default()
+		};
+
+		let mut abs_bits = [0u8; ABS_CNT / 8];
+		ioctl( fd, eviocgbit( EV_ABS, abs_bits.len() as u64 ), abs_bits.as_mut_ptr() )?;
+		for (code, _) in AXIS_USAGES {
+			if !bit_set( &abs_bits, code as usize ) { continue; }
+			let info = abs_info( fd, code )?;
+			caps.axes.push( EvdevAxis { code, min: info[ 1 ], max: info[ 2 ], value: info[ 0 ] } );
+		}
+		caps.hat = bit_set( &abs_bits, ABS_HAT0X as usize ) && bit_set( &abs_bits, ABS_HAT0Y as usize );
+
+		let mut key_bits = [0u8; KEY_CNT / 8];
+		ioctl( fd, eviocgbit( EV_KEY, key_bits.len() as u64 ), key_bits.as_mut_ptr() )?;
+		caps.keys = (BTN_MISC as usize..KEY_CNT)
+			.filter( |code| bit_set( &key_bits, *code ) )
+			.map( |code| code as u16 )
+			.collect();
+		Ok( caps )
+	}
+
+	/// axes in report order
+	fn report_axes( &self ) -> Vec<(EvdevAxis, u16)> {
+		let mut ret: Vec<(EvdevAxis, u16)> = Vec::new();
+		for (code, usage) in AXIS_USAGES {
+			let Some( axis ) = self.axes.iter().find( |a| a.code == code ) else { continue; };
+			if usage == descriptor::USAGE_SLIDER && ret.iter().filter( |(_, u)| *u == usage ).count() == MAX_SLIDERS {
+				continue;
+			}
+			ret.push( (*axis, usage) );
+		}
+		ret
+	}
+
+	/// buttons in report order: as many as fit in a report after the axes, leaving room for the hat
+	fn report_keys( &self ) -> &[u16] {
+		let bits = DEV_BUF_LEN.saturating_sub( self.report_axes().len() * 4 ) * 8;
+		let room = bits.saturating_sub( if self.hat { 4 } else { 0 } );
+		&self.keys[ ..self.keys.len().min( room ) ]
+	}
+
+	/// sliders beyond the first two, and buttons past the end of a report, which are not in the reports
+	pub fn left_out( &self ) -> Vec<u16> {
+		let used = self.report_axes();
+		let mut ret: Vec<u16> = AXIS_USAGES.iter()
+			.filter( |(code, _)| self.axes.iter().any( |a| a.code == *code ) && !used.iter().any( |(a, _)| a.code == *code ) )
+			.map( |(code, _)| *code )
+			.collect();
+		ret.extend_from_slice( &self.keys[ self.report_keys().len().. ] );
+		ret
+	}
+
+	fn button_count( &self ) -> usize {
+		self.report_keys().len() + if self.hat { 4 } else { 0 }
+	}
+
+	/// a HID report descriptor describing the reports made by EvdevDevice:
+	/// each axis as 32 bits, then one bit per button, then the hat as four buttons
+	/// (up, right, down, left), no report ids
+	pub fn report_descriptor( &self ) -> Vec<u8> {
+		let mut desc: Vec<u8> = vec![
+			0x05, descriptor::USAGE_PAGE_GENERIC_DESKTOP as u8,
+			0x09, 0x04,				// usage: joystick
+			0xA1, 0x01,				// collection: application
+		];
+		for (axis, usage) in self.report_axes() {
+			desc.extend_from_slice( &[0x09, usage as u8] );
+			desc.push( 0x17 );		// logical minimum, 4 bytes
+			desc.extend_from_slice( &axis.min.to_le_bytes() );
+			desc.push( 0x27 );		// logical maximum, 4 bytes
+			desc.extend_from_slice( &axis.max.to_le_bytes() );
+			desc.extend_from_slice( &[0x75, 32, 0x95, 1, 0x81, 0x02] );
+		}
+		let buttons = self.button_count();
+		if buttons > 0 {
+			desc.extend_from_slice( &[0x05, descriptor::USAGE_PAGE_BUTTON as u8] );
+			desc.extend_from_slice( &[0x19, 0x01] );	// usage minimum: button 1
+			desc.push( 0x2A );							// usage maximum, 2 bytes
+			desc.extend_from_slice( &(buttons as u16).to_le_bytes() );
+			desc.extend_from_slice( &[0x15, 0x00, 0x25, 0x01, 0x75, 0x01] );
+			desc.push( 0x96 );							// report count, 2 bytes
+			desc.extend_from_slice( &(buttons as u16).to_le_bytes() );
+			desc.extend_from_slice( &[0x81, 0x02] );
+		}
+		desc.push( 0xC0 );		// end collection
+		desc
+	}
+
+	/// bytes in one report
+	pub fn report_len( &self ) -> usize {
+		self.report_axes().len() * 4 + self.button_count().div_ceil( 8 )
+	}
+}
+
+/* ******************************************************************************* */
+/// Current state of a device, as built up from its events
+struct EvdevState {
+	source: Box<dyn Read + Send>,
+	fd: RawFd,
+	pending: Vec<u8>,		// part of an event, read but not yet used
+	dropped: bool,			// events were lost, skip to the end of the set and ask the kernel
+	axes: Vec<i32>,			// in report order
+	keys: Vec<bool>,
+	hat: (i32, i32),
+}
+
+/// An event node, or any other file or pipe that supplies input events
+pub struct EvdevDevice {
+	caps: EvdevCaps,
+	state: Mutex<EvdevState>,
+}
+
+impl EvdevDevice {
+	/// read events from source, which may be an event node, or a recording of one
+	/// in a plain file or pipe; caps describes the device that made the events
+	pub fn new<R: Read + AsRawFd + Send + 'static>( source: R, caps: EvdevCaps ) -> EvdevDevice {
+		let state = EvdevState {
+			fd: source.as_raw_fd(),
+			source: Box::new( source ),
+			pending: Vec::new(),
+			dropped: false,
+			axes: caps.report_axes().iter().map( |(a, _)| a.value ).collect(),
+			keys: vec![false; caps.report_keys().len()],
+			hat: (0, 0),
+		};
+		EvdevDevice { caps, state: Mutex::new( state ) }
+	}
+
+	/// the current state, laid out as described by EvdevCaps::report_descriptor
+	fn make_report( &self, state: &EvdevState, buff: &mut [u8] ) -> usize {
+		let len = self.caps.report_len().min( buff.len() );
+		let mut report = vec![0u8; self.caps.report_len()];
+		for (n, value) in state.axes.iter().enumerate() {
+			report[ n * 4..n * 4 + 4 ].copy_from_slice( &value.to_le_bytes() );
+		}
+		let (hat_x, hat_y) = state.hat;
+		let mut buttons: Vec<bool> = state.keys.clone();
+		if self.caps.hat {
+			buttons.extend_from_slice( &[hat_y < 0, hat_x > 0, hat_y > 0, hat_x < 0] );
+		}
+		let first = state.axes.len() * 4;
+		for (n, pressed) in buttons.iter().enumerate() {
+			if *pressed {
+				report[ first + n / 8 ] |= 1 << (n % 8);
+			}
+		}
+		buff[ ..len ].copy_from_slice( &report[ ..len ] );
+		len
+	}
+
+	/// after events were lost: the state as the kernel has it now
+	/// a recording or pipe cannot be asked, its state is kept as it was
+	fn resync( &self, state: &mut EvdevState ) {
+		let mut key_bits = [0u8; KEY_CNT / 8];
+		if ioctl( state.fd, eviocgkey( key_bits.len() as u64 ), key_bits.as_mut_ptr() ).is_ok() {
+			for (n, code) in self.caps.report_keys().iter().enumerate() {
+				state.keys[ n ] = bit_set( &key_bits, *code as usize );
+			}
+		}
+		for (n, (axis, _)) in self.caps.report_axes().iter().enumerate() {
+			if let Ok( info ) = abs_info( state.fd, axis.code ) {
+				state.axes[ n ] = info[ 0 ];
+			}
+		}
+		if self.caps.hat {
+			if let (Ok( x ), Ok( y )) = (abs_info( state.fd, ABS_HAT0X ), abs_info( state.fd, ABS_HAT0Y )) {
+				state.hat = (x[ 0 ], y[ 0 ]);
+			}
+		}
+	}
+
+	fn apply_event( &self, state: &mut EvdevState, ev_type: u16, code: u16, value: i32 ) {
+		match ev_type {
+			EV_ABS => {
+				match code {
+					ABS_HAT0X => {	state.hat.0 = value;	}
+					ABS_HAT0Y => {	state.hat.1 = value;	}
+					_ => {
+						if let Some( n ) = self.caps.report_axes().iter().position( |(a, _)| a.code == code ) {
+							state.axes[ n ] = value;
+						}
+					}
+				}
+			}
+			EV_KEY => {
+				if let Some( n ) = self.caps.report_keys().iter().position( |k| *k == code ) {
+					state.keys[ n ] = value != 0;
+				}
+			}
+			_ => {}
+		}
+	}
+}
+
+impl InputDevice for EvdevDevice {
+	fn product_string( &self ) -> Result<Option<String>, String> {
+		Ok( Some( self.caps.name.clone() ) )
+	}
+
+	/// reads events until the end of the next complete set (SYN_REPORT), then
+	/// returns the device's state as a report
+	fn read_timeout( &self, buff: &mut [u8], timeout: i32 ) -> Result<usize, String> {
+		let event_size = std::mem::size_of::<libc::input_event>();
+		let mut state = self.state.lock().map_err( |err| err.to_string() )?;
+		loop {
+			while state.pending.len() >= event_size {
+				let event: Vec<u8> = state.pending.drain( ..event_size ).collect();
+				// safety: event is exactly one input_event, read_unaligned copes with any alignment
+				let event: libc::input_event = unsafe {
+					std::ptr::read_unaligned( event.as_ptr() as *const libc::input_event )
+				};
+				match (event.type_, event.code) {
+					(EV_SYN, SYN_REPORT) => {
+						if state.dropped {
+							state.dropped = false;
+							self.resync( &mut state );
+						}
+						return Ok( self.make_report( &state, buff ) );
+					}
+					(EV_SYN, SYN_DROPPED) => {
+						// events were lost: those up to the next SYN_REPORT are only part of a set
+						state.dropped = true;
+					}
+					_ if state.dropped => {}
+					(ev_type, code) => {
+						self.apply_event( &mut state, ev_type, code, event.value );
+					}
+				}
+			}
+
+			let mut fds = libc::pollfd { fd: state.fd, events: libc::POLLIN, revents: 0 };
+			// safety: fds is a single, valid pollfd for the duration of the call
+			let ready = unsafe { libc::poll( &mut fds, 1, timeout ) };
+			if ready < 0 {
+				return Err( std::io::Error::last_os_error().to_string() );
+			}
+			if ready == 0 {
+				return Ok( 0 );
+			}
+			if fds.revents & (libc::POLLERR | libc::POLLNVAL) != 0 {
+				return Err( format!("{} disconnected", self.caps.name) );
+			}
+			let mut chunk = [0u8; 1024];
+			let len = state.source.read( &mut chunk ).map_err( |err| err.to_string() )?;
+			if len == 0 {
+				// end of a recording, or the writer has closed the pipe
+				return Ok( 0 );
+			}
+			state.pending.extend_from_slice( &chunk[ ..len ] );
+		}
+	}
+
+	fn report_descriptor( &self, buff: &mut [u8] ) -> Result<usize, String> {
+		let desc = self.caps.report_descriptor();
+		let len = desc.len().min( buff.len() );
+		buff[ ..len ].copy_from_slice( &desc[ ..len ] );
+		Ok( len )
+	}
+}
+
+/* ******************************************************************************* */
+/// All /dev/input/event* nodes that have joystick axes or buttons
+#[derive(Default)]
+pub struct EvdevBackend {
+	devices: Vec<DeviceEntry>,
+}
+
+impl EvdevBackend {
+	pub fn new() -> EvdevBackend {
+		let mut ret = EvdevBackend::default();
+		let _ = ret.refresh();
+		ret
+	}
+}
+
+impl InputBackend for EvdevBackend {
+	fn refresh( &mut self ) -> Result<(), String> {
+		self.devices = Vec::new();
+		let dir = std::fs::read_dir( INPUT_DIR ).map_err( |err| format!("{} {}", INPUT_DIR, err) )?;
+		for entry in dir.flatten() {
+			let node = entry.file_name().to_string_lossy().to_string();
+			if !node.starts_with( "event" ) { continue; }
+			let path = format!("{}/{}", INPUT_DIR, node);
+			// devices we may not read are skipped, not an error
+			let Ok( file ) = File::open( &path ) else { continue; };
+			let Ok( caps ) = EvdevCaps::query( file.as_raw_fd() ) else { continue; };
+			if caps.report_axes().is_empty() && caps.keys.is_empty() { continue; }
+			self.devices.push( DeviceEntry {
+				vid: caps.vid,
+				pid: caps.pid,
+				usage_page: descriptor::USAGE_PAGE_GENERIC_DESKTOP,
+				path,
+				name: Some( caps.name ),
+				phys: Some( caps.phys ),
+				serial: caps.uniq,
+				interface_number: None,
+			});
+		}
+		self.devices.sort_by( |a, b| a.path.cmp( &b.path ) );
+		Ok( () )
+	}
+
+	fn devices( &self ) -> Vec<DeviceEntry> {
+		self.devices.clone()
+	}
+
+	fn open( &self, entry: &DeviceEntry ) -> Result<Box<dyn InputDevice>, String> {
+		let file = File::open( &entry.path ).map_err( |err| format!("{} {}", entry.path, err) )?;
+		let caps = EvdevCaps::query( file.as_raw_fd() )?;
+		#[cfg(feature = "logging")]
+		for code in caps.left_out() {
+			info!("{} {}: 0x{:03x} left out, only two sliders are shown, and the buttons that fit in {} bytes",
+					entry.path, caps.name, code, DEV_BUF_LEN);
+		}
+		Ok( Box::new( EvdevDevice::new( file, caps ) ) )
+	}
+}
+
+/* ******************************************************************************* */
+
+fn bit_set( bits: &[u8], n: usize ) -> bool {
+	n / 8 < bits.len() && bits[ n / 8 ] & (1 << (n % 8)) != 0
+}
+
+/* ******************************************************************************* */
+
+fn ioctl( fd: RawFd, request: u64, data: *mut u8 ) -> Result<(), String> {
+	// safety: every request used here writes no more than the size encoded in it,
+	// and each caller passes a buffer of at least that size
+	let ret = unsafe { libc::ioctl( fd, request as _, data ) };
+	if ret < 0 {
+		return Err( std::io::Error::last_os_error().to_string() );
+	}
+	Ok( () )
+}
+
+/* ******************************************************************************* */
+/// an absolute axis' value, minimum, maximum, fuzz, flat and resolution
+fn abs_info( fd: RawFd, code: u16 ) -> Result<[i32; 6], String> {
+	let mut info = [0i32; 6];
+	ioctl( fd, eviocgabs( code ), info.as_mut_ptr() as *mut u8 )?;
+	Ok( info )
+}
+
+/* ******************************************************************************* */
+
+fn ioctl_string( fd: RawFd, request: u64 ) -> String {
+	let mut buff = [0u8; 256];
+	match ioctl( fd, request, buff.as_mut_ptr() ) {
+		Ok( () ) => {
+			let len = buff.iter().position( |b| *b == 0 ).unwrap_or( buff.len() );
+			String::from_utf8_lossy( &buff[ ..len ] ).to_string()
+		}
+		Err( _ ) => {	"".to_string()	}
+	}
+}
+
+/* ******************************************************************************* */
+/* Tests */
+/* ******************************************************************************* */
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::descriptor::ReportLayout;
+	use std::io::Write;
+	use std::os::unix::io::FromRawFd;
+	use std::os::unix::net::UnixStream;
+
+	const BTN_TRIGGER: u16 = 0x120;
+	const BTN_THUMB: u16 = 0x121;
+
+	fn caps() -> EvdevCaps {
+		let axis = |code: u16| EvdevAxis { code, min: 0, max: 255, value: 0 };
+		EvdevCaps {
+			name: "Test Stick".to_string(),
+			axes: vec![ axis( ABS_GAS ), axis( ABS_X ), axis( ABS_RUDDER ), axis( ABS_THROTTLE ) ],
+			keys: vec![ BTN_TRIGGER, BTN_THUMB ],
+			hat: true,
+			..EvdevCaps::default()
+		}
+	}
+
+	/// one input_event, as the kernel writes it
+	fn event( ev_type: u16, code: u16, value: i32 ) -> Vec<u8> {
+		let event = libc::input_event {
+			time: libc::timeval { tv_sec: 0, tv_usec: 0 },
+			type_: ev_type,
+			code,
+			value,
+		};
+		// safety: input_event is plain data, read here as the bytes it is made of
+		unsafe {
+			std::slice::from_raw_parts( &event as *const libc::input_event as *const u8,
+										std::mem::size_of::<libc::input_event>() ).to_vec()
+		}
+	}
+
+	#[test]
+	fn only_two_sliders() {
+		let caps = caps();
+		let codes: Vec<u16> = caps.report_axes().iter().map( |(a, _)| a.code ).collect();
+		assert_eq!( codes, vec![ ABS_X, ABS_THROTTLE, ABS_RUDDER ] );
+		assert_eq!( caps.left_out(), vec![ ABS_GAS ] );
+
+		let layout = ReportLayout::parse( &caps.report_descriptor() ).unwrap();
+		let rudder = layout.find( descriptor::USAGE_PAGE_GENERIC_DESKTOP, descriptor::USAGE_SLIDER, 1 ).unwrap();
+		assert_eq!( (rudder.bit_offset, rudder.logical_max), (64, 255) );
+		assert!( layout.find( descriptor::USAGE_PAGE_GENERIC_DESKTOP, descriptor::USAGE_SLIDER, 2 ).is_none() );
+		assert_eq!( (layout.buttons().len(), layout.report_len(), caps.report_len()), (6, 13, 13) );
+	}
+
+	#[test]
+	fn reports_fit_the_buffer() {
+		let mut caps = caps();
+		caps.keys = (BTN_MISC..KEY_CNT as u16).collect();
+		// three axes of four bytes, then buttons up to the hat's four
+		let room = (DEV_BUF_LEN - 12) * 8 - 4;
+		assert_eq!( caps.report_len(), DEV_BUF_LEN );
+		let left_out = caps.left_out();
+		assert_eq!( left_out.len(), 1 + caps.keys.len() - room );
+		assert_eq!( (left_out[ 0 ], left_out[ 1 ]), (ABS_GAS, BTN_MISC + room as u16) );
+		let layout = ReportLayout::parse( &caps.report_descriptor() ).unwrap();
+		assert_eq!( (layout.report_len(), layout.buttons().len()), (DEV_BUF_LEN, room + 4) );
+	}
+
+	#[test]
+	fn events_from_a_pipe() {
+		let mut fds = [0; 2];
+		// safety: fds has room for the two descriptors pipe writes
+		assert_eq!( unsafe { libc::pipe( fds.as_mut_ptr() ) }, 0 );
+		// safety: each descriptor is new, and owned by one File only
+		let (reader, mut writer) = unsafe { (File::from_raw_fd( fds[ 0 ] ), File::from_raw_fd( fds[ 1 ] )) };
+		let device = EvdevDevice::new( reader, caps() );
+		for (ev_type, code, value) in [
+			(EV_ABS, ABS_X, 200), (EV_ABS, ABS_HAT0X, 1), (EV_SYN, SYN_REPORT, 0),
+			(EV_KEY, BTN_THUMB, 1), (EV_ABS, ABS_HAT0X, 0), (EV_SYN, SYN_REPORT, 0),
+			(EV_ABS, ABS_X, 0),
+		] {
+			writer.write_all( &event( ev_type, code, value ) ).unwrap();
+		}
+		// as a recording ends
+		drop( writer );
+
+		let layout = ReportLayout::parse( &device.caps.report_descriptor() ).unwrap();
+		let x = layout.find( descriptor::USAGE_PAGE_GENERIC_DESKTOP, descriptor::USAGE_X, 0 ).unwrap();
+		let buttons = layout.buttons();
+		let mut buff = [0u8; 64];
+		let mut read = || {
+			assert_eq!( device.read_timeout( &mut buff, 100 ), Ok( 13 ) );
+			let pressed: Vec<usize> = (0..buttons.len()).filter( |n| buttons[ *n ].read( &buff, false ) == Some( 1 ) ).collect();
+			(x.read( &buff, false ).unwrap(), pressed)
+		};
+		assert_eq!( read(), (200, vec![ 3 ]) );
+		assert_eq!( read(), (200, vec![ 1 ]) );
+		// the last set is not complete, and nothing more will come
+		assert_eq!( device.read_timeout( &mut buff, 100 ), Ok( 0 ) );
+	}
+
+	#[test]
+	fn events_to_reports() {
+		let (mut writer, reader) = UnixStream::pair().unwrap();
+		let device = EvdevDevice::new( reader, caps() );
+		let mut stream: Vec<u8> = Vec::new();
+		for (ev_type, code, value) in [
+			(EV_ABS, ABS_X, 100), (EV_KEY, BTN_TRIGGER, 1), (EV_SYN, SYN_REPORT, 0),
+			// lost events: the rest of this set is only part of it
+			(EV_SYN, SYN_DROPPED, 0), (EV_ABS, ABS_X, 5), (EV_KEY, BTN_TRIGGER, 0), (EV_SYN, SYN_REPORT, 0),
+			(EV_ABS, ABS_HAT0Y, -1), (EV_ABS, ABS_THROTTLE, 7), (EV_SYN, SYN_REPORT, 0),
+		] {
+			stream.extend( event( ev_type, code, value ) );
+		}
+		// split mid-event, as a read may be
+		writer.write_all( &stream[ ..30 ] ).unwrap();
+		let mut buff = [0u8; 64];
+		assert_eq!( device.read_timeout( &mut buff, 0 ), Ok( 0 ) );
+		writer.write_all( &stream[ 30.. ] ).unwrap();
+
+		let layout = ReportLayout::parse( &device.caps.report_descriptor() ).unwrap();
+		let x = layout.find( descriptor::USAGE_PAGE_GENERIC_DESKTOP, descriptor::USAGE_X, 0 ).unwrap();
+		let throttle = layout.find( descriptor::USAGE_PAGE_GENERIC_DESKTOP, descriptor::USAGE_SLIDER, 0 ).unwrap();
+		let buttons = layout.buttons();
+		let mut read = || {
+			let len = device.read_timeout( &mut buff, 0 ).unwrap();
+			assert_eq!( len, 13 );
+			let pressed: Vec<usize> = (0..buttons.len()).filter( |n| buttons[ *n ].read( &buff, false ) == Some( 1 ) ).collect();
+			(x.read( &buff, false ).unwrap(), throttle.read( &buff, false ).unwrap(), pressed)
+		};
+		assert_eq!( read(), (100, 0, vec![ 0 ]) );
+		// a pipe cannot be asked for the state, the state before the lost events is kept
+		assert_eq!( read(), (100, 0, vec![ 0 ]) );
+		assert_eq!( read(), (100, 7, vec![ 0, 2 ]) );
+		assert_eq!( device.read_timeout( &mut buff, 0 ), Ok( 0 ) );
+	}
+}
+
+/* ******************************************************************************* *
+ *		*** End ***
+ * ******************************************************************************* */
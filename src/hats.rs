@@ -0,0 +1,307 @@
+/* ******************************************************************************* */
+/* Hats (POV switches): where each is in a device's reports, and which way it is pushed */
+/* ******************************************************************************* */
+/*
+ * A hat arrives either as an angle (0 up, counting clockwise, anything else centred) or
+ * as four or eight buttons, one for each way it can be pushed, e.g.
+ *	hat_1 = name=POV									the report descriptor's first hat
+ *	hat_1 = encoding=angle, byte=6, bit=4, bits=4		an angle of 0 to 7, in the top of byte 6
+ *	hat_2 = encoding=4_button, buttons=17 18 19 20		up, right, down and left, as button numbers
+ *	hat_3 = encoding=8_button, buttons=21 22 23 24 25 26 27 28		up, up right, right, ... up left
+ * With auto_map, and no hat_ keys, every hat in the report descriptor is shown.
+ * Positions are eighths of a turn, clockwise from up, as most hats have eight.
+ */
+use crate::buttons::Button;
+use crate::descriptor::{self, ReportLayout, };
+
+/* ******************************************************************************* */
+/* Structures */
+/* ******************************************************************************* */
+
+/// How a hat is sent
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum HatSource {
+	Angle {
+		at: Option<(usize, usize)>,		// byte and bit; None, the report descriptor's hat of the same number
+		bits: usize,
+		positions: i32,					// 8, or 4 for hats that only go up, right, down and left
+		min: i32,						// the value sent for up
+	},
+	Buttons( Vec<usize> ),				// four (up, right, down, left) or eight button numbers
+}
+
+/* ******************************************************************************* */
+/// One hat, as given by a hat_<n> key
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HatDef {
+	pub number: usize,				// n, from the key's name
+	pub source: HatSource,
+	pub name: Option<String>,
+}
+
+impl HatDef {
+	/// a hat from an .ini file: number is n, from hat_<n>
+	pub fn parse( number: usize, text: &str ) -> Result<HatDef, String> {
+		if number == 0 {
+			return Err( "hats are numbered from 1".to_string() );
+		}
+		let mut encoding = "angle".to_string();
+		let mut byte: Option<usize> = None;
+		let mut bit: usize = 0;
+		let mut bits: usize = 4;
+		let mut positions: i32 = 8;
+		let mut min: i32 = 0;
+		let mut buttons: Vec<usize> = Vec::new();
+		let mut name: Option<String> = None;
+		for field in text.split( ',' ).map( |f| f.trim() ).filter( |f| !f.is_empty() ) {
+			let Some( (key, value) ) = field.split_once( '=' ) else {
+				return Err( format!("'{}' should be name=value", field) );
+			};
+			let value = value.trim();
+			match key.trim().to_lowercase().as_str() {
+				"encoding" => {	encoding = value.to_lowercase();	}
+				"byte" => {	byte = Some( value.parse::<usize>().map_err( |e| format!("byte {}", e) )? );	}
+				"bit" => {	bit = value.parse::<usize>().map_err( |e| format!("bit {}", e) )?;	}
+				"bits" => {	bits = value.parse::<usize>().map_err( |e| format!("bits {}", e) )?;	}
+				"positions" => {	positions = value.parse::<i32>().map_err( |e| format!("positions {}", e) )?;	}
+				"min" => {	min = value.parse::<i32>().map_err( |e| format!("min {}", e) )?;	}
+				"buttons" => {
+					for b in value.split_whitespace() {
+						buttons.push( b.parse::<usize>().map_err( |e| format!("buttons {}", e) )? );
+					}
+				}
+				"name" => {	name = Some( value.to_string() ).filter( |v| !v.is_empty() );	}
+				other => {	return Err( format!("unknown field '{}'", other) );	}
+			}
+		}
+		let source = match encoding.as_str() {
+			"angle" => {
+				if !(1..=16).contains( &bits ) {
+					return Err( "bits should be 1 to 16".to_string() );
+				}
+				if positions != 4 && positions != 8 {
+					return Err( "positions should be 4 or 8".to_string() );
+				}
+				HatSource::Angle { at: byte.map( |b| (b, bit) ), bits, positions, min }
+			}
+			"4_button" | "8_button" => {
+				let wanted = if encoding == "4_button" { 4 } else { 8 };
+				if buttons.len() != wanted {
+					return Err( format!("{} needs {} buttons=, separated by spaces", encoding, wanted) );
+				}
+				HatSource::Buttons( buttons )
+			}
+			other => {	return Err( format!("unknown encoding '{}', use angle, 4_button or 8_button", other) );	}
+		};
+		Ok( HatDef { number, source, name } )
+	}
+}
+
+/* ******************************************************************************* */
+/// A hat in a device's report
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Hat {
+	pub number: usize,
+	pub name: Option<String>,
+	pub position: Option<u8>,		// eighths of a turn clockwise from up, None when centred
+}
+
+impl Hat {
+	/// degrees clockwise from up, None when centred
+	pub fn angle( &self ) -> Option<f32> {
+		self.position.map( |p| p as f32 * 45.0 )
+	}
+
+	/// the way the hat is pushed: N, NE, E, ... or centred
+	pub fn direction( &self ) -> &'static str {
+		match self.position {
+			Some( p ) => ["N", "NE", "E", "SE", "S", "SW", "W", "NW"][ p as usize % 8 ],
+			None => "centred",
+		}
+	}
+
+	/// the name if given, else "Hat n", and the way it is pushed
+	pub fn label( &self ) -> String {
+		match &self.name {
+			Some( name ) => format!("{}: {} {}", self.number, name, self.direction()),
+			None => format!("Hat {} {}", self.number, self.direction()),
+		}
+	}
+}
+
+/* ******************************************************************************* */
+/// every hat of a device, as the hat_<n> keys give them; without any, the report
+/// descriptor's hats (layout is only given when auto_map is on)
+/// buttons are the device's buttons from the same report, for hats sent as buttons;
+/// previous are the hats before this report, kept for those that are not in it
+pub fn read( defs: &[HatDef], layout: Option<&ReportLayout>, buff: &[u8], buttons: &[Button], previous: &[Hat] ) -> Vec<Hat> {
+	let found: Vec<HatDef>;
+	let defs = match (defs.is_empty(), layout) {
+		(true, Some( l )) => {
+			let count = l.fields.iter()
+				.filter( |f| f.usage_page == descriptor::USAGE_PAGE_GENERIC_DESKTOP && f.usage == descriptor::USAGE_HAT_SWITCH )
+				.count();
+			found = (1..=count).map( |n| HatDef {
+				number: n,
+				source: HatSource::Angle { at: None, bits: 4, positions: 8, min: 0 },
+				name: None,
+			}).collect();
+			&found
+		}
+		_ => defs,
+	};
+	defs.iter().map( |def| Hat {
+		number: def.number,
+		name: def.name.clone(),
+		position: position( def, layout, buff, buttons ).unwrap_or_else( || {
+			previous.iter().find( |h| h.number == def.number ).and_then( |h| h.position )
+		}),
+	}).collect()
+}
+
+/// which way a hat is pushed, in eighths of a turn (None, centred); None when the hat is
+/// not in this report, e.g. it is in a report with another id
+fn position( def: &HatDef, layout: Option<&ReportLayout>, buff: &[u8], buttons: &[Button] ) -> Option<Option<u8>> {
+	match &def.source {
+		HatSource::Angle { at: Some( (byte, bit) ), bits, positions, min } => {
+			let first = byte * 8 + bit;
+			let mut value: i32 = 0;
+			for n in 0..*bits {
+				let b = first + n;
+				if buff.get( b / 8 )? & (1 << (b % 8)) != 0 {
+					value |= 1 << n;
+				}
+			}
+			Some( angle_position( value - min, *positions ) )
+		}
+		HatSource::Angle { at: None, .. } => {
+			// the descriptor says how many positions, and what up is
+			let Some( field ) = layout.and_then( |l| l.find( descriptor::USAGE_PAGE_GENERIC_DESKTOP, descriptor::USAGE_HAT_SWITCH, def.number - 1 ) ) else {
+				return Some( None );
+			};
+			let value = field.read( buff, layout?.uses_report_ids )?;
+			Some( angle_position( value - field.logical_min, field.logical_max - field.logical_min + 1 ) )
+		}
+		HatSource::Buttons( numbers ) => {
+			let pressed: Vec<bool> = numbers.iter()
+				.map( |n| buttons.iter().any( |b| b.number == *n && b.pressed ) )
+				.collect();
+			match pressed.len() {
+				4 => Some( four_way( pressed[ 0 ], pressed[ 1 ], pressed[ 2 ], pressed[ 3 ] ) ),
+				_ => Some( pressed.iter().position( |p| *p ).map( |p| p as u8 ) ),
+			}
+		}
+	}
+}
+
+/// an angle of 0 to positions - 1 in eighths; anything outside that is centred
+fn angle_position( value: i32, positions: i32 ) -> Option<u8> {
+	if positions <= 0 || value < 0 || value >= positions {
+		return None;
+	}
+	Some( (value * 8 / positions) as u8 )
+}
+
+/// four buttons as eighths: two side by side are a diagonal, opposites cancel out
+fn four_way( up: bool, right: bool, down: bool, left: bool ) -> Option<u8> {
+	let v = up as i8 - down as i8;
+	let h = right as i8 - left as i8;
+	match (v, h) {
+		(1, 0) => Some( 0 ),
+		(1, 1) => Some( 1 ),
+		(0, 1) => Some( 2 ),
+		(-1, 1) => Some( 3 ),
+		(-1, 0) => Some( 4 ),
+		(-1, -1) => Some( 5 ),
+		(0, -1) => Some( 6 ),
+		(1, -1) => Some( 7 ),
+		_ => None,
+	}
+}
+
+/* ******************************************************************************* */
+/* Tests */
+/* ******************************************************************************* */
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::buttons;
+
+	// report 1 has buttons 1 to 8, report 2 buttons 9 to 12 and a hat
+	const TWO_REPORTS: &[u8] = &[
+		0x05, 0x01, 0x09, 0x04, 0xA1, 0x01,
+		0x85, 0x01, 0x05, 0x09, 0x19, 0x01, 0x29, 0x08, 0x15, 0x00, 0x25, 0x01, 0x75, 0x01, 0x95, 0x08, 0x81, 0x02,
+		0x85, 0x02, 0x05, 0x09, 0x19, 0x09, 0x29, 0x0C, 0x15, 0x00, 0x25, 0x01, 0x75, 0x01, 0x95, 0x04, 0x81, 0x02,
+		0x05, 0x01, 0x09, 0x39, 0x15, 0x00, 0x25, 0x07, 0x75, 0x04, 0x95, 0x01, 0x81, 0x42,
+		0xC0,
+	];
+
+	fn positions( hats: &[Hat] ) -> Vec<Option<u8>> {
+		hats.iter().map( |h| h.position ).collect()
+	}
+
+	#[test]
+	fn parse() {
+		let def = HatDef::parse( 1, "encoding=angle, byte=6, bit=4, bits=4, positions=4, min=1, name=POV" ).unwrap();
+		assert_eq!( def.source, HatSource::Angle { at: Some( (6, 4) ), bits: 4, positions: 4, min: 1 } );
+		assert_eq!( def.name.as_deref(), Some( "POV" ) );
+		assert_eq!( HatDef::parse( 2, "encoding=4_button, buttons=17 18 19 20" ).unwrap().source, HatSource::Buttons( vec![ 17, 18, 19, 20 ] ) );
+		assert_eq!( HatDef::parse( 3, "" ).unwrap().source, HatSource::Angle { at: None, bits: 4, positions: 8, min: 0 } );
+		for text in [ "bits=0", "positions=6", "encoding=4_button, buttons=1 2 3", "encoding=8_button, buttons=1 2 3 4", "encoding=twist", "byte", "colour=red" ] {
+			assert!( HatDef::parse( 1, text ).is_err(), "{}", text );
+		}
+		assert!( HatDef::parse( 0, "" ).is_err() );
+	}
+
+	#[test]
+	fn angles() {
+		let defs = vec![
+			HatDef::parse( 1, "byte=1, bit=4" ).unwrap(),
+			HatDef::parse( 2, "byte=2, bits=3, positions=4, min=1" ).unwrap(),
+		];
+		let hats = read( &defs, None, &[ 1, 0x2F, 0b100 ], &[], &[] );
+		assert_eq!( positions( &hats ), vec![ Some( 2 ), Some( 6 ) ] );
+		// anything past the last position is centred
+		let hats = read( &defs, None, &[ 1, 0xF0, 0 ], &[], &[] );
+		assert_eq!( positions( &hats ), vec![ None, None ] );
+		assert_eq!( (hats[ 0 ].direction(), hats[ 0 ].label()), ("centred", "Hat 1 centred".to_string()) );
+		let hat = Hat { number: 2, name: Some( "POV".to_string() ), position: Some( 5 ) };
+		assert_eq!( (hat.angle(), hat.label()), (Some( 225.0 ), "2: POV SW".to_string()) );
+	}
+
+	#[test]
+	fn buttons_as_hats() {
+		let defs = vec![
+			HatDef::parse( 1, "encoding=4_button, buttons=1 2 3 4" ).unwrap(),
+			HatDef::parse( 2, "encoding=8_button, buttons=5 6 7 8 9 10 11 12" ).unwrap(),
+		];
+		let hat = |pressed: &[usize]| -> Vec<Option<u8>> {
+			let list: Vec<Button> = (1..=12).map( |n| buttons::button( n, &[], pressed.contains( &n ) ) ).collect();
+			positions( &read( &defs, None, &[], &list, &[] ) )
+		};
+		assert_eq!( hat( &[] ), vec![ None, None ] );
+		assert_eq!( hat( &[ 1, 2, 11 ] ), vec![ Some( 1 ), Some( 6 ) ] );
+		assert_eq!( hat( &[ 3, 4 ] ), vec![ Some( 5 ), None ] );
+		// opposites cancel out
+		assert_eq!( hat( &[ 1, 3 ] ), vec![ None, None ] );
+		assert_eq!( hat( &[ 1, 2, 4 ] ), vec![ Some( 0 ), None ] );
+	}
+
+	#[test]
+	fn hats_in_other_reports_are_kept() {
+		let layout = ReportLayout::parse( TWO_REPORTS ).unwrap();
+		let hats = read( &[], Some( &layout ), &[ 2, 0x30 ], &[], &[] );
+		assert_eq!( positions( &hats ), vec![ Some( 3 ) ] );
+		let hats = read( &[], Some( &layout ), &[ 1, 0xFF ], &[], &hats );
+		assert_eq!( positions( &hats ), vec![ Some( 3 ) ] );
+		let hats = read( &[], Some( &layout ), &[ 2, 0xF0 ], &[], &hats );
+		assert_eq!( positions( &hats ), vec![ None ] );
+		// a hat the descriptor does not have is centred
+		let defs = vec![ HatDef::parse( 2, "" ).unwrap() ];
+		assert_eq!( positions( &read( &defs, Some( &layout ), &[ 2, 0x30 ], &[], &[] ) ), vec![ None ] );
+	}
+}
+
+/* ******************************************************************************* *
+ *		*** End ***
+ * ******************************************************************************* */
@@ -0,0 +1,222 @@
+/* ******************************************************************************* */
+/* Hot-plug: when to look again for devices */
+/* ******************************************************************************* */
+/*
+ * Looking for devices (enumerating) is slow, so it is not done every frame.
+ * An ArrivalSource says when it is worth looking again:
+ *	IntervalSource	every so often, works everywhere
+ *	UeventSource	Linux, when the kernel says a device has been added or removed;
+ *					reads kernel uevents, or the same messages from any file or pipe
+ */
+use std::time::{Duration, Instant, };
+
+/* ******************************************************************************* */
+
+pub trait ArrivalSource: Send {
+	/// true when devices may have arrived or gone since the last call
+	fn poll( &mut self ) -> bool;
+}
+
+/* ******************************************************************************* */
+/// Say "look again" at a fixed interval
+pub struct IntervalSource {
+	every: Duration,
+	last: Instant,
+}
+
+impl IntervalSource {
+	pub fn new( every: Duration ) -> IntervalSource {
+		IntervalSource { every, last: Instant::now() }
+	}
+}
+
+impl ArrivalSource for IntervalSource {
+	fn poll( &mut self ) -> bool {
+		if self.last.elapsed() >= self.every {
+			self.last = Instant::now();
+			return true;
+		}
+		false
+	}
+}
+
+/* ******************************************************************************* */
+/// Never look again, devices must be present when the program starts
+pub struct NoSource;
+
+impl ArrivalSource for NoSource {
+	fn poll( &mut self ) -> bool {
+		false
+	}
+}
+
+/* ******************************************************************************* */
+/* uevents, Linux only */
+/* ******************************************************************************* */
+#[cfg(target_os = "linux")]
+pub use uevent::UeventSource;
+
+#[cfg(target_os = "linux")]
+mod uevent {
+	use super::ArrivalSource;
+	use std::io::Read;
+	use std::os::unix::io::{AsRawFd, FromRawFd, RawFd, };
+	use std::time::{Duration, Instant, };
+
+	// the kernel sends the event before udev has set permissions on the new node,
+	// so keep looking for a little while after each event
+	const SETTLE: Duration = Duration::from_secs( 2 );
+	const RETRY: Duration = Duration::from_millis( 250 );
+
+	// the subsystems joysticks appear in
+	const SUBSYSTEMS: [&str; 3] = ["SUBSYSTEM=hidraw", "SUBSYSTEM=input", "SUBSYSTEM=usb"];
+
+	pub struct UeventSource {
+		source: Box<dyn Read + Send>,
+		fd: RawFd,
+		pending_until: Option<Instant>,
+		last: Instant,
+	}
+
+	impl UeventSource {
+		/// listen to the kernel's uevents
+		pub fn netlink() -> Result<UeventSource, String> {
+			// safety: plain socket calls; the fd is owned by the File made from it
+			unsafe {
+				let fd = libc::socket( libc::AF_NETLINK,
+										libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+										libc::NETLINK_KOBJECT_UEVENT );
+				if fd < 0 {
+					return Err( std::io::Error::last_os_error().to_string() );
+				}
+				let file = std::fs::File::from_raw_fd( fd );
+				let mut addr: libc::sockaddr_nl = std::mem::zeroed();
+				addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+				addr.nl_groups = 1;		// kernel events
+				if libc::bind( fd,
+								&addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+								std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t ) < 0 {
+					return Err( std::io::Error::last_os_error().to_string() );
+				}
+				Ok( UeventSource::new( file ) )
+			}
+		}
+
+		/// read uevent messages (e.g. "add@/devices/...\0ACTION=add\0SUBSYSTEM=hidraw\0")
+		/// from source; a pipe or file can stand in for the kernel
+		pub fn new<R: Read + AsRawFd + Send + 'static>( source: R ) -> UeventSource {
+			UeventSource {
+				fd: source.as_raw_fd(),
+				source: Box::new( source ),
+				pending_until: None,
+				last: Instant::now() - RETRY,
+			}
+		}
+
+		/// read whatever is waiting, true if any of it was a joystick arriving or leaving
+		fn read_events( &mut self ) -> bool {
+			let mut ret = false;
+			loop {
+				let mut fds = libc::pollfd { fd: self.fd, events: libc::POLLIN, revents: 0 };
+				// safety: fds is a single, valid pollfd for the duration of the call
+				if unsafe { libc::poll( &mut fds, 1, 0 ) } <= 0 || fds.revents & libc::POLLIN == 0 {
+					return ret;
+				}
+				let mut buff = [0u8; 8192];
+				match self.source.read( &mut buff ) {
+					Ok( 0 ) | Err( _ ) => {	return ret;	}
+					Ok( len ) => {
+						ret |= is_device_change( &buff[ ..len ] );
+					}
+				}
+			}
+		}
+	}
+
+	impl ArrivalSource for UeventSource {
+		fn poll( &mut self ) -> bool {
+			if self.read_events() {
+				self.pending_until = Some( Instant::now() + SETTLE );
+			}
+			match self.pending_until {
+				Some( until ) if Instant::now() < until => {
+					if self.last.elapsed() >= RETRY {
+						self.last = Instant::now();
+						return true;
+					}
+					false
+				}
+				Some( _ ) => {
+					self.pending_until = None;
+					false
+				}
+				None => {	false	}
+			}
+		}
+	}
+
+	fn is_device_change( message: &[u8] ) -> bool {
+		let text = String::from_utf8_lossy( message );
+		let fields: Vec<&str> = text.split( ['\0', '\n'] ).collect();
+		let action = fields.iter().any( |f| {
+			f.starts_with( "add@" ) || f.starts_with( "remove@" )
+				|| *f == "ACTION=add" || *f == "ACTION=remove"
+		});
+		action && fields.iter().any( |f| SUBSYSTEMS.contains( f ) )
+	}
+}
+
+/* ******************************************************************************* */
+/* Tests */
+/* ******************************************************************************* */
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn interval() {
+		let mut source = IntervalSource::new( Duration::ZERO );
+		assert!( source.poll() );
+		let mut source = IntervalSource::new( Duration::from_secs( 3600 ) );
+		assert!( !source.poll() );
+		assert!( !NoSource.poll() );
+	}
+
+	#[cfg(target_os = "linux")]
+	#[test]
+	fn uevents_from_a_pipe() {
+		use std::io::Write;
+		use std::os::unix::net::UnixStream;
+
+		let (mut kernel, listener) = UnixStream::pair().unwrap();
+		let mut source = UeventSource::new( listener );
+		assert!( !source.poll() );
+
+		// a disk: nothing to look for
+		kernel.write_all( b"add@/devices/virtual/block/loop0\0ACTION=add\0SUBSYSTEM=block\0" ).unwrap();
+		assert!( !source.poll() );
+
+		kernel.write_all( b"add@/devices/pci0000:00/usb1/1-2/1-2:1.0/0003:3344:0001.0001/hidraw/hidraw3\0ACTION=add\0SUBSYSTEM=hidraw\0" ).unwrap();
+		assert!( source.poll() );
+		// and again, a little later, while udev sets up the node
+		assert!( !source.poll() );
+		std::thread::sleep( Duration::from_millis( 300 ) );
+		assert!( source.poll() );
+
+		// as udevadm monitor shows them, one field to a line
+		let (mut kernel, listener) = UnixStream::pair().unwrap();
+		let mut source = UeventSource::new( listener );
+		kernel.write_all( b"ACTION=remove\nDEVPATH=/devices/pci0000:00/usb1/1-2\nSUBSYSTEM=usb\n" ).unwrap();
+		assert!( source.poll() );
+
+		// the writer has gone: nothing more, and no error
+		let (kernel, listener) = UnixStream::pair().unwrap();
+		let mut source = UeventSource::new( listener );
+		drop( kernel );
+		assert!( !source.poll() );
+	}
+}
+
+/* ******************************************************************************* *
+ *		*** End ***
+ * ******************************************************************************* */
@@ -0,0 +1,191 @@
+/* ******************************************************************************* */
+/* Changing single values in the .ini file */
+/* ******************************************************************************* */
+/*
+ * configparser can write a whole file, but drops comments and layout on the way.
+ * Users keep notes in their .ini files, so values are changed line by line instead:
+ * everything not being changed is written back as it was.
+ */
+use configparser::ini::{Ini, IniDefault, };
+use std::fs;
+
+/* ******************************************************************************* */
+/// a parser for this program's .ini files: ';' and '#' start comment lines only,
+/// as values use ';' to separate lists, e.g. routes' sources and buttons_from
+pub fn parser() -> Ini {
+	let mut defaults = IniDefault::default();
+	defaults.enable_inline_comments = false;
+	Ini::new_from_defaults( defaults )
+}
+
+/* ******************************************************************************* */
+/// the value of key in section, as written in the file
+/// section names are not case sensitive, as when the file is read
+pub fn get_value( path: &str, section: &str, key: &str ) -> Result<Option<String>, String> {
+	let text = fs::read_to_string( path ).map_err( |err| format!("{} {}", path, err) )?;
+	Ok( get_value_in( &text, section, key ) )
+}
+
+/* ******************************************************************************* */
+/// set key in section to value, adding the key (at the end of the section) if it is not there,
+/// and the section (at the end of the file) if that is not there
+pub fn set_value( path: &str, section: &str, key: &str, value: &str ) -> Result<(), String> {
+	let text = fs::read_to_string( path ).map_err( |err| format!("{} {}", path, err) )?;
+	let text = set_value_in( &text, section, key, value )?;
+	fs::write( path, text ).map_err( |err| format!("{} {}", path, err) )
+}
+
+/* ******************************************************************************* */
+
+fn get_value_in( text: &str, section: &str, key: &str ) -> Option<String> {
+	let mut in_section = false;
+	for line in text.lines() {
+		if let Some( name ) = section_name( line ) {
+			in_section = name.eq_ignore_ascii_case( section );
+			continue;
+		}
+		if in_section {
+			if let Some( (k, v) ) = key_value( line ) {
+				if k.eq_ignore_ascii_case( key ) {
+					return Some( v.trim().to_string() );
+				}
+			}
+		}
+	}
+	None
+}
+
+/* ******************************************************************************* */
+
+fn set_value_in( text: &str, section: &str, key: &str, value: &str ) -> Result<String, String> {
+	let lines: Vec<&str> = text.lines().collect();
+	let mut ret: Vec<String> = Vec::with_capacity( lines.len() + 1 );
+	let mut in_section = false;
+	let mut found_section = false;
+	let mut done = false;
+	// where to add the key, and how to indent it, if it is not already there
+	let mut last_key: Option<usize> = None;
+	let mut indent = "\t".to_string();
+
+	for line in lines {
+		if let Some( name ) = section_name( line ) {
+			in_section = name.eq_ignore_ascii_case( section );
+			found_section |= in_section;
+			if in_section {
+				last_key = Some( ret.len() );
+			}
+			ret.push( line.to_string() );
+			continue;
+		}
+		if in_section && !done {
+			if let Some( (k, _) ) = key_value( line ) {
+				indent = line[ ..line.len() - line.trim_start().len() ].to_string();
+				if k.eq_ignore_ascii_case( key ) {
+					ret.push( format!("{}{} = {}", indent, key, value) );
+					done = true;
+					continue;
+				}
+				last_key = Some( ret.len() );
+			}
+		}
+		ret.push( line.to_string() );
+	}
+
+	if !found_section {
+		if ret.last().is_some_and( |l| !l.trim().is_empty() ) {
+			ret.push( String::new() );
+		}
+		ret.push( format!("[{}]", section) );
+		ret.push( format!("{}{} = {}", indent, key, value) );
+	} else if !done {
+		if let Some( n ) = last_key {
+			ret.insert( n + 1, format!("{}{} = {}", indent, key, value) );
+		}
+	}
+	let mut text_out = ret.join( "\n" );
+	if text.ends_with( '\n' ) || !found_section {
+		text_out.push( '\n' );
+	}
+	Ok( text_out )
+}
+
+/* ******************************************************************************* */
+
+/// a key = value line's key (trimmed) and value; comment lines, whatever is in them, are not
+fn key_value( line: &str ) -> Option<(&str, &str)> {
+	let trimmed = line.trim_start();
+	if trimmed.starts_with( ';' ) || trimmed.starts_with( '#' ) {
+		return None;
+	}
+	line.split_once( '=' ).map( |(k, v)| (k.trim(), v) )
+}
+
+fn section_name( line: &str ) -> Option<&str> {
+	let line = line.trim();
+	if line.starts_with( '[' ) && line.ends_with( ']' ) {
+		return Some( line[ 1..line.len() - 1 ].trim() );
+	}
+	None
+}
+
+/* ******************************************************************************* */
+/* Tests */
+/* ******************************************************************************* */
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn lists_are_not_comments() {
+		let text = "; a comment\n[HOTAS]\n# another\nbuttons_from = right js; throttle\nrz_route = pedals:rx; pedals:ry; mix=toe_brakes\n";
+		let config = parser().read( text.to_string() ).unwrap();
+		let section = &config[ "hotas" ];
+		assert_eq!( section.len(), 2 );
+		assert_eq!( section[ "buttons_from" ].as_deref(), Some( "right js; throttle" ) );
+		assert_eq!( section[ "rz_route" ].as_deref(), Some( "pedals:rx; pedals:ry; mix=toe_brakes" ) );
+	}
+
+	const TEXT: &str = "; x = 1, 2 was wrong\n[Stick]\n\tvid = 3344\n\t# x = 5, 6\n\tx = 1, 2\n\n[Throttle]\n\tvid = 3344\n";
+
+	#[test]
+	fn comments_are_not_keys() {
+		assert_eq!( get_value_in( TEXT, "stick", "x" ), Some( "1, 2".to_string() ) );
+		assert_eq!( get_value_in( "[Stick]\n; x = 5, 6\n", "stick", "x" ), None );
+		assert_eq!( get_value_in( TEXT, "throttle", "x" ), None );
+	}
+
+	#[test]
+	fn replace_a_key() {
+		let text = set_value_in( TEXT, "STICK", "x", "3, 4" ).unwrap();
+		assert_eq!( text, TEXT.replace( "\tx = 1, 2", "\tx = 3, 4" ) );
+	}
+
+	#[test]
+	fn add_a_key() {
+		let text = set_value_in( TEXT, "throttle", "slider_0", "1, 2" ).unwrap();
+		assert_eq!( text, format!("{}\tslider_0 = 1, 2\n", TEXT) );
+		// after the section's last key, not its blank lines or comments
+		let text = set_value_in( TEXT, "stick", "y", "3, 4" ).unwrap();
+		assert_eq!( text, TEXT.replace( "\tx = 1, 2\n", "\tx = 1, 2\n\ty = 3, 4\n" ) );
+	}
+
+	#[test]
+	fn add_a_section() {
+		let text = set_value_in( TEXT, "pedals", "rz", "1, 2" ).unwrap();
+		assert_eq!( text, format!("{}\n[pedals]\n\trz = 1, 2\n", TEXT) );
+		assert_eq!( set_value_in( "", "pedals", "rz", "1, 2" ).unwrap(), "[pedals]\n\trz = 1, 2\n" );
+	}
+
+	#[test]
+	fn comments_are_kept() {
+		let text = "[Stick]\n; x = 5, 6, the old offsets\n\tx = 1, 2\n# y = 7, 8\n";
+		let changed = set_value_in( text, "stick", "x", "3, 4" ).unwrap();
+		assert_eq!( changed, "[Stick]\n; x = 5, 6, the old offsets\n\tx = 3, 4\n# y = 7, 8\n" );
+		let added = set_value_in( text, "stick", "y", "9, 10" ).unwrap();
+		assert_eq!( added, "[Stick]\n; x = 5, 6, the old offsets\n\tx = 1, 2\n\ty = 9, 10\n# y = 7, 8\n" );
+	}
+}
+
+/* ******************************************************************************* *
+ *		*** End ***
+ * ******************************************************************************* */
@@ -0,0 +1,415 @@
+/* ******************************************************************************* */
+/* Layout: where each device is shown in the window */
+/* ******************************************************************************* */
+/*
+ * The [Layout] section of the .ini file places devices by their section names, e.g.
+ *	[Layout]
+ *	throttle = row=0, column=0
+ *	right js = row=0, column=1
+ *	rudder pedals = row=1, column=0, anchor=top
+ *	overlay = x=120, y=40, scale=0.5, rotation=90, z=1, anchor=centre
+ * Devices with a row (and column) are put in a grid: each column as wide as its widest
+ * device, each row as tall as its tallest, gap apart.  Devices with an x and y are put
+ * there, in window pixels, over the grid.  A device not in the [Layout] section goes in
+ * row 0, at its col, as it always has; one with col = -1 as well is not shown.
+ * The sizes used are those the devices took when last drawn (see arrange), so a grid
+ * makes room for buttons, hats and widgets under the images.
+ */
+use std::collections::HashMap;
+
+/* ******************************************************************************* */
+/* Constants */
+
+const DEFAULT_GAP: f32 = 8.0;		// pixels between rows and columns
+const MAX_GRID: usize = 64;			// rows, and columns, in the grid: far more than there are devices
+
+/* ******************************************************************************* */
+/* Structures */
+/* ******************************************************************************* */
+
+/// Which point of a device is put at its place: its x and y, or the same point of its grid cell
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Anchor {
+	#[default]
+	TopLeft,
+	Top,
+	TopRight,
+	Left,
+	Centre,
+	Right,
+	BottomLeft,
+	Bottom,
+	BottomRight,
+}
+
+impl Anchor {
+	pub fn from_name( name: &str ) -> Result<Anchor, String> {
+		match name.trim().to_lowercase().replace( '-', "_" ).as_str() {
+			"top_left" => Ok( Anchor::TopLeft ),
+			"top" => Ok( Anchor::Top ),
+			"top_right" => Ok( Anchor::TopRight ),
+			"left" => Ok( Anchor::Left ),
+			"centre" | "center" => Ok( Anchor::Centre ),
+			"right" => Ok( Anchor::Right ),
+			"bottom_left" => Ok( Anchor::BottomLeft ),
+			"bottom" => Ok( Anchor::Bottom ),
+			"bottom_right" => Ok( Anchor::BottomRight ),
+			other => Err( format!("unknown anchor '{}'", other) ),
+		}
+	}
+
+	/// the anchor's point in a box of width by height, from its top left corner
+	pub fn point( &self, width: f32, height: f32 ) -> (f32, f32) {
+		let x = match self {
+			Anchor::TopLeft | Anchor::Left | Anchor::BottomLeft => 0.0,
+			Anchor::Top | Anchor::Centre | Anchor::Bottom => width * 0.5,
+			Anchor::TopRight | Anchor::Right | Anchor::BottomRight => width,
+		};
+		let y = match self {
+			Anchor::TopLeft | Anchor::Top | Anchor::TopRight => 0.0,
+			Anchor::Left | Anchor::Centre | Anchor::Right => height * 0.5,
+			Anchor::BottomLeft | Anchor::Bottom | Anchor::BottomRight => height,
+		};
+		(x, y)
+	}
+}
+
+/* ******************************************************************************* */
+/// Where one device goes, as its line in the [Layout] section gives it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Place {
+	pub at: Option<(f32, f32)>,			// x and y, in window pixels
+	pub row: Option<usize>,
+	pub column: Option<usize>,			// None, after the others in its row
+	pub scale: f32,
+	pub rotation: f32,					// degrees, clockwise
+	pub z: i32,							// higher is drawn later, over lower
+	pub anchor: Anchor,
+}
+
+impl Default for Place {
+	fn default() -> Self {
+		Place { at: None, row: None, column: None, scale: 1.0, rotation: 0.0, z: 0, anchor: Anchor::TopLeft }
+	}
+}
+
+impl Place {
+	fn parse( text: &str ) -> Result<Place, String> {
+		let mut place = Place::default();
+		let mut x: Option<f32> = None;
+		let mut y: Option<f32> = None;
+		for field in text.split( ',' ).map( |f| f.trim() ).filter( |f| !f.is_empty() ) {
+			let Some( (name, value) ) = field.split_once( '=' ) else {
+				return Err( format!("'{}' should be name=value", field) );
+			};
+			let value = value.trim();
+			let float = |v: &str| v.parse::<f32>().map_err( |e| format!("{} {}", name.trim(), e) );
+			let whole = |v: &str| match v.parse::<usize>() {
+				Ok( n ) if n < MAX_GRID => Ok( n ),
+				Ok( _ ) => Err( format!("{} should be less than {}", name.trim(), MAX_GRID) ),
+				Err( e ) => Err( format!("{} {}", name.trim(), e) ),
+			};
+			match name.trim().to_lowercase().as_str() {
+				"x" => {	x = Some( float( value )? );	}
+				"y" => {	y = Some( float( value )? );	}
+				"row" => {	place.row = Some( whole( value )? );	}
+				"column" | "col" => {	place.column = Some( whole( value )? );	}
+				"scale" => {
+					place.scale = float( value )?;
+					if !(place.scale.is_finite() && place.scale > 0.0) {
+						return Err( "scale should be more than 0".to_string() );
+					}
+				}
+				"rotation" => {	place.rotation = float( value )?;	}
+				"z" => {	place.z = value.parse::<i32>().map_err( |e| format!("z {}", e) )?;	}
+				"anchor" => {	place.anchor = Anchor::from_name( value )?;	}
+				other => {	return Err( format!("unknown field '{}'", other) );	}
+			}
+		}
+		if x.is_some() || y.is_some() {
+			place.at = Some( (x.unwrap_or( 0.0 ), y.unwrap_or( 0.0 )) );
+		}
+		if place.at.is_none() && place.row.is_none() {
+			place.row = Some( 0 );
+		}
+		Ok( place )
+	}
+}
+
+/* ******************************************************************************* */
+/// One device, as it is to be drawn
+#[derive(Clone, Debug, PartialEq)]
+pub struct Arranged {
+	pub section: String,
+	pub x: f32,							// top left corner, in window pixels
+	pub y: f32,
+	pub scale: f32,
+	pub rotation: f32,					// degrees, clockwise
+}
+
+/* ******************************************************************************* */
+/// The [Layout] section, read
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layout {
+	places: HashMap<String, Place>,		// by section name, lower case
+	gap: f32,
+}
+
+impl Default for Layout {
+	fn default() -> Self {
+		Layout { places: HashMap::new(), gap: DEFAULT_GAP }
+	}
+}
+
+impl Layout {
+	/// the [Layout] section's keys: gap, and a place for each device, by its section name
+	/// returns the layout, and an error for each line that could not be read
+	pub fn from_config( values: HashMap<String, Option<String>> ) -> (Layout, Vec<String>) {
+		let mut layout = Layout::default();
+		let mut errors: Vec<String> = Vec::new();
+		for (key, value) in values {
+			let key = key.trim().to_lowercase();
+			let value = value.unwrap_or_default();
+			match key.as_str() {
+				"comment" => {}
+				"gap" => {
+					match value.trim().parse::<f32>() {
+						Ok( gap ) if gap.is_finite() && gap >= 0.0 => {	layout.gap = gap;	}
+						Ok( _ ) => {	errors.push( "[Layout] gap should be 0 or more".to_string() );	}
+						Err( err ) => {	errors.push( format!("[Layout] gap {}", err) );	}
+					}
+				}
+				section => {
+					match Place::parse( &value ) {
+						Ok( place ) => {	layout.places.insert( section.to_string(), place );	}
+						Err( err ) => {	errors.push( format!("[Layout] {}: {}", section, err) );	}
+					}
+				}
+			}
+		}
+		(layout, errors)
+	}
+
+	/// the sections placed, that are not among the devices' sections
+	pub fn unknown<'a>( &'a self, sections: &[&str] ) -> Vec<&'a str> {
+		let mut ret: Vec<&str> = self.places.keys()
+			.filter( |name| !sections.iter().any( |s| s.eq_ignore_ascii_case( name ) ) )
+			.map( |name| name.as_str() )
+			.collect();
+		ret.sort();
+		ret
+	}
+
+	/// where each device goes, in the order to draw them (lowest z first)
+	/// devices: each device's section, col (usize::MAX if not shown) and size when last drawn
+	/// (at a scale of 1), in the order they should take in a row when not given a column
+	pub fn arrange( &self, devices: &[(String, usize, (f32, f32))] ) -> Vec<Arranged> {
+		// every device shown, with its place: as given, else in row 0 at its col
+		let mut placed: Vec<(&str, Place, (f32, f32))> = Vec::new();
+		for (section, col, size) in devices {
+			let place = match self.places.get( &section.to_lowercase() ) {
+				Some( place ) => place.clone(),
+				None if *col < usize::MAX => Place { row: Some( 0 ), column: Some( (*col).min( MAX_GRID - 1 ) ), ..Place::default() },
+				None => {	continue;	}
+			};
+			let size = (size.0 * place.scale, size.1 * place.scale);
+			placed.push( (section, place, size) );
+		}
+
+		// rows given without a column follow the columns given
+		let mut next: HashMap<usize, usize> = HashMap::new();
+		for (_, place, _) in placed.iter().filter( |(_, p, _)| p.at.is_none() ) {
+			if let (Some( row ), Some( column )) = (place.row, place.column) {
+				let n = next.entry( row ).or_insert( 0 );
+				*n = (*n).max( column + 1 );
+			}
+		}
+		for (_, place, _) in placed.iter_mut().filter( |(_, p, _)| p.at.is_none() && p.column.is_none() ) {
+			let n = next.entry( place.row.unwrap_or( 0 ) ).or_insert( 0 );
+			place.column = Some( *n );
+			*n += 1;
+		}
+
+		// each column as wide as its widest, each row as tall as its tallest
+		let mut widths: Vec<f32> = Vec::new();
+		let mut heights: Vec<f32> = Vec::new();
+		for (_, place, size) in placed.iter().filter( |(_, p, _)| p.at.is_none() ) {
+			let (row, column) = (place.row.unwrap_or( 0 ), place.column.unwrap_or( 0 ));
+			if widths.len() <= column { widths.resize( column + 1, 0.0 ); }
+			if heights.len() <= row { heights.resize( row + 1, 0.0 ); }
+			widths[ column ] = widths[ column ].max( size.0 );
+			heights[ row ] = heights[ row ].max( size.1 );
+		}
+		// empty rows and columns take no room
+		let starts = |sizes: &[f32]| -> Vec<f32> {
+			let mut at = 0.0;
+			sizes.iter().map( |s| {
+				let start = at;
+				if *s > 0.0 { at += s + self.gap; }
+				start
+			}).collect()
+		};
+		let (xs, ys) = (starts( &widths ), starts( &heights ));
+
+		let mut ret: Vec<(i32, Arranged)> = placed.into_iter().map( |(section, place, size)| {
+			// the anchor's point: the x and y given, or the same point of the grid cell
+			let (x, y) = match place.at {
+				Some( at ) => at,
+				None => {
+					let (row, column) = (place.row.unwrap_or( 0 ), place.column.unwrap_or( 0 ));
+					let (cx, cy) = place.anchor.point( widths[ column ], heights[ row ] );
+					(xs[ column ] + cx, ys[ row ] + cy)
+				}
+			};
+			let (ax, ay) = place.anchor.point( size.0, size.1 );
+			(place.z, Arranged {
+				section: section.to_string(),
+				x: x - ax,
+				y: y - ay,
+				scale: place.scale,
+				rotation: place.rotation,
+			})
+		}).collect();
+		// nothing left of, or above, the window
+		let left = ret.iter().map( |(_, a)| a.x ).fold( 0.0, f32::min );
+		let top = ret.iter().map( |(_, a)| a.y ).fold( 0.0, f32::min );
+		for (_, a) in ret.iter_mut() {
+			a.x -= left;
+			a.y -= top;
+		}
+		ret.sort_by_key( |(z, _)| *z );		// stable: same z, in the order given
+		ret.into_iter().map( |(_, a)| a ).collect()
+	}
+}
+
+/* ******************************************************************************* */
+/// the size of the box around a width by height box, turned by rotation degrees about its centre
+pub fn turned_size( size: (f32, f32), rotation: f32 ) -> (f32, f32) {
+	let (sin, cos) = rotation.to_radians().sin_cos();
+	let (sin, cos) = (sin.abs(), cos.abs());
+	(size.0 * cos + size.1 * sin, size.0 * sin + size.1 * cos)
+}
+
+/* ******************************************************************************* */
+/* Tests */
+/* ******************************************************************************* */
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn layout( lines: &[(&str, &str)] ) -> Layout {
+		let values = lines.iter().map( |(k, v)| (k.to_string(), Some( v.to_string() )) ).collect();
+		let (layout, errors) = Layout::from_config( values );
+		assert!( errors.is_empty(), "{:?}", errors );
+		layout
+	}
+
+	/// each device's section and top left corner
+	fn corners( arranged: &[Arranged] ) -> Vec<(&str, f32, f32)> {
+		arranged.iter().map( |a| (a.section.as_str(), a.x, a.y) ).collect()
+	}
+
+	#[test]
+	fn places() {
+		let place = Place::parse( "x=120, y=40, scale=0.5, rotation=90, z=1, anchor=Bottom-Right" ).unwrap();
+		assert_eq!( place, Place {
+			at: Some( (120.0, 40.0) ), row: None, column: None, scale: 0.5, rotation: 90.0, z: 1, anchor: Anchor::BottomRight,
+		} );
+		assert_eq!( Place::parse( "col=2" ).unwrap(), Place { row: Some( 0 ), column: Some( 2 ), ..Place::default() } );
+		assert_eq!( Place::parse( "y=10" ).unwrap().at, Some( (0.0, 10.0) ) );
+		for text in [ "row", "row=-1", "row=64", "column=1000000", "scale=0", "scale=inf", "scale=NaN", "x=left", "anchor=middle", "colour=red" ] {
+			assert!( Place::parse( text ).is_err(), "{}", text );
+		}
+		assert_eq!( Place::parse( "row=63" ).unwrap().row, Some( 63 ) );
+
+		let (layout, errors) = Layout::from_config( [
+			("gap".to_string(), Some( "wide".to_string() )),
+			("Throttle".to_string(), Some( "row=99".to_string() )),
+			("stick".to_string(), Some( "row=1".to_string() )),
+		].into_iter().collect() );
+		errors.iter().for_each( |e| assert!( e.starts_with( "[Layout] " ), "{}", e ) );
+		assert_eq!( errors.len(), 2 );
+		assert_eq!( layout.unknown( &[ "Stick" ] ), Vec::<&str>::new() );
+		assert_eq!( layout.unknown( &[ "pedals" ] ), vec![ "stick" ] );
+
+		for gap in [ "-1", "inf", "NaN" ] {
+			let (layout, errors) = Layout::from_config( [ ("gap".to_string(), Some( gap.to_string() )) ].into_iter().collect() );
+			assert_eq!( errors, vec![ "[Layout] gap should be 0 or more".to_string() ], "{}", gap );
+			assert_eq!( layout.gap, Layout::default().gap );
+		}
+	}
+
+	#[test]
+	fn anchors() {
+		assert_eq!( Anchor::from_name( "center" ), Ok( Anchor::Centre ) );
+		assert_eq!( Anchor::TopLeft.point( 10.0, 20.0 ), (0.0, 0.0) );
+		assert_eq!( Anchor::Centre.point( 10.0, 20.0 ), (5.0, 10.0) );
+		assert_eq!( Anchor::BottomRight.point( 10.0, 20.0 ), (10.0, 20.0) );
+		assert_eq!( Anchor::Left.point( 10.0, 20.0 ), (0.0, 10.0) );
+	}
+
+	#[test]
+	fn grid() {
+		let layout = layout( &[
+			("gap", "10"),
+			("throttle", "row=0, column=0"),
+			("right js", "row=0, column=1"),
+			("pedals", "row=2, anchor=top"),
+			("mfd", "row=0"),
+		] );
+		let arranged = layout.arrange( &[
+			("Throttle".to_string(), 0, (100.0, 200.0)),
+			("Right JS".to_string(), 1, (150.0, 100.0)),
+			("Pedals".to_string(), 0, (50.0, 50.0)),
+			("MFD".to_string(), 0, (20.0, 20.0)),
+			("Left JS".to_string(), 3, (10.0, 10.0)),
+			("Hidden".to_string(), usize::MAX, (10.0, 10.0)),
+		] );
+		// left js in row 0 at its col, mfd after it; column 2 and row 1 take no room
+		assert_eq!( corners( &arranged ), vec![
+			("Throttle", 0.0, 0.0),
+			("Right JS", 110.0, 0.0),
+			("Pedals", 25.0, 210.0),
+			("MFD", 290.0, 0.0),
+			("Left JS", 270.0, 0.0),
+		] );
+	}
+
+	#[test]
+	fn anywhere() {
+		let layout = layout( &[
+			("stick", "row=0, column=0"),
+			("overlay", "x=0, y=0, scale=0.5, anchor=centre, z=-1, rotation=90"),
+			("mfd", "x=10, y=300, z=1"),
+		] );
+		let arranged = layout.arrange( &[
+			("stick".to_string(), 0, (100.0, 100.0)),
+			("mfd".to_string(), 0, (40.0, 40.0)),
+			("overlay".to_string(), 0, (100.0, 60.0)),
+			// far past the grid: in its last column
+			("pedals".to_string(), 1000, (10.0, 10.0)),
+		] );
+		// lowest z first; the overlay, centred on the corner, moves everything down and right
+		assert_eq!( corners( &arranged ), vec![
+			("overlay", 0.0, 0.0),
+			("stick", 25.0, 15.0),
+			("pedals", 25.0 + 100.0 + DEFAULT_GAP, 15.0),
+			("mfd", 35.0, 315.0),
+		] );
+		assert_eq!( (arranged[ 0 ].scale, arranged[ 0 ].rotation), (0.5, 90.0) );
+	}
+
+	#[test]
+	fn turned() {
+		let close = |a: (f32, f32), b: (f32, f32)| (a.0 - b.0).abs() < 0.001 && (a.1 - b.1).abs() < 0.001;
+		assert!( close( turned_size( (100.0, 20.0), 0.0 ), (100.0, 20.0) ) );
+		assert!( close( turned_size( (100.0, 20.0), 90.0 ), (20.0, 100.0) ) );
+		assert!( close( turned_size( (100.0, 20.0), -180.0 ), (100.0, 20.0) ) );
+		let diagonal = 120.0 * std::f32::consts::FRAC_1_SQRT_2;
+		assert!( close( turned_size( (100.0, 20.0), 45.0 ), (diagonal, diagonal) ) );
+	}
+}
+
+/* ******************************************************************************* *
+ *		*** End ***
+ * ******************************************************************************* */
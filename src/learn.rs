@@ -0,0 +1,285 @@
+/* ******************************************************************************* */
+/* Learn mode: finds which report bytes and bits a control uses */
+/* ******************************************************************************* */
+/*
+ * Rather than reading log_device output by eye:
+ *	hold still for a moment		bytes that change anyway are noise (axis jitter)
+ *	move one control			the bytes and bits it changes are shown
+ * and the .ini file line for that control is proposed, e.g. "x = 3, 4" with
+ * "x_encoding = bits=10", or "buttons = 5".
+ */
+use crate::device::{DeviceInfo, DeviceManager, Message, AXES, };
+use crate::ini_file;
+use std::time::{Duration, Instant, };
+
+/* ******************************************************************************* */
+/* Constants */
+
+/// controls a proposal can be made for: the axes, then buttons
+pub const TARGETS: [&str; AXES.len() + 1] = {
+	let mut targets = [ "buttons"; AXES.len() + 1 ];
+	let mut n = 0;
+	while n < AXES.len() {
+		targets[ n ] = AXES[ n ];
+		n += 1;
+	}
+	targets
+};
+// how long to hold still, while noise is measured
+const STILL_TIME: Duration = Duration::from_millis( 1000 );
+
+/* ******************************************************************************* */
+/* Structures */
+/* ******************************************************************************* */
+
+/// What has been seen of one byte of the report
+#[derive(Clone, Debug, Default)]
+pub struct ByteStats {
+	pub baseline: u8,	// value while holding still
+	pub min: u8,
+	pub max: u8,
+	pub bits: u8,		// bits that have differed from the baseline
+	noise_bits: u8,		// bits that changed while holding still
+	noise_range: u8,
+}
+
+impl ByteStats {
+	fn new( value: u8 ) -> ByteStats {
+		ByteStats { baseline: value, min: value, max: value, ..ByteStats::default() }
+	}
+
+	/// changed by more than it does when nothing is touched
+	pub fn changed( &self ) -> bool {
+		self.bits & !self.noise_bits != 0 || self.max - self.min > self.noise_range.saturating_add( 2 )
+	}
+}
+
+/* ******************************************************************************* */
+/// Watches one device, while the user moves one control
+pub struct Learner {
+	pub device: DeviceInfo,
+	pub target: &'static str,
+	pub bytes: Vec<ByteStats>,
+	started: Instant,
+}
+
+impl Learner {
+	pub fn new( device: DeviceInfo ) -> Learner {
+		Learner {
+			device,
+			target: TARGETS[ 0 ],
+			bytes: Vec::new(),
+			started: Instant::now(),
+		}
+	}
+
+	/// start again, e.g. for another control
+	pub fn restart( &mut self ) {
+		self.bytes = Vec::new();
+		self.started = Instant::now();
+	}
+
+	pub fn holding_still( &self ) -> bool {
+		self.started.elapsed() < STILL_TIME
+	}
+
+	/// look at the device's latest report; call every frame
+	pub fn sample( &mut self, devices: &DeviceManager ) {
+		let report = match devices.device( &self.device.key ) {
+			Some( dr ) => {	dr.report.clone()	}
+			None => {	return;	}
+		};
+		if report.is_empty() { return; }
+		if report.len() != self.bytes.len() {
+			if !self.bytes.is_empty() && !self.holding_still() {
+				return;		// another kind of report, e.g. a different report id
+			}
+			self.bytes = report.iter().map( |b| ByteStats::new( *b ) ).collect();
+		}
+		let still = self.holding_still();
+		for (stats, b) in self.bytes.iter_mut().zip( report.iter() ) {
+			if still {
+				stats.noise_bits |= b ^ stats.baseline;
+				stats.min = stats.min.min( *b );
+				stats.max = stats.max.max( *b );
+				stats.noise_range = stats.max - stats.min;
+			} else {
+				stats.bits |= b ^ stats.baseline;
+				stats.min = stats.min.min( *b );
+				stats.max = stats.max.max( *b );
+			}
+		}
+	}
+
+	/// offsets of the bytes the control changed
+	pub fn changed( &self ) -> Vec<usize> {
+		if self.holding_still() { return Vec::new(); }
+		self.bytes.iter().enumerate()
+			.filter( |(_, stats)| stats.changed() )
+			.map( |(n, _)| n )
+			.collect()
+	}
+
+	/// the .ini file lines for the target control: (key, value), keeping what is
+	/// already in the .ini file the devices were loaded from
+	pub fn proposal( &self, devices: &DeviceManager ) -> Vec<(String, String)> {
+		let changed = self.changed();
+		if changed.is_empty() { return Vec::new(); }
+
+		if self.target == "buttons" {
+			let mut bytes: Vec<usize> = self.existing( devices, "buttons" )
+				.map( |old| old.split( ',' ).filter_map( |b| b.trim().parse::<usize>().ok() ).collect() )
+				.unwrap_or_default();
+			for n in changed.iter().filter( |n| self.bytes[ **n ].bits & !self.bytes[ **n ].noise_bits != 0 ) {
+				if !bytes.contains( n ) {
+					bytes.push( *n );
+				}
+			}
+			let bytes: Vec<String> = bytes.iter().map( |b| b.to_string() ).collect();
+			return vec![ ("buttons".to_string(), bytes.join( ", " )) ];
+		}
+
+		// HID data is little endian: the low byte comes first, the high byte changes less
+		let (offsets, bits) = match changed.iter().find( |n| changed.contains( &(**n + 1) ) ) {
+			Some( low ) => {
+				let high = self.bytes[ low + 1 ].bits | self.bytes[ low + 1 ].max;
+				let high_bits = 8 - high.leading_zeros() as usize;
+				(format!("{}, {}", low, low + 1), (8 + high_bits.div_ceil( 2 ) * 2).clamp( 10, 16 ))
+			}
+			None => {
+				let Some( n ) = changed.iter().max_by_key( |n| self.bytes[ **n ].max - self.bytes[ **n ].min ) else {
+					return Vec::new();
+				};
+				(format!("{}, 0", n), 8)
+			}
+		};
+
+		// keep the label, invert and calibrate fields of an existing line
+		let mut value = offsets;
+		if let Some( old ) = self.existing( devices, self.target ) {
+			for field in old.split( ',' ).skip( 2 ) {
+				value = format!("{},{}", value, field);
+			}
+		}
+		vec![
+			(self.target.to_string(), value),
+			(format!("{}_encoding", self.target), format!("bits={}", bits)),
+		]
+	}
+
+	/// write the proposal to the .ini file the devices were loaded from, and use it straight away
+	pub fn save( &self, devices: &mut DeviceManager ) -> Vec<Message> {
+		let mut ret: Vec<Message> = Vec::new();
+		let Some( path ) = devices.config_file().map( String::from ) else {
+			return vec![ Message::Err( "The devices were not loaded from a file, nothing saved".to_string() ) ];
+		};
+		for (key, value) in self.proposal( devices ) {
+			if let Err( err ) = ini_file::set_value( &path, &self.device.section, &key, &value ) {
+				ret.push( Message::Err( err ) );
+				continue;
+			}
+			ret.append( &mut devices.set_config_value( &self.device.key, &key, &value ) );
+		}
+		ret
+	}
+
+	fn existing( &self, devices: &DeviceManager, key: &str ) -> Option<String> {
+		let path = devices.config_file()?;
+		ini_file::get_value( path, &self.device.section, key ).ok().flatten()
+	}
+}
+
+/* ******************************************************************************* */
+/* Tests */
+/* ******************************************************************************* */
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::backend::mock::{MockBackend, MockDevice, MockScript, };
+	use crate::hotplug::NoSource;
+	use crate::test_dir::TestDir;
+
+	const INI: &str = "[Stick]
+	vid = 3344
+	pid = 0001
+	auto_map = false
+	x = 7, 8, invert
+	buttons = 5
+";
+
+	/// devices loaded from their own copy of INI, kept while the TestDir is, and a learner watching the stick
+	fn learner( name: &str ) -> (DeviceManager, MockScript, Learner, TestDir, String) {
+		let dir = TestDir::new( name );
+		let path = dir.write( "joystick_monitor.ini", INI );
+		let mut backend = MockBackend::new();
+		let script = backend.add_device( MockDevice::new( 0x3344, 0x0001, "Stick" ) );
+		let mut devices = DeviceManager::new();
+		devices.set_backend( Box::new( backend ) );
+		devices.set_hotplug( Box::new( NoSource ) );
+		devices.load_file( &path );
+		let learner = Learner::new( devices.devices()[ 0 ].clone() );
+		(devices, script, learner, dir, path)
+	}
+
+	fn feed( devices: &mut DeviceManager, script: &MockScript, learner: &mut Learner, reports: &[[u8; 6]] ) {
+		for report in reports {
+			script.push_report( report );
+			script.settle();
+			devices.poll();
+			learner.sample( devices );
+		}
+	}
+
+	/// as if the user had held still for long enough
+	fn stop_holding_still( learner: &mut Learner ) {
+		learner.started = Instant::now().checked_sub( STILL_TIME ).unwrap();
+	}
+
+	#[test]
+	fn two_byte_axis() {
+		let (mut devices, script, mut learner, _dir, _) = learner( "learn_axis" );
+		// byte 3 jitters by a bit, with nothing touched
+		feed( &mut devices, &script, &mut learner, &[ [ 1, 0x00, 0x02, 0x10, 0, 0 ], [ 1, 0x00, 0x02, 0x11, 0, 0 ] ] );
+		assert!( learner.changed().is_empty() );
+		stop_holding_still( &mut learner );
+		feed( &mut devices, &script, &mut learner, &[ [ 1, 0xFF, 0x03, 0x11, 0, 0 ], [ 1, 0x00, 0x00, 0x10, 0, 0 ] ] );
+		let proposal = learner.proposal( &devices );
+		assert_eq!( learner.changed(), vec![ 1, 2 ] );
+		assert_eq!( proposal, vec![
+			("x".to_string(), "1, 2, invert".to_string()),
+			("x_encoding".to_string(), "bits=10".to_string()),
+		] );
+	}
+
+	#[test]
+	fn buttons_are_added() {
+		let (mut devices, script, mut learner, _dir, _) = learner( "learn_buttons" );
+		learner.target = "buttons";
+		feed( &mut devices, &script, &mut learner, &[ [ 1, 0, 0, 0, 0, 0 ] ] );
+		stop_holding_still( &mut learner );
+		feed( &mut devices, &script, &mut learner, &[ [ 1, 0, 0, 0, 0b100, 0b1 ], [ 1, 0, 0, 0, 0, 0 ] ] );
+		let proposal = learner.proposal( &devices );
+		assert_eq!( proposal, vec![ ("buttons".to_string(), "5, 4".to_string()) ] );
+	}
+
+	#[test]
+	fn save() {
+		let (mut devices, script, mut learner, _dir, path) = learner( "learn_save" );
+		learner.target = "y";
+		feed( &mut devices, &script, &mut learner, &[ [ 1, 0, 0, 0x80, 0, 0 ] ] );
+		stop_holding_still( &mut learner );
+		feed( &mut devices, &script, &mut learner, &[ [ 1, 0, 0, 0x00, 0, 0 ], [ 1, 0, 0, 0xFF, 0, 0 ] ] );
+		let errors: Vec<Message> = learner.save( &mut devices ).into_iter().filter( |m| matches!( m, Message::Err( _ ) ) ).collect();
+		let saved = ini_file::get_value( &path, "Stick", "y" );
+		assert!( errors.is_empty() );
+		assert_eq!( saved, Ok( Some( "3, 0".to_string() ) ) );
+
+		// nowhere to save to
+		let mut devices = DeviceManager::new();
+		assert!( matches!( learner.save( &mut devices ).as_slice(), [ Message::Err( _ ) ] ) );
+	}
+}
+
+/* ******************************************************************************* *
+ *		*** End ***
+ * ******************************************************************************* */
@@ -0,0 +1,71 @@
+/* ******************************************************************************* */
+/* Joystick Monitor, the library: finds, reads and decodes HID game controllers */
+/* ******************************************************************************* */
+//! Reads joysticks, throttles and rudder pedals as set up in a joystick_monitor.ini
+//! file (see config/ConfigReadMe.md), without any of the GUI.
+//! The joystick_monitor program is one user of it; build with
+//! `default-features = false` to leave eframe and egui out.
+//!
+//! ```no_run
+//! use joystick_monitor::{DeviceManager, Message, CONFIG_FILE, };
+//!
+//! let mut devices = DeviceManager::new();
+//! for mssg in devices.load_file( CONFIG_FILE ) {
+//!     if let Message::Err( err ) = mssg { eprintln!( "{}", err ); }
+//! }
+//! loop {
+//!     devices.poll();     // read whatever each device has sent
+//!     for (key, report) in devices.reports() {
+//!         println!( "{} {}: x {:.2} y {:.2}", key, report.name, report.x_f32(), report.y_f32() );
+//!     }
+//!     std::thread::sleep( std::time::Duration::from_millis( 16 ) );
+//! }
+//! ```
+//!
+//! The modules, most used first:
+//! - device: DeviceManager, the .ini file's devices (Joystick), and their reports (DeviceReport)
+//! - backend: how devices are found and opened (hidapi, hidraw, evdev, or a mock for tests)
+//! - display: how axes are shown, as bars, dials or ministicks (rx_widget, ...)
+//! - descriptor: HID report descriptors, for axes and buttons not given in the .ini file
+//! - capture: recording reports to a file, and replaying them
+//! - layout: where each device is shown, from the [Layout] section
+//! - assets: the images drawn, by name (img/images.ini), and each device's choice of them
+//! - skins: a device's look, as a directory of images drawn in layers, changed by its axes, buttons and hats
+//! - hotplug: when to look again for devices that are not there
+//! - buttons: each button's bit, name and group (button_1, button_2, ...)
+//! - hats: hat (POV) switches, sent as an angle or as buttons (hat_1, hat_2, ...)
+//! - routing: axes taken from other devices, and mixed (x_route, echo_x, ...)
+//! - ini_file: changing single values in the .ini file, keeping comments
+//! - calibrate, learn: finding an axis' range, or a control's bytes, by watching a device
+
+pub mod assets;
+pub mod backend;
+pub mod buttons;
+pub mod calibrate;
+pub mod capture;
+pub mod descriptor;
+pub mod device;
+pub mod display;
+#[cfg(target_os = "linux")]
+pub mod evdev;
+pub mod hats;
+pub mod hotplug;
+pub mod ini_file;
+pub mod layout;
+pub mod learn;
+#[cfg(not(feature = "single-threaded"))]
+pub mod reader;
+pub mod routing;
+pub mod skins;
+#[cfg(test)]
+mod test_dir;
+
+pub use backend::DeviceKey;
+pub use device::{
+	Connection, DeviceInfo, DeviceManager, DeviceReport, Joystick, JoystickAxis, Message,
+	CONFIG_FILE, JS_MAX,
+};
+
+/* ******************************************************************************* *
+ *		*** End ***
+ * ******************************************************************************* */
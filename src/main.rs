@@ -2,34 +2,38 @@
 // hide console window on Windows in release
 #[cfg(feature = "logging")]
 use chrono::Utc;
-use crate::device::Message;
+use joystick_monitor::{assets, buttons, calibrate, device, display, hats, layout, learn, skins, };
+use joystick_monitor::device::Message;
 use eframe::egui;
 use egui::{Ui, Vec2, Color32, Sense, };
 use egui_extras::image::RetainedImage;
 use std::cmp;
+use std::collections::HashMap;
 use std::time::{Duration, Instant, };
-use walkdir::WalkDir;
 
 #[cfg(feature = "logging")]
 use log::info;
 use ::function_name::named;
 
 mod about;
-mod device;
 
 const DARK_FILL: egui::Color32 = egui::Color32::from_rgb(0x11, 0x00, 0x11); //:DARK_GRAY;  // :DARK_BLUE;  // :BLACK;
 const FILL_COLOUR: egui::Color32 = egui::Color32::GREEN;
 const FRAME_RATE: u64 = 60; // 30; //120; // 60; //30; // 60;              // frames per second
+const HAT_SIZE: f32 = 48.0;
 const IMG_SIZE: f32 = 240.0;
 const MAX_ERR_LIST: usize = 7; // 100;
 const PROGRAM_TITLE: &str = "Joystick Monitor";
 const TICK: Duration = Duration::from_millis(1000 / FRAME_RATE - 3);
+const WIDGET_SIZE: f32 = 64.0;              // bars' length, dials' and ministicks' size
 
 #[derive(Copy, Clone, PartialEq)]
 enum State {
     About,
+    Calibrating,
     Initialising,
     IsNew,
+    Learning,
     PrepScreen,
     Running,
 }
@@ -37,12 +41,21 @@ enum State {
 struct MyApp {
     state: State,
     // old_state: State,
+    devices: device::DeviceManager,
     err_list: Vec<String>,
-    images: Vec<RetainedImage>,
-    img_sizes: Vec<egui::Vec2>,
+    manifest: assets::Manifest,
+    images: HashMap<String, RetainedImage>,     // by name in the manifest, a skin's as skin/name
+    skins: HashMap<String, skins::Skin>,        // by the skin key's value
+    asset_errors: Vec<String>,      // images that could not be loaded or found, shown until restart
+    save_errors: Vec<String>,       // from the last save to the .ini file, shown until dismissed
     now: Instant,
     show_buttons: bool,
+    show_status: bool,
+    show_raw: bool,
     best_width: f32,
+    drawn: HashMap<String, Vec2>,     // each device's size when last drawn, at a scale of 1
+    wizard: Option<calibrate::Wizard>,
+    learner: Option<learn::Learner>,
     // tint: [u8; 3],
     // recover: bool,
 }
@@ -91,37 +104,233 @@ impl MyApp {
         });
     }
 
+    #[named]
+    fn calibration_screen(&mut self, ui: &mut Ui) {
+        self.check_devices();
+        let Some( wizard ) = &mut self.wizard else {
+            self.state = State::Running;
+            return;
+        };
+        wizard.sample(&self.devices);
+
+        let mut finished = false;
+        ui.vertical(|ui| {
+            ui.heading(format!("Calibrate {}", wizard.device.name));
+            ui.label(wizard.instruction());
+            ui.add_space(8.0);
+
+            egui::Grid::new("calibration_axes").striped(true).show(ui, |ui| {
+                for txt in ["Axis", "Now", "Min", "Centre", "Max"] {
+                    ui.label(txt);
+                }
+                ui.end_row();
+                for axis in &wizard.axes {
+                    ui.label(axis.axis);
+                    ui.label(axis.value.to_string());
+                    ui.label(axis.min.to_string());
+                    ui.label(axis.centre().map( |c| c.to_string() ).unwrap_or_default());
+                    ui.label(axis.max.to_string());
+                    ui.end_row();
+                }
+            });
+
+            if !wizard.buttons.is_empty() {
+                ui.add_space(8.0);
+                for (n, (byte, bit)) in wizard.buttons.iter().enumerate() {
+                    ui.label(format!("Button {}: byte {}, bit {}", n + 1, byte, bit));
+                }
+            }
+
+            if wizard.step == calibrate::Step::Review {
+                ui.add_space(8.0);
+                let results = wizard.results(&self.devices);
+                if results.is_empty() {
+                    ui.label("Nothing moved, nothing to save");
+                }
+                for (key, value) in results {
+                    ui.monospace(format!("{} = {}", key, value));
+                }
+            }
+
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                if wizard.step == calibrate::Step::Review {
+                    if ui.button("Save").clicked() {
+                        // a save that works clears the errors of the last one
+                        self.save_errors = Vec::new();
+                        for mssg in wizard.save(&mut self.devices) {
+                            if let Message::Err( mssg ) = mssg {
+                                self.save_errors.push(show_error(
+                                        module_path!(),
+                                        function_name!(),
+                                        mssg ) );
+                            }
+                        }
+                        finished = true;
+                    }
+                } else if ui.button("Next").clicked() {
+                    wizard.next();
+                }
+                if ui.button("Cancel").clicked() {
+                    finished = true;
+                }
+            });
+        });
+
+        if finished {
+            self.wizard = None;
+            self.state = State::Running;
+        }
+    }
+
     #[named]
     fn check_devices(&mut self) {
-		self.err_list = Vec::new();
-        for mssg in device::check_devices() {
+		self.err_list = self.save_errors.iter().chain(self.asset_errors.iter()).cloned().collect();
+        for mssg in self.devices.poll() {
             match mssg {
                 Message::Err( mssg ) => {
                     self.err_list.insert( 0, show_error( // .push( show_error(
                         module_path!(),
                         function_name!(),
-                        format!("{}", mssg) ) );
+                        mssg ) );
                 }
                 Message::None => {}
             }
         }       
     }
 
-    fn find_image(&mut self, dir_entry: walkdir::DirEntry) {
-        let path = dir_entry.path();
-        match path.extension(){
-            Some( ext ) => {
-                if ext == "svg" {
-                    match path.to_str() {
-                        Some( img_path ) => {
-                            self.load_image( img_path );
+    #[named]
+    fn learn_screen(&mut self, ui: &mut Ui) {
+        self.check_devices();
+        let Some( learner ) = &mut self.learner else {
+            self.state = State::Running;
+            return;
+        };
+        learner.sample(&self.devices);
+
+        let mut finished = false;
+        ui.vertical(|ui| {
+            ui.heading(format!("Learn {}", learner.device.name));
+            ui.horizontal(|ui| {
+                ui.label("Control:");
+                let before = learner.target;
+                egui::ComboBox::from_id_source("learn_target")
+                    .selected_text(learner.target)
+                    .show_ui(ui, |ui| {
+                        for target in learn::TARGETS {
+                            ui.selectable_value(&mut learner.target, target, target);
+                        }
+                    });
+                if learner.target != before {
+                    learner.restart();
+                }
+            });
+            if learner.holding_still() {
+                ui.label("Hold still, touch nothing ...");
+            } else {
+                ui.label(format!("Now move {} (only), through its full range", learner.target));
+            }
+            ui.add_space(8.0);
+
+            egui::Grid::new("learn_bytes").striped(true).show(ui, |ui| {
+                for txt in ["Byte", "Still", "Min", "Max", "Bits changed"] {
+                    ui.label(txt);
+                }
+                ui.end_row();
+                for n in learner.changed() {
+                    let stats = &learner.bytes[ n ];
+                    ui.label(n.to_string());
+                    ui.label(stats.baseline.to_string());
+                    ui.label(stats.min.to_string());
+                    ui.label(stats.max.to_string());
+                    // bit 7 on the left, as the byte is written in binary
+                    let bits: String = (0..8).rev()
+                        .map( |bit| if stats.bits & (1 << bit) != 0 { '1' } else { '.' } )
+                        .collect();
+                    ui.label(egui::RichText::new(bits).monospace().color(FILL_COLOUR));
+                    ui.end_row();
+                }
+            });
+
+            ui.add_space(8.0);
+            let proposal = learner.proposal(&self.devices);
+            for (key, value) in &proposal {
+                ui.monospace(format!("{} = {}", key, value));
+            }
+
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                if !proposal.is_empty() && ui.button("Save").clicked() {
+                    self.save_errors = Vec::new();
+                    for mssg in learner.save(&mut self.devices) {
+                        if let Message::Err( mssg ) = mssg {
+                            self.save_errors.push(show_error(
+                                    module_path!(),
+                                    function_name!(),
+                                    mssg ) );
                         }
-                        None => {}
                     }
+                    learner.restart();
+                }
+                if ui.button("Again").clicked() {
+                    learner.restart();
+                }
+                if ui.button("Close").clicked() {
+                    finished = true;
+                }
+            });
+        });
+        ui.add_space(8.0);
+        self.error_list(ui);
+
+        if finished {
+            self.learner = None;
+            self.state = State::Running;
+        }
+    }
+
+    /// the errors, newest first, and a button to dismiss those from saving
+    fn error_list(&mut self, ui: &mut Ui) {
+        ui.vertical(|ui| {
+            for str in &self.err_list {
+                ui.add(egui::Label::new(str));
+            }
+            if !self.save_errors.is_empty() && ui.button("Dismiss").clicked() {
+                self.save_errors.clear();
+            }
+            for _n in self.err_list.len()..MAX_ERR_LIST {
+                ui.add(egui::Label::new(""));
+            }
+        });
+    }
+
+    /// every image in the manifest, by name, and an error for each name a device uses
+    /// that is not there
+    #[named]
+    fn load_images(&mut self) {
+        let (manifest, errors) = assets::Manifest::load_file(assets::MANIFEST_FILE);
+        for err in errors {
+            self.asset_errors.push(show_error(module_path!(), function_name!(), err));
+        }
+        for (name, path) in manifest.files() {
+            if let Some(img) = self.load_image(path) {
+                self.images.insert(name.to_string(), img);
+            }
+        }
+        let names: Vec<String> = self.devices.joysticks().iter().filter_map(|js| js.skin()).map(String::from).collect();
+        for name in names {
+            self.load_skin(&name);
+        }
+        for js in self.devices.joysticks() {
+            let skin = js.skin().and_then(|name| self.skins.get(name));
+            for name in js.images().missing(&manifest) {
+                if !skin.is_some_and(|skin| skin.images.contains(&name)) {
+                    self.asset_errors.push(show_error(module_path!(), function_name!(),
+                        format!("[{}] no image '{}' in {}", js.section(), name, assets::MANIFEST_FILE)));
                 }
             }
-            None => {}
         }
+        self.manifest = manifest;
     }
     
     #[named]
@@ -133,33 +342,12 @@ impl MyApp {
         while self.err_list.len() > MAX_ERR_LIST {
             self.err_list.remove( MAX_ERR_LIST );
         }
-        match self.state {
-            State::PrepScreen => {
-                self.state = State::Initialising;
-                self.set_api();
-                
-                for entry in
-                    WalkDir::new("img").sort_by_key(
-                        |a| a.file_name().to_owned()) {
-                            
-                    match entry {
-                        Ok( dir_entry ) => {
-                            if dir_entry.file_type().is_dir() {
-                                continue;
-                            }
-                            self.find_image(dir_entry);
-                        }
-                        Err( err ) => {
-                            self.err_list.insert(0, show_error( //   .push( show_error(
-                                                    module_path!(),
-                                                    function_name!(),
-                                                    format!("{}", err) ) );
-                        }
-                    }
-                }
-                self.state = State::Running;
-            }
-            _ => {}
+        if self.state == State::PrepScreen {
+            self.state = State::Initialising;
+            self.set_api();
+            
+            self.load_images();
+            self.state = State::Running;
         }
         
 		#[cfg(feature = "logging")] {
@@ -167,107 +355,123 @@ impl MyApp {
         }
     }
 
-    fn joystick_screen(&mut self, ui: &mut Ui, ctx: &egui::Context, menu_height: f32) -> f32 {
+    /// every device, where the layout puts it, and the errors underneath
+    /// returns the size it all takes, for the window to fit
+    fn joystick_screen(&mut self, ui: &mut Ui, ctx: &egui::Context) -> Vec2 {
         self.check_devices();
 
-        let outer = ui.horizontal_centered(|ui| {
-            let dev_repts: &mut Vec<device::DeviceReport> = &mut Vec::new();
-            unsafe {
-                for (_, dev_report) in device::DEVICES_REPORTS.iter() {
-                    if dev_report.col < std::usize::MAX {
-                        dev_repts.push( dev_report.clone() );
+        let dev_repts: &mut Vec<device::DeviceReport> = &mut Vec::new();
+        for dev_report in self.devices.reports().values() {
+            if self.show_raw {
+                dev_repts.push( dev_report.as_raw() );
+            } else {
+                dev_repts.push( dev_report.clone() );
+            }
+        }
+        dev_repts.sort_unstable();
+
+        // place each device by the size it took last time (at a scale of 1), or its image's
+        let items: Vec<(String, usize, (f32, f32))> = dev_repts.iter().map(|dev_report| {
+            let size = self.drawn.get(&dev_report.section).copied()
+                .unwrap_or_else(|| self.body_size(ctx, dev_report));
+            (dev_report.section.clone(), dev_report.col, (size.x, size.y))
+        }).collect();
+        let arranged = self.devices.layout().arrange(&items);
+
+        // a turned device reaches past its own box, to that of the box turned about its centre
+        let mut bounds = Vec2::ZERO;
+        for place in &arranged {
+            if let Some(size) = self.drawn.get(&place.section) {
+                let size = *size * place.scale;
+                let turned = Vec2::from(layout::turned_size((size.x, size.y), place.rotation));
+                bounds = bounds.max(Vec2::new(place.x, place.y) + (size + turned) * 0.5);
+            }
+        }
+        let (area, response) = ui.allocate_exact_size(bounds, Sense::click());
+
+        for place in &arranged {
+            let Some(dev_report) = dev_repts.iter().find(|d| d.section == place.section) else {
+                continue;
+            };
+            let scale = place.scale;
+            let turn = place.rotation.to_radians();
+            let img_size = self.body_size(ctx, dev_report) * scale;
+            let cell = egui::Rect::from_min_size(area.min + Vec2::new(place.x, place.y), Vec2::splat(f32::INFINITY));
+            let mut child = ui.child_ui(cell, egui::Layout::top_down(egui::Align::Min));
+            child.vertical(|ui| {
+                match (&dev_report.display, self.skin(dev_report)) {
+                    (_, Some(skin)) => {
+                        self.skin_layers(ui, ctx, dev_report, skin, scale, turn);
+                    }
+                    (display::Display::Stick, None) => {
+                        // the stick's image, moved by x and y, turned by z, and its centre over it
+                        let stick = skins::Skin::stick(
+                            &dev_report.images.body(dev_report.col, &self.manifest),
+                            &dev_report.images.centre,
+                            (img_size.x / scale, img_size.y / scale),
+                            (dev_report.x_calibrate as f32, dev_report.y_calibrate as f32, dev_report.z_calibrate as f32));
+                        self.skin_layers(ui, ctx, dev_report, &stick, scale, turn);
+                    }
+                    (display::Display::Throttle(throttle), None) => {
+                        throttle_widget(ui, dev_report, throttle, img_size, turn);
+                    }
+                    (display::Display::Pedals(pedals), None) => {
+                        pedals_widget(ui, dev_report, pedals, img_size, turn);
                     }
                 }
-            }
-            
-            dev_repts.sort_unstable();
-            for dev_report in dev_repts {
-                if dev_report.col < std::usize::MAX {
-                    ui.vertical(|ui| {
-                        let texture = self.images[ dev_report.col ].texture_id(ctx);
-                        
-                        //let tint: egui::Color32;
-                        // tint = Color32::LIGHT_GREEN ; // :LIGHT_GRAY; // :GRAY;
-                        // tint = Color32::WHITE;
-                        
-                        let img = egui::widgets::Image::new( 
-                                    texture, 
-                                    self.img_sizes[dev_report.col ])
-                                .rotate((dev_report.z_f32()-0.5)*2.0 + dev_report.z_calibrate as f32,
-                                Vec2::splat(0.5))
-                                 // .tint(Color32::WHITE);
-                                 // .tint(tint)
-                                 ;
-                        img.paint_at(ui, egui::Rect::from_center_size(
-                                        egui::Pos2::new( 
-                                            (dev_report.x_f32()+0.55)*self.img_sizes[dev_report.col ].x * 0.5 +
-                                            dev_report.col as f32 * self.img_sizes[dev_report.col ].x +
-                                            dev_report.x_calibrate as f32, 
-                                            (dev_report.y_f32()+0.55)*self.img_sizes[dev_report.col ].y * 0.5 +
-                                            dev_report.y_calibrate as f32 + menu_height,),
-                                        egui::Vec2::new( 
-                                            self.img_sizes[dev_report.col ].x, 
-                                            self.img_sizes[dev_report.col ].y)));
-                        
-                        
-                        /*
-                        dbg!( dev_report.x );
-                        if dev_report.x < 2 || dev_report.x > 59998 {
-                            dbg!( dev_report.x_f32() );
-                            dbg!( (dev_report.x_f32()+0.55)*self.img_sizes[dev_report.col ].x * 0.5 +
-                            dev_report.col as f32 * self.img_sizes[dev_report.col ].x +
-                            dev_report.x_calibrate as f32 );
+                
+                if !dev_report.hats.is_empty() {
+                    ui.horizontal(|ui| {
+                        for hat in &dev_report.hats {
+                            hat_compass(ui, hat, scale);
                         }
-                        */
-                        
-                        let texture = self.images[ 2 ].texture_id(ctx);     // hard coded, wrong!!!!
-                        let img = egui::widgets::Image::new( 
-                                    texture, 
-                                    self.img_sizes[dev_report.col ]);
-                        ui.add( img);
-                        
-                        if self.show_buttons {
-                            let mut first_row: bool = true;
-                            for btn_row in &dev_report.buttons {
-                                ui.horizontal(|ui| { 
-                                    let mut val: u8 = 0b00000001;
-                                    loop {
-                                        let mut btn_texture = self.images[self.images.len()-1].texture_id(ctx);
-                                        if 0 < (btn_row & val) {
-                                            if first_row & (val == 0b00000001) {
-                                                btn_texture = self.images[self.images.len()-3].texture_id(ctx);                                                
-                                            } else {
-                                                btn_texture = self.images[self.images.len()-2].texture_id(ctx);                                                
-                                            }
-                                        }
-                                        
-                                        ui.image(
-                                            btn_texture,
-                                            self.img_sizes[self.images.len()-1 ]);
-                                            
-                                        if val > 0b01000001 {
-                                            break;
-                                        }
-                                        val *= 2;
-                                        first_row = false;
-                                    };
-                                });     
-                            }
+                    });
+                }
+                
+                if !dev_report.widgets.is_empty() {
+                    ui.horizontal(|ui| {
+                        for widget in &dev_report.widgets {
+                            axis_widget(ui, dev_report, widget, scale);
                         }
                     });
                 }
-            }
-        });
+                
+                if self.show_status {
+                    if dev_report.connection == device::Connection::Virtual {
+                        ui.label( format!("{}: {}", dev_report.name, dev_report.connection) );
+                    } else {
+                        ui.label( format!("{}: {} ({} reports)", dev_report.name, dev_report.connection, dev_report.drained) );
+                    }
+                }
+                
+                if self.show_buttons {
+                    // one row for each group, button 1 (usually the trigger) drawn differently
+                    let images = &dev_report.images;
+                    let size = self.device_image(ctx, dev_report, &images.button_off)
+                        .map_or(Vec2::splat(IMG_SIZE * 0.125), |(_, size)| size) * scale;
+                    ui.set_max_width(img_size.x.max(size.x));
+                    for group in buttons::groups( &dev_report.buttons ) {
+                        ui.horizontal_wrapped(|ui| {
+                            for btn in dev_report.buttons.iter().filter( |b| b.group == group ) {
+                                let name = match (btn.pressed, btn.number) {
+                                    (false, _) => &images.button_off,
+                                    (true, 1) => &images.button_primary,
+                                    (true, _) => &images.button_on,
+                                };
+                                let response = match self.device_image(ctx, dev_report, name) {
+                                    Some((texture, _)) => ui.image(texture, size),
+                                    None => ui.allocate_response(size, Sense::hover()),
+                                };
+                                response.on_hover_text(btn.label());
+                            }
+                        });
+                    }
+                }
+            });
+            self.drawn.insert(place.section.clone(), child.min_rect().size() / scale);
+        }
         
-        ui.vertical(|ui| {
-            for str in &self.err_list {
-                ui.add(egui::Label::new(str));
-            }
-            for _n in self.err_list.len()..MAX_ERR_LIST {
-                ui.add(egui::Label::new(""));
-            }
-        });
-        let response = outer.response.interact(Sense::click());
+        self.error_list(ui);
         response.context_menu(|ui| {
             let mut btn_txt = "Show Buttons";
             if self.show_buttons { btn_txt = "Hide Buttons";}
@@ -275,60 +479,162 @@ impl MyApp {
                 self.show_buttons = !self.show_buttons;
                 ui.close_menu();
             }
+            let mut status_txt = "Show Status";
+            if self.show_status { status_txt = "Hide Status";}
+            if ui.button(status_txt).clicked() {
+                self.show_status = !self.show_status;
+                ui.close_menu();
+            }
+            let mut raw_txt = "Show Raw Axes";
+            if self.show_raw { raw_txt = "Show Calibrated Axes";}
+            if ui.button(raw_txt).clicked() {
+                self.show_raw = !self.show_raw;
+                ui.close_menu();
+            }
+            ui.menu_button("Calibrate", |ui| {
+                for info in self.devices.devices() {
+                    if ui.button(&info.name).clicked() {
+                        self.wizard = Some( calibrate::Wizard::new( info ) );
+                        self.state = State::Calibrating;
+                        ui.close_menu();
+                    }
+                }
+            });
+            ui.menu_button("Learn", |ui| {
+                for info in self.devices.devices() {
+                    if ui.button(&info.name).clicked() {
+                        self.learner = Some( learn::Learner::new( info ) );
+                        self.state = State::Learning;
+                        ui.close_menu();
+                    }
+                }
+            });
             if ui.button("About").clicked() {
                 self.state = State::About;
                 ui.close_menu();
             }
         });
         
-        outer.response.rect.width()
+        Vec2::new(area.width(), ui.min_rect().height())
     }
     
+    /// a skin, and its images as skin/name, once for all the devices that use it
     #[named]
-    fn load_image(&mut self, img_path: &str ) {
-        match std::fs::read_to_string(img_path) {
-            Ok( svg_str ) => {
-                match RetainedImage::from_svg_str(
-                    img_path, &svg_str ) {
-                    Ok( img ) => {
-                        let size: egui::Vec2 = egui::Vec2{
-                            x: img.width() as f32,
-                            y: img.height() as f32,};
-                            
-                        self.images.push( img );
-                        self.img_sizes.push( size );
-                    }
-                    Err( err ) => {
-                        self.err_list.insert(0, show_error(         //) .push( show_error(
-                                module_path!(),
-                                function_name!(),
-                                format!("init, load image file '{}', error {}",
-                                        img_path, err) ) );
+    fn load_skin(&mut self, name: &str) {
+        if self.skins.contains_key(name) {
+            return;
+        }
+        match skins::Skin::load(name) {
+            Ok((skin, errors)) => {
+                for err in errors {
+                    self.asset_errors.push(show_error(module_path!(), function_name!(), err));
+                }
+                for (image, path) in skin.images.files() {
+                    if let Some(img) = self.load_image(path) {
+                        self.images.insert(format!("{}/{}", skin.name, image), img);
                     }
                 }
+                self.skins.insert(name.to_string(), skin);
             }
-            Err(_) => {
-                self.err_list.insert(0, show_error(
+            Err(err) => {
+                self.asset_errors.push(show_error(module_path!(), function_name!(), err));
+            }
+        }
+    }
+
+    /// the skin a device is drawn with, if it has one that could be read
+    fn skin(&self, dev_report: &device::DeviceReport) -> Option<&skins::Skin> {
+        dev_report.skin.as_ref().and_then(|name| self.skins.get(name))
+    }
+
+    /// one of a device's images by name: its skin's, else the manifest's
+    fn device_image(&self, ctx: &egui::Context, dev_report: &device::DeviceReport, name: &str) -> Option<(egui::TextureId, Vec2)> {
+        match self.skin(dev_report) {
+            Some(skin) if skin.images.contains(name) => self.image(ctx, &format!("{}/{}", skin.name, name)),
+            _ => self.image(ctx, name),
+        }
+    }
+
+    /// the device's picture, with its size
+    fn body(&self, ctx: &egui::Context, dev_report: &device::DeviceReport) -> Option<(egui::TextureId, Vec2)> {
+        self.device_image(ctx, dev_report, &dev_report.images.body(dev_report.col, &self.manifest))
+    }
+
+    /// the size of a device's picture: its skin's box, else its image's
+    fn body_size(&self, ctx: &egui::Context, dev_report: &device::DeviceReport) -> Vec2 {
+        match self.skin(dev_report) {
+            Some(skin) => Vec2::new(skin.size.0, skin.size.1),
+            None => self.body(ctx, dev_report).map_or(Vec2::splat(IMG_SIZE), |(_, size)| size),
+        }
+    }
+
+    /// a skin's layers, each moved, turned, sized and coloured as the device's axes, buttons
+    /// and hats say, then all turned clockwise by turn (radians) about the middle of the skin's box
+    fn skin_layers(&self, ui: &mut Ui, ctx: &egui::Context, dev_report: &device::DeviceReport, skin: &skins::Skin, scale: f32, turn: f32) {
+        let (rect, _) = ui.allocate_exact_size(Vec2::new(skin.size.0, skin.size.1) * scale, Sense::hover());
+        let painter = ui.painter();
+        for pose in skin.poses(dev_report) {
+            let image = match skin.images.contains(&pose.image) {
+                true => self.image(ctx, &format!("{}/{}", skin.name, pose.image)),
+                false => self.image(ctx, &pose.image),
+            };
+            let Some((texture, size)) = image else {
+                continue;       // its error is in the list
+            };
+            let size = size * scale;
+            let (ax, ay) = pose.anchor.point(size.x, size.y);
+            let min = rect.min + Vec2::new(pose.at.0, pose.at.1) * scale - Vec2::new(ax, ay);
+            // sized about the pivot, which stays where it is
+            let pivot = min + Vec2::new(pose.pivot.0 * size.x, pose.pivot.1 * size.y);
+            let sized = Vec2::new(size.x * pose.scale.0, size.y * pose.scale.1);
+            let layer = egui::Rect::from_min_size(
+                pivot - Vec2::new(pose.pivot.0 * sized.x, pose.pivot.1 * sized.y), sized);
+            let mut mesh = egui::Mesh::with_texture(texture);
+            mesh.add_rect_with_uv(layer,
+                egui::Rect::from_min_max(egui::Pos2::ZERO, egui::Pos2::new(1.0, 1.0)),
+                Color32::from_rgb(pose.tint.0, pose.tint.1, pose.tint.2).linear_multiply(pose.opacity));
+            mesh.rotate(egui::emath::Rot2::from_angle(pose.angle), pivot);
+            mesh.rotate(egui::emath::Rot2::from_angle(turn), rect.center());
+            painter.add(egui::Shape::mesh(mesh));
+        }
+    }
+
+    /// an image by name, with its size; None if the manifest has no such image
+    fn image(&self, ctx: &egui::Context, name: &str) -> Option<(egui::TextureId, Vec2)> {
+        self.images.get(name).map(|img| (img.texture_id(ctx), img.size_vec2()))
+    }
+
+    /// an .svg or .png file, as an image to draw
+    #[named]
+    fn load_image(&mut self, img_path: &str ) -> Option<RetainedImage> {
+        let loaded = match std::fs::read(img_path) {
+            Ok( bytes ) if img_path.to_lowercase().ends_with(".svg") => {
+                RetainedImage::from_svg_bytes(img_path, &bytes)
+            }
+            Ok( bytes ) => {    RetainedImage::from_image_bytes(img_path, &bytes)  }
+            Err( err ) => {     Err( format!("Should have been able to read {}: {}", img_path, err) )  }
+        };
+        match loaded {
+            Ok( img ) => {  Some( img ) }
+            Err( err ) => {
+                self.asset_errors.push( show_error(
                         module_path!(),
                         function_name!(),
-                        format!(
-                            "Should have been able to read {}",
-                            img_path) ) );
+                        format!("init, load image file '{}', error {}",
+                                img_path, err) ) );
+                None
             }
         }
     }
 
     #[named]
     fn set_api(&mut self) {
-        for mssg in device::load_devices( FRAME_RATE as i32 ) {
-            match mssg {
-                Message::Err( mssg ) => {
-                    self.err_list.insert(0, show_error(
-                            module_path!(),
-                            function_name!(),
-                            mssg ) );
-                }
-                _ => {}
+        for mssg in self.devices.load_file(device::CONFIG_FILE) {
+            if let Message::Err( mssg ) = mssg {
+                self.err_list.insert(0, show_error(
+                        module_path!(),
+                        function_name!(),
+                        mssg ) );
             }
         }
     }
@@ -338,12 +644,21 @@ impl Default for MyApp {
     fn default() -> Self {
         Self {
             state: State::IsNew,
+            devices: device::DeviceManager::new(),
             err_list: Vec::new(),
-            images: Vec::new(),
-            img_sizes: Vec::new(),
+            manifest: assets::Manifest::default(),
+            images: HashMap::new(),
+            skins: HashMap::new(),
+            asset_errors: Vec::new(),
+            save_errors: Vec::new(),
             now: Instant::now(),
             show_buttons: false,
+            show_status: true,
+            show_raw: false,
             best_width: 0.0,
+            drawn: HashMap::new(),
+            wizard: None,
+            learner: None,
             // tint: [255; 3],
             // recover: true,
         }
@@ -353,16 +668,15 @@ impl Default for MyApp {
 impl eframe::App for MyApp {
     //#[named]
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
-        let menu_height: f32 = 0.0; // f64;
         let mut win_height: f32 = 0.0; // 240.0;
         let mut win_width: f32 = 240.0;
+        let mut wanted: Option<Vec2> = None;       // the window's size, from the layout
         ctx.request_repaint_after(TICK);
 
-        let fill_colour: Color32;
-        match self.state {
-            State::Running => { fill_colour = FILL_COLOUR; }
-            _ => { fill_colour = DARK_FILL; }
-        }
+        let fill_colour: Color32 = match self.state {
+            State::Running => { FILL_COLOUR }
+            _ => { DARK_FILL }
+        };
 
         let cen_pan = egui::CentralPanel::default().show(ctx, |ui| {
             egui::Frame::none()
@@ -372,6 +686,8 @@ impl eframe::App for MyApp {
                     .stroke(egui::Stroke::NONE)
                     .show(ui, |ui| {
 
+                // the window's size, less the room the joystick screen is given
+                let border = ctx.screen_rect().size() - ui.available_size();
                 match self.state {
                     //State::About => {   self.about_screen( ui );    }
                     State::IsNew => {
@@ -382,12 +698,20 @@ impl eframe::App for MyApp {
                         self.about_screen( ui );
                         self.init();
                     }
-                    State::Running => { win_width = self.joystick_screen(ui, ctx, menu_height); }
+                    State::Running => { wanted = Some(self.joystick_screen(ui, ctx) + border); }
+                    State::Calibrating => { self.calibration_screen( ui ); }
+                    State::Learning => { self.learn_screen( ui ); }
                     _ => {  self.about_screen( ui );    }
                 }
             });
         });
 
+        if let Some(size) = wanted {
+            // the joystick screen fits its layout
+            frame.set_window_size(size.max(Vec2::new(win_width, win_height)));
+            self.best_width = size.x;
+            return;
+        }
         win_height = cmp::max(cen_pan.response.rect.height() as u64 - 6, win_height as u64 ) as f32;
         win_width = cmp::max(cen_pan.response.rect.width() as u64 - 6, win_width as u64 ) as f32;
         self.best_width = cmp::max(self.best_width as u64, win_width as u64 ) as f32;
@@ -411,6 +735,197 @@ impl eframe::App for MyApp {
  *
  * ******************************************************************************* */
 
+/// an axis as a bar, dial or ministick, with its label underneath
+fn axis_widget(ui: &mut Ui, dev_report: &device::DeviceReport, widget: &display::AxisWidget, scale: f32) {
+    let value = dev_report.axis_f32(widget.axis).unwrap_or(0.5).clamp(0.0, 1.0);
+    let lit = FILL_COLOUR;
+    let unlit = Color32::DARK_GRAY;
+    ui.vertical(|ui| {
+        let size = match widget.kind {
+            display::WidgetKind::VBar => Vec2::new(WIDGET_SIZE * 0.25, WIDGET_SIZE),
+            display::WidgetKind::HBar => Vec2::new(WIDGET_SIZE, WIDGET_SIZE * 0.25),
+            _ => Vec2::splat(WIDGET_SIZE),
+        } * scale;
+        let (response, painter) = ui.allocate_painter(size, Sense::hover());
+        let rect = response.rect;
+        match widget.kind {
+            display::WidgetKind::VBar => {
+                let mut fill = rect;
+                fill.set_top(rect.bottom() - rect.height() * value);
+                painter.rect_filled(fill, 0.0, lit);
+            }
+            display::WidgetKind::HBar => {
+                let mut fill = rect;
+                fill.set_right(rect.left() + rect.width() * value);
+                painter.rect_filled(fill, 0.0, lit);
+            }
+            display::WidgetKind::Dial => {
+                // from 7 o'clock (none) round to 5 o'clock (full)
+                let radius = rect.width() * 0.5 - 2.0;
+                let angle = (value - 0.5) * 1.5 * std::f32::consts::PI;
+                painter.circle_stroke(rect.center(), radius, egui::Stroke::new(1.0, unlit));
+                painter.line_segment(
+                    [rect.center(), rect.center() + Vec2::new(angle.sin(), -angle.cos()) * radius],
+                    egui::Stroke::new(3.0, lit));
+            }
+            display::WidgetKind::Ministick(down) => {
+                let down = dev_report.axis_f32(down).unwrap_or(0.5).clamp(0.0, 1.0);
+                painter.line_segment([rect.center_top(), rect.center_bottom()], egui::Stroke::new(1.0, unlit));
+                painter.line_segment([rect.left_center(), rect.right_center()], egui::Stroke::new(1.0, unlit));
+                painter.circle_filled(
+                    egui::Pos2::new(rect.left() + rect.width() * value, rect.top() + rect.height() * down),
+                    5.0, lit);
+            }
+        }
+        if widget.kind != display::WidgetKind::Dial {
+            painter.rect_stroke(rect, 0.0, egui::Stroke::new(1.0, unlit));
+        }
+        response.on_hover_text(format!("{} {:.0}%", widget.label, value * 100.0));
+        ui.small(&widget.label);
+    });
+}
+
+/// rudder pedals: a yaw bar across the top, filling from the centre to the side pushed,
+/// and the toe brakes under each end, filling upwards; all turned clockwise by turn (radians)
+fn pedals_widget(ui: &mut Ui, dev_report: &device::DeviceReport, pedals: &display::Pedals, size: Vec2, turn: f32) {
+    let (response, painter) = ui.allocate_painter(size, Sense::hover());
+    let rect = response.rect.shrink(size.x * 0.1);
+    let unlit = Color32::DARK_GRAY;
+    let mut shapes: Vec<egui::Shape> = Vec::new();
+    let yaw = pedals.yaw(dev_report);
+    let bar = egui::Rect::from_min_size(rect.min, Vec2::new(rect.width(), rect.height() * 0.15));
+    let mut fill = bar;
+    if yaw < 0.0 {
+        fill.set_left(bar.center().x + bar.width() * 0.5 * yaw);
+        fill.set_right(bar.center().x);
+    } else {
+        fill.set_left(bar.center().x);
+        fill.set_right(bar.center().x + bar.width() * 0.5 * yaw);
+    }
+    shapes.push(egui::Shape::rect_filled(fill, 0.0, FILL_COLOUR));
+    shapes.push(egui::Shape::rect_stroke(bar, 0.0, egui::Stroke::new(1.0, unlit)));
+    shapes.push(egui::Shape::line_segment([bar.center_top(), bar.center_bottom()], egui::Stroke::new(1.0, Color32::YELLOW)));
+    let mut text = format!("yaw {:+.0}%", yaw * 100.0);
+    if let Some((left, right)) = pedals.brakes(dev_report) {
+        let top = bar.bottom() + rect.height() * 0.1;
+        let width = rect.width() * 0.2;
+        for (x, value) in [(rect.left(), left), (rect.right() - width, right)] {
+            let brake = egui::Rect::from_min_max(egui::Pos2::new(x, top), egui::Pos2::new(x + width, rect.bottom()));
+            let mut fill = brake;
+            fill.set_top(brake.bottom() - brake.height() * value);
+            shapes.push(egui::Shape::rect_filled(fill, 0.0, FILL_COLOUR));
+            shapes.push(egui::Shape::rect_stroke(brake, 0.0, egui::Stroke::new(1.0, unlit)));
+        }
+        text = format!("{}\nleft brake {:.0}%\nright brake {:.0}%", text, left * 100.0, right * 100.0);
+    }
+    painter.extend(shapes.into_iter().map(|shape| turned(shape, response.rect.center(), turn)));
+    response.on_hover_text(text);
+}
+
+/// a throttle: a slot for each lever, the detents across them, and a bar joining two
+/// levers that are together; all turned clockwise by turn (radians)
+fn throttle_widget(ui: &mut Ui, dev_report: &device::DeviceReport, throttle: &display::Throttle, size: Vec2, turn: f32) {
+    let (response, painter) = ui.allocate_painter(size, Sense::hover());
+    let rect = response.rect.shrink(size.x * 0.1);
+    let unlit = Color32::DARK_GRAY;
+    let mut shapes: Vec<egui::Shape> = Vec::new();
+    let positions = throttle.positions(dev_report);
+    let slot_width = rect.width() * 0.15;
+    let handle = Vec2::new(rect.width() * 0.3, rect.height() * 0.08);
+    // levers side by side, in the right half, leaving the left for the detents' names
+    let slot_x = |n: usize| rect.left() + rect.width() * (0.6 + 0.25 * n as f32 - 0.125 * (positions.len() - 1) as f32);
+    let travel_y = |at: f32| rect.bottom() - handle.y * 0.5 - (rect.height() - handle.y) * at;
+    for n in 0..positions.len() {
+        let slot = egui::Rect::from_center_size(
+            egui::Pos2::new(slot_x(n), rect.center().y),
+            Vec2::new(slot_width, rect.height()));
+        shapes.push(egui::Shape::rect_stroke(slot, 2.0, egui::Stroke::new(1.0, unlit)));
+    }
+    for detent in &throttle.detents {
+        let y = travel_y(detent.at_f32());
+        shapes.push(egui::Shape::line_segment(
+            [egui::Pos2::new(rect.left() + rect.width() * 0.4, y), egui::Pos2::new(rect.right(), y)],
+            egui::Stroke::new(1.0, Color32::YELLOW)));
+        shapes.push(painter.fonts(|fonts| egui::Shape::text(fonts,
+            egui::Pos2::new(rect.left() + rect.width() * 0.38, y), egui::Align2::RIGHT_CENTER,
+            &detent.name, egui::FontId::proportional(12.0), Color32::YELLOW)));
+    }
+    let locked = throttle.locked(dev_report);
+    if locked {
+        let y = travel_y((positions[0] + positions[1]) * 0.5);
+        shapes.push(egui::Shape::line_segment([egui::Pos2::new(slot_x(0), y), egui::Pos2::new(slot_x(1), y)],
+            egui::Stroke::new(4.0, FILL_COLOUR)));
+    }
+    for (n, at) in positions.iter().enumerate() {
+        let lever = egui::Rect::from_center_size(egui::Pos2::new(slot_x(n), travel_y(*at)), handle);
+        shapes.push(egui::Shape::rect_filled(lever, 3.0, FILL_COLOUR));
+    }
+    painter.extend(shapes.into_iter().map(|shape| turned(shape, response.rect.center(), turn)));
+    let mut text: Vec<String> = throttle.levers.iter().zip(&positions)
+        .map(|(axis, at)| format!("{} {:.0}%", axis, at * 100.0))
+        .collect();
+    if locked {
+        text.push("locked together".to_string());
+    }
+    response.on_hover_text(text.join("\n"));
+}
+
+/// a shape turned clockwise about centre by turn (radians); rectangles lose their rounded corners
+fn turned(shape: egui::Shape, centre: egui::Pos2, turn: f32) -> egui::Shape {
+    if turn == 0.0 {
+        return shape;
+    }
+    let rot = egui::emath::Rot2::from_angle(turn);
+    let point = |p: egui::Pos2| centre + rot * (p - centre);
+    match shape {
+        egui::Shape::Rect(rect) => {
+            let r = rect.rect;
+            egui::Shape::convex_polygon(
+                [r.left_top(), r.right_top(), r.right_bottom(), r.left_bottom()].into_iter().map(point).collect(),
+                rect.fill, rect.stroke)
+        }
+        egui::Shape::LineSegment { points, stroke } => {
+            egui::Shape::LineSegment { points: points.map(point), stroke }
+        }
+        egui::Shape::Circle(mut circle) => {
+            circle.center = point(circle.center);
+            egui::Shape::Circle(circle)
+        }
+        egui::Shape::Text(mut text) => {
+            text.pos = point(text.pos);
+            text.angle += turn;
+            egui::Shape::Text(text)
+        }
+        egui::Shape::Path(mut path) => {
+            path.points = path.points.into_iter().map(point).collect();
+            egui::Shape::Path(path)
+        }
+        egui::Shape::Vec(shapes) => {
+            egui::Shape::Vec(shapes.into_iter().map(|s| turned(s, centre, turn)).collect())
+        }
+        other => other,
+    }
+}
+
+/// a hat as a compass: a ring of eight points, the one it is pushed to lit, or the centre
+fn hat_compass(ui: &mut Ui, hat: &hats::Hat, scale: f32) {
+    let (response, painter) = ui.allocate_painter(Vec2::splat(HAT_SIZE * scale), Sense::hover());
+    let centre = response.rect.center();
+    let radius = HAT_SIZE * scale * 0.5 - 2.0;
+    let unlit = Color32::DARK_GRAY;
+    painter.circle_stroke(centre, radius, egui::Stroke::new(1.0, unlit));
+    for n in 0..8u8 {
+        let angle = n as f32 * std::f32::consts::FRAC_PI_4;
+        let point = centre + Vec2::new(angle.sin(), -angle.cos()) * radius * 0.7;
+        let size = if n % 2 == 0 { 4.0 } else { 3.0 } * scale;        // up, right, down and left a little bigger
+        let colour = if hat.position == Some(n) { FILL_COLOUR } else { unlit };
+        painter.circle_filled(point, size, colour);
+    }
+    let colour = if hat.position.is_none() { FILL_COLOUR } else { unlit };
+    painter.circle_filled(centre, 3.0 * scale, colour);
+    response.on_hover_text(hat.label());
+}
+
 fn load_icon(path: &str) -> eframe::IconData {
     let (icon_rgba, icon_width, icon_height) = {
         let image = image::open(path)
@@ -446,14 +961,11 @@ fn main() {
         ..Default::default()
     };
     
-    match eframe::run_native(
+    let _ = eframe::run_native(
         PROGRAM_TITLE,
         options,
         Box::new(|_cc| Box::new(MyApp::default())),
-    ) {
-		Ok(_) => {}
-		Err(_) => {}
-	}
+    );
 }
 
 /* ******************************************************************************* */
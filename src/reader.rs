@@ -0,0 +1,182 @@
+/* ******************************************************************************* */
+/* Reader threads: one for each open device, unless built single-threaded */
+/* ******************************************************************************* */
+/*
+ * Each thread owns its device, waits for reports, and sends them to the UI thread
+ * over a channel.  A slow or sleeping device then only holds up its own thread, and
+ * reports are read as the device sends them, not once a frame.
+ * The channel holds at most MAX_WAITING reports: when the UI thread falls behind, the
+ * reader waits for it, and later reports wait in the device, as they would unread.
+ * None are dropped, so a button pressed and let go between frames is still seen.
+ * The UI thread takes whatever has arrived, without waiting (see device::read_device).
+ */
+use crate::backend::InputDevice;
+use std::sync::atomic::{AtomicBool, Ordering, };
+use std::sync::mpsc::{self, Receiver, TryRecvError, };
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, };
+
+/* ******************************************************************************* */
+/* Constants */
+
+// how long a read waits, and so how soon a thread notices it should stop (milliseconds)
+const READ_TIMEOUT: i32 = 100;
+// devices that return at once when there is nothing to read are asked again after this
+const IDLE_WAIT: Duration = Duration::from_millis( 1 );
+// the most reports sent and not yet taken by the UI thread, before the reader waits
+const MAX_WAITING: usize = 1024;
+
+/* ******************************************************************************* */
+/* Structures */
+/* ******************************************************************************* */
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ReaderEvent {
+	Report( Instant, Vec<u8> ),		// when it was read, and the report
+	Error( String ),		// the thread has stopped, the device has gone
+}
+
+/* ******************************************************************************* */
+/// The UI thread's end of a reader thread; dropping it stops the thread
+pub struct Reader {
+	events: Receiver<ReaderEvent>,
+	stop: Arc<AtomicBool>,
+}
+
+impl Reader {
+	/// move device to a new thread, reading reports of up to buff_len bytes
+	pub fn spawn( name: String, device: Box<dyn InputDevice>, buff_len: usize ) -> Result<Reader, String> {
+		let (sender, events) = mpsc::sync_channel( MAX_WAITING );
+		let stop = Arc::new( AtomicBool::new( false ) );
+		let stopping = stop.clone();
+		thread::Builder::new()
+			.name( format!("reader {}", name) )
+			.spawn( move || {
+				let mut buff = vec![0u8; buff_len];
+				while !stopping.load( Ordering::Relaxed ) {
+					let started = Instant::now();
+					let event = match device.read_timeout( &mut buff, READ_TIMEOUT ) {
+						Ok( 0 ) => {
+							if started.elapsed() < IDLE_WAIT {
+								thread::sleep( IDLE_WAIT );
+							}
+							continue;
+						}
+						Ok( len ) => {	ReaderEvent::Report( Instant::now(), buff[ ..len ].to_vec() )	}
+						Err( err ) => {	ReaderEvent::Error( err )	}
+					};
+					let stopped = matches!( event, ReaderEvent::Error( _ ) );
+					// waits while MAX_WAITING are waiting; fails once the Reader is dropped
+					if sender.send( event ).is_err() || stopped {
+						break;		// nobody listening, or nothing more to read
+					}
+				}
+			})
+			.map_err( |err| err.to_string() )?;
+		Ok( Reader { events, stop } )
+	}
+
+	/// the next waiting event, without waiting for one
+	pub fn try_next( &self ) -> Option<ReaderEvent> {
+		match self.events.try_recv() {
+			Ok( event ) => {	Some( event )	}
+			Err( TryRecvError::Empty ) => {	None	}
+			Err( TryRecvError::Disconnected ) => {
+				Some( ReaderEvent::Error( "reader thread stopped".to_string() ) )
+			}
+		}
+	}
+}
+
+impl Drop for Reader {
+	fn drop( &mut self ) {
+		// the thread sees this within READ_TIMEOUT, and closes the device
+		self.stop.store( true, Ordering::Relaxed );
+	}
+}
+
+/* ******************************************************************************* */
+/* Tests */
+/* ******************************************************************************* */
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::backend::mock::MockDevice;
+
+	/// the next event, waiting up to a second for it
+	fn next( reader: &Reader ) -> Option<ReaderEvent> {
+		let started = Instant::now();
+		while started.elapsed() < Duration::from_secs( 1 ) {
+			if let Some( event ) = reader.try_next() {
+				return Some( event );
+			}
+			thread::sleep( IDLE_WAIT );
+		}
+		None
+	}
+
+	#[test]
+	fn reports_then_an_error() {
+		let device = MockDevice::new( 0x3344, 0x0001, "Stick" );
+		let script = device.script.clone();
+		script.push_report( &[ 1, 2, 3 ] );
+		script.push_report( &[ 1, 2, 3, 4, 5, 6 ] );
+		let before = Instant::now();
+		let reader = Reader::spawn( "Stick".to_string(), Box::new( device ), 4 ).unwrap();
+
+		let Some( ReaderEvent::Report( at, report ) ) = next( &reader ) else { panic!( "no report" ) };
+		assert!( at >= before );
+		assert_eq!( report, vec![ 1, 2, 3 ] );
+		// cut to the buffer
+		assert!( matches!( next( &reader ), Some( ReaderEvent::Report( _, report ) ) if report == vec![ 1, 2, 3, 4 ] ) );
+		assert_eq!( reader.try_next(), None );
+
+		script.set_error( Some( "unplugged".to_string() ) );
+		assert_eq!( next( &reader ), Some( ReaderEvent::Error( "unplugged".to_string() ) ) );
+		// the thread has gone
+		assert_eq!( next( &reader ), Some( ReaderEvent::Error( "reader thread stopped".to_string() ) ) );
+	}
+
+	#[test]
+	fn dropping_stops_the_thread() {
+		let device = MockDevice::new( 0x3344, 0x0001, "Stick" );
+		let script = device.script.clone();
+		let reader = Reader::spawn( "Stick".to_string(), Box::new( device ), 4 ).unwrap();
+		drop( reader );
+		thread::sleep( Duration::from_millis( 50 ) );
+		// not read, nobody is listening
+		script.push_report( &[ 1 ] );
+		thread::sleep( Duration::from_millis( 50 ) );
+		assert_eq!( script.pending(), 1 );
+	}
+
+	#[test]
+	fn a_full_channel_waits() {
+		let device = MockDevice::new( 0x3344, 0x0001, "Stick" );
+		let script = device.script.clone();
+		for n in 0 .. MAX_WAITING + 10 {
+			script.push_report( &[ n as u8 ] );
+		}
+		let reader = Reader::spawn( "Stick".to_string(), Box::new( device ), 4 ).unwrap();
+		thread::sleep( Duration::from_millis( 200 ) );
+		// MAX_WAITING sent, one more read and waiting to be sent, the rest left in the device
+		assert_eq!( script.pending(), 9 );
+
+		// taking them lets the reader go on, and none are lost
+		let mut taken = 0;
+		while let Some( ReaderEvent::Report( _, report ) ) = next( &reader ) {
+			assert_eq!( report, vec![ taken as u8 ] );
+			taken += 1;
+			if taken == MAX_WAITING + 10 {
+				break;
+			}
+		}
+		assert_eq!( taken, MAX_WAITING + 10 );
+		assert_eq!( script.pending(), 0 );
+	}
+}
+
+/* ******************************************************************************* *
+ *		*** End ***
+ * ******************************************************************************* */
@@ -0,0 +1,341 @@
+/* ******************************************************************************* */
+/* Axis routing: an axis' value taken from other devices' axes, and mixed */
+/* ******************************************************************************* */
+/*
+ * A device's section can give any of its axes a route, e.g.
+ *	z_route = rudder pedals:x							the pedals' x, in place of this device's z
+ *	rz_route = rudder pedals:rx; rudder pedals:ry; mix=toe_brakes
+ * Sources are separated by ';', each is a device (a section name, "self", or a vid and pid
+ * as for echo) and an axis, then any of invert, scale= and offset=.
+ * Values are worked out from 0.0 to 1.0 of the axis' travel, and routes are worked out in
+ * an order that has each route's sources ready before it: a route may read a routed axis,
+ * but not (even through others) itself.
+ */
+use crate::backend::DeviceKey;
+use crate::device::find_axis;
+use std::collections::{HashMap, HashSet, };
+use std::fmt;
+
+/* ******************************************************************************* */
+/* Structures */
+/* ******************************************************************************* */
+
+/// How the sources of a route make one value
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mix {
+	Copy,			// one source, as it is
+	Sum,			// all sources added, up to full
+	Difference,		// the first, less the others, down to zero
+	Max,			// the greatest
+	ToeBrakes,		// two brakes as one centred axis: first pressed reads low, second high
+}
+
+impl Mix {
+	fn from_name( name: &str ) -> Result<Mix, String> {
+		match name.trim().to_lowercase().as_str() {
+			"copy" => Ok( Mix::Copy ),
+			"sum" => Ok( Mix::Sum ),
+			"difference" => Ok( Mix::Difference ),
+			"max" => Ok( Mix::Max ),
+			"toe_brakes" | "differential" => Ok( Mix::ToeBrakes ),
+			other => Err( format!("unknown mix '{}', use sum, difference, max or toe_brakes", other) ),
+		}
+	}
+}
+
+/* ******************************************************************************* */
+/// One axis a route reads from
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteSource {
+	pub device: String,				// as written: a section name, "self", or a vid and pid
+	pub key: Option<DeviceKey>,		// the device, once found (see plan)
+	pub axis: &'static str,
+	pub invert: bool,
+	pub scale: f64,
+	pub offset: f64,
+}
+
+impl RouteSource {
+	fn parse( text: &str ) -> Result<RouteSource, String> {
+		let mut fields = text.split( ',' );
+		let first = fields.next().unwrap_or( "" ).trim();
+		let Some( (device, axis) ) = first.rsplit_once( ':' ) else {
+			return Err( format!("'{}' should be device:axis", first) );
+		};
+		let axis = find_axis( axis )?;
+		let mut source = RouteSource {
+			device: device.trim().to_string(),
+			key: None,
+			axis,
+			invert: false,
+			scale: 1.0,
+			offset: 0.0,
+		};
+		for field in fields.map( |f| f.trim() ).filter( |f| !f.is_empty() ) {
+			let (name, value) = field.split_once( '=' ).unwrap_or( (field, "") );
+			let value = value.trim();
+			match name.trim().to_lowercase().as_str() {
+				"invert" => {
+					source.invert = value.is_empty() || value.parse::<bool>().map_err( |e| format!("invert {}", e) )?;
+				}
+				"scale" => {	source.scale = value.parse::<f64>().map_err( |e| format!("scale {}", e) )?;	}
+				"offset" => {	source.offset = value.parse::<f64>().map_err( |e| format!("offset {}", e) )?;	}
+				other => {	return Err( format!("unknown field '{}'", other) );	}
+			}
+		}
+		Ok( source )
+	}
+
+	/// the source's value (0.0 to 1.0), inverted, scaled and offset
+	fn apply( &self, value: f64 ) -> f64 {
+		let value = match self.invert {
+			true => 1.0 - value,
+			false => value,
+		};
+		value * self.scale + self.offset
+	}
+}
+
+impl fmt::Display for RouteSource {
+	fn fmt( &self, f: &mut fmt::Formatter ) -> fmt::Result {
+		write!( f, "{}:{}", self.device, self.axis )
+	}
+}
+
+/* ******************************************************************************* */
+/// Where an axis' value comes from, in place of the device's own
+#[derive(Clone, Debug, PartialEq)]
+pub struct Route {
+	pub target: &'static str,	// the axis set, on the route's own device
+	pub sources: Vec<RouteSource>,
+	pub mix: Mix,
+}
+
+impl Route {
+	/// a route from an .ini file: target is the axis, e.g. "z" for z_route
+	pub fn parse( target: &str, text: &str ) -> Result<Route, String> {
+		let target = find_axis( target )?;
+		let mut sources: Vec<RouteSource> = Vec::new();
+		let mut mix: Option<Mix> = None;
+		for item in text.split( ';' ).map( |i| i.trim() ).filter( |i| !i.is_empty() ) {
+			match item.strip_prefix( "mix=" ) {
+				Some( name ) => {	mix = Some( Mix::from_name( name )? );	}
+				None => {	sources.push( RouteSource::parse( item )? );	}
+			}
+		}
+		let mix = match (mix, sources.len()) {
+			(_, 0) => {	return Err( "no sources".to_string() );	}
+			(None, 1) => Mix::Copy,
+			(None, _) => {	return Err( "more than one source needs a mix=".to_string() );	}
+			(Some( Mix::Copy ), n) if n > 1 => {	return Err( "copy takes one source".to_string() );	}
+			(Some( Mix::ToeBrakes ), n) if n != 2 => {	return Err( "toe_brakes takes two sources".to_string() );	}
+			(Some( mix ), _) => mix,
+		};
+		Ok( Route { target, sources, mix } )
+	}
+
+	/// the same axis of another device, as echo_x, echo_y and echo_z have always done
+	pub fn echo( axis: &str, device: &str ) -> Result<Route, String> {
+		Route::parse( axis, &format!("{}:{}", device, axis) )
+	}
+
+	/// mix the sources' values (0.0 to 1.0 each, in the order of the sources)
+	pub fn apply( &self, values: &[f64] ) -> f64 {
+		let values: Vec<f64> = self.sources.iter().zip( values ).map( |(s, v)| s.apply( *v ) ).collect();
+		let value = match self.mix {
+			Mix::Copy => values[ 0 ],
+			Mix::Sum => values.iter().sum(),
+			Mix::Difference => values[ 0 ] - values[ 1.. ].iter().sum::<f64>(),
+			Mix::Max => values.iter().cloned().fold( f64::MIN, f64::max ),
+			Mix::ToeBrakes => 0.5 + (values[ 1 ] - values[ 0 ]) / 2.0,
+		};
+		value.clamp( 0.0, 1.0 )
+	}
+}
+
+/* ******************************************************************************* */
+/// find each source's device, and order the routes so that every route comes after
+/// any route it reads from
+/// routes: each device's key, with the routes from its section
+/// sections: each device's section name (lower case, as configparser gives it) and key
+/// returns the routes that can be used, and an error for each that cannot
+pub fn plan( routes: &[(DeviceKey, Route)], sections: &[(String, DeviceKey)] ) -> (Vec<(DeviceKey, Route)>, Vec<String>) {
+	let mut errors: Vec<String> = Vec::new();
+	let mut found: Vec<(DeviceKey, Route)> = Vec::new();
+
+	'routes: for (key, route) in routes {
+		let mut route = route.clone();
+		for source in route.sources.iter_mut() {
+			match find_device( &source.device, key, sections ) {
+				Some( k ) => {	source.key = Some( k );	}
+				None => {
+					errors.push( format!("{} {}_route: no device '{}'", key, route.target, source.device) );
+					continue 'routes;
+				}
+			}
+		}
+		found.push( (key.clone(), route) );
+	}
+
+	// (device, axis) set by a route, and still to be ordered
+	let mut waiting: HashMap<(DeviceKey, &'static str), usize> = found.iter().enumerate()
+		.map( |(n, (key, route))| ((key.clone(), route.target), n) )
+		.collect();
+	let mut ordered: Vec<(DeviceKey, Route)> = Vec::new();
+	let mut done: HashSet<usize> = HashSet::new();
+	loop {
+		// a route is ready when none of its sources are still waiting
+		let ready: Vec<usize> = (0..found.len())
+			.filter( |n| !done.contains( n ) )
+			.filter( |n| found[ *n ].1.sources.iter().all( |s| {
+				!waiting.contains_key( &(s.key.clone().unwrap_or_default(), s.axis) )
+			}))
+			.collect();
+		if ready.is_empty() { break; }
+		for n in ready {
+			let (key, route) = &found[ n ];
+			waiting.remove( &(key.clone(), route.target) );
+			ordered.push( (key.clone(), route.clone()) );
+			done.insert( n );
+		}
+	}
+	// whatever is left reads from itself, one way or another, or from a route that does
+	let mut circle: Vec<String> = waiting.keys().map( |(key, axis)| format!("{} {}", key, axis) ).collect();
+	if !circle.is_empty() {
+		circle.sort();
+		errors.push( format!("routes that go round in a circle are not used: {}", circle.join( ", " )) );
+	}
+	(ordered, errors)
+}
+
+/* ******************************************************************************* */
+/// a route's source device: "self", a section name, or a vid and pid (and serial=, ...)
+pub fn find_device( device: &str, own: &DeviceKey, sections: &[(String, DeviceKey)] ) -> Option<DeviceKey> {
+	if device.eq_ignore_ascii_case( "self" ) {
+		return Some( own.clone() );
+	}
+	if let Some( (_, key) ) = sections.iter().find( |(name, _)| name.eq_ignore_ascii_case( device ) ) {
+		return Some( key.clone() );
+	}
+	let wanted = DeviceKey::parse( device ).ok()?;
+	if let Some( (_, key) ) = sections.iter().find( |(_, key)| *key == wanted ) {
+		return Some( key.clone() );
+	}
+	// just a vid and pid will do, when only one device has them
+	let mut same: Vec<&DeviceKey> = sections.iter()
+		.map( |(_, key)| key )
+		.filter( |key| key.vid == wanted.vid && key.pid == wanted.pid )
+		.collect();
+	match (same.len(), wanted == DeviceKey::new( wanted.vid, wanted.pid )) {
+		(1, true) => same.pop().cloned(),
+		_ => None,
+	}
+}
+
+/* ******************************************************************************* */
+/* Tests */
+/* ******************************************************************************* */
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn sections() -> Vec<(String, DeviceKey)> {
+		vec![
+			("stick".to_string(), DeviceKey::new( 0x3344, 0x0001 )),
+			("rudder pedals".to_string(), DeviceKey::new( 0x3344, 0x0002 )),
+			("left".to_string(), DeviceKey { serial: Some( "L1".to_string() ), ..DeviceKey::new( 0x3344, 0x0003 ) }),
+			("right".to_string(), DeviceKey { serial: Some( "R2".to_string() ), ..DeviceKey::new( 0x3344, 0x0003 ) }),
+		]
+	}
+
+	#[test]
+	fn parse() {
+		let route = Route::parse( "rz", "Rudder Pedals:RX, invert; rudder pedals:ry, scale=0.5, offset=0.1; mix=toe_brakes" ).unwrap();
+		assert_eq!( (route.target, route.mix, route.sources.len()), ("rz", Mix::ToeBrakes, 2) );
+		assert_eq!( (route.sources[ 0 ].device.as_str(), route.sources[ 0 ].axis, route.sources[ 0 ].invert), ("Rudder Pedals", "rx", true) );
+		assert_eq!( (route.sources[ 1 ].scale, route.sources[ 1 ].offset), (0.5, 0.1) );
+		assert_eq!( Route::echo( "z", "3344 0002" ).unwrap().sources[ 0 ].to_string(), "3344 0002:z" );
+
+		for (target, text) in [
+			("w", "self:x"),
+			("z", ""),
+			("z", "pedals"),
+			("z", "pedals:w"),
+			("z", "pedals:x, wobble=1"),
+			("z", "pedals:x; pedals:y"),
+			("z", "pedals:x; pedals:y; mix=copy"),
+			("z", "pedals:x; mix=toe_brakes"),
+			("z", "pedals:x; pedals:y; mix=average"),
+		] {
+			assert!( Route::parse( target, text ).is_err(), "{} {}", target, text );
+		}
+	}
+
+	#[test]
+	fn mixes() {
+		let apply = |text: &str, values: &[f64]| Route::parse( "z", text ).unwrap().apply( values );
+		assert_eq!( apply( "a:x", &[ 0.25 ] ), 0.25 );
+		assert_eq!( apply( "a:x, invert", &[ 0.25 ] ), 0.75 );
+		assert_eq!( apply( "a:x, scale=2, offset=0.25", &[ 0.5 ] ), 1.0 );
+		assert_eq!( apply( "a:x; a:y; mix=sum", &[ 0.75, 0.5 ] ), 1.0 );
+		assert_eq!( apply( "a:x; a:y; mix=difference", &[ 0.25, 0.5 ] ), 0.0 );
+		assert_eq!( apply( "a:x; a:y; a:z; mix=max", &[ 0.25, 0.75, 0.5 ] ), 0.75 );
+		// first brake pressed reads low, second high, neither centred
+		assert_eq!( apply( "a:x; a:y; mix=toe_brakes", &[ 1.0, 0.0 ] ), 0.0 );
+		assert_eq!( apply( "a:x; a:y; mix=toe_brakes", &[ 0.0, 1.0 ] ), 1.0 );
+		assert_eq!( apply( "a:x; a:y; mix=toe_brakes", &[ 0.0, 0.0 ] ), 0.5 );
+	}
+
+	#[test]
+	fn devices_found() {
+		let sections = sections();
+		let own = DeviceKey::new( 0x3344, 0x0001 );
+		assert_eq!( find_device( "SELF", &own, &sections ), Some( own.clone() ) );
+		assert_eq!( find_device( "Rudder Pedals", &own, &sections ), Some( sections[ 1 ].1.clone() ) );
+		assert_eq!( find_device( "3344 0002", &own, &sections ), Some( sections[ 1 ].1.clone() ) );
+		assert_eq!( find_device( "3344 0003 serial=R2", &own, &sections ), Some( sections[ 3 ].1.clone() ) );
+		// two devices have this vid and pid
+		assert_eq!( find_device( "3344 0003", &own, &sections ), None );
+		assert_eq!( find_device( "throttle", &own, &sections ), None );
+	}
+
+	#[test]
+	fn routes_are_ordered() {
+		let sections = sections();
+		let (stick, pedals) = (sections[ 0 ].1.clone(), sections[ 1 ].1.clone());
+		let routes = vec![
+			(stick.clone(), Route::parse( "rz", "self:z" ).unwrap()),
+			(stick.clone(), Route::parse( "z", "rudder pedals:x" ).unwrap()),
+			(pedals.clone(), Route::parse( "y", "throttle:y" ).unwrap()),
+		];
+		let (ordered, errors) = plan( &routes, &sections );
+		assert_eq!( errors, vec![ "3344 0002 y_route: no device 'throttle'".to_string() ] );
+		let targets: Vec<&str> = ordered.iter().map( |(_, route)| route.target ).collect();
+		assert_eq!( targets, vec![ "z", "rz" ] );
+		assert_eq!( ordered[ 1 ].1.sources[ 0 ].key, Some( stick ) );
+	}
+
+	#[test]
+	fn circles_are_not_used() {
+		let sections = sections();
+		let (stick, pedals) = (sections[ 0 ].1.clone(), sections[ 1 ].1.clone());
+		let routes = vec![
+			(stick.clone(), Route::parse( "z", "rudder pedals:x" ).unwrap()),
+			(pedals.clone(), Route::parse( "x", "stick:rz" ).unwrap()),
+			(stick.clone(), Route::parse( "rz", "self:z" ).unwrap()),
+			// reads from the circle, so waits for it for ever
+			(pedals.clone(), Route::parse( "y", "stick:z" ).unwrap()),
+			(pedals.clone(), Route::parse( "rx", "self:ry" ).unwrap()),
+		];
+		let (ordered, errors) = plan( &routes, &sections );
+		assert_eq!( ordered.len(), 1 );
+		assert_eq!( (ordered[ 0 ].0.clone(), ordered[ 0 ].1.target), (pedals, "rx") );
+		assert_eq!( errors, vec![
+			"routes that go round in a circle are not used: 3344 0001 rz, 3344 0001 z, 3344 0002 x, 3344 0002 y".to_string()
+		] );
+	}
+}
+
+/* ******************************************************************************* *
+ *		*** End ***
+ * ******************************************************************************* */
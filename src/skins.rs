@@ -0,0 +1,731 @@
+/* ******************************************************************************* */
+/* Skins: a device's look, as a directory of images and the layers they are drawn in */
+/* ******************************************************************************* */
+/*
+ * A skin is a directory in skins/, chosen in a device's section with e.g.
+ *	skin = virpil-constellation
+ * It holds its own images and a skin.ini, e.g.
+ *	[Skin]
+ *	name = Virpil Constellation
+ *	size = 240, 240								the box the layers are drawn in, in pixels
+ *	[Images]
+ *	grip = grip.svg								as img/images.ini, files from the skin's directory
+ *	[Layer grip]
+ *	image = grip
+ *	at = 120, 200								where its anchor goes in the box (default the centre)
+ *	anchor = bottom								which point of the image that is (default centre)
+ *	pivot = 0.5, 0.9							where it turns, as fractions of its size (default 0.5, 0.5)
+ *	z = 1										layers with a higher z are drawn over those lower
+ *	translate_x = x								the axes that move and turn it
+ *	translate_y = y
+ *	rotate = z, range=-30..30, curve=smooth
+ *	[Layer trigger pulled]
+ *	image = trigger_down
+ *	show_when = button 1						shown only while button 1 is pressed
+ *	highlight = hat 1 up, tint=#ffff00			tinted yellow while hat 1 is pushed up
+ * Any of translate_x, translate_y, rotate, scale, scale_x, scale_y, opacity and tint can be
+ * given an axis, with fields:
+ *	range=a..b		what the axis' ends give: pixels, degrees, times the size, 0 to 1, or two #rrggbb colours
+ *	input=a..b		the part of the axis' travel used, 0 to 1 (default 0..1)
+ *	curve=name		linear (default), ease_in, ease_out, smooth or step
+ *	pivot=x y		where the layer turns and scales, in place of the layer's pivot
+ * Without a range, a translation moves the layer a quarter of the box's size either way, a
+ * rotation turns it a radian either way, the same as a stick's image without a skin.
+ * Only the layers and where they go are here; drawing them is left to the program (see main.rs).
+ */
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::assets::Manifest;
+use crate::buttons::Button;
+use crate::device::{find_axis, DeviceReport, };
+use crate::hats::Hat;
+use crate::ini_file;
+use crate::layout::Anchor;
+
+/* ******************************************************************************* */
+/* Constants */
+
+pub const SKINS_DIR: &str = "skins";
+pub const SKIN_FILE: &str = "skin.ini";
+
+const DEFAULT_SIZE: (f32, f32) = (240.0, 240.0);	// the size of the stick images in img/
+const WHITE: (u8, u8, u8) = (255, 255, 255);		// a tint that changes nothing
+const HIGHLIGHT: (u8, u8, u8) = (255, 255, 0);		// yellow, when a highlight does not give a tint
+
+/* ******************************************************************************* */
+/* Structures */
+/* ******************************************************************************* */
+
+/// What an axis does to a layer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transform {
+	TranslateX,
+	TranslateY,
+	Rotate,
+	Scale,
+	ScaleX,
+	ScaleY,
+	Opacity,
+	Tint,
+}
+
+impl Transform {
+	fn from_key( key: &str ) -> Option<Transform> {
+		match key {
+			"translate_x" => Some( Transform::TranslateX ),
+			"translate_y" => Some( Transform::TranslateY ),
+			"rotate" => Some( Transform::Rotate ),
+			"scale" => Some( Transform::Scale ),
+			"scale_x" => Some( Transform::ScaleX ),
+			"scale_y" => Some( Transform::ScaleY ),
+			"opacity" => Some( Transform::Opacity ),
+			"tint" => Some( Transform::Tint ),
+			_ => None,
+		}
+	}
+}
+
+/// How an axis' travel is shaped, before its range is applied
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Curve {
+	#[default]
+	Linear,
+	EaseIn,					// slow at first, faster towards the end
+	EaseOut,				// fast at first, slower towards the end
+	Smooth,					// slow at both ends
+	Step,					// all or nothing, at halfway
+}
+
+impl Curve {
+	fn from_name( name: &str ) -> Result<Curve, String> {
+		match name.trim().to_lowercase().as_str() {
+			"linear" => Ok( Curve::Linear ),
+			"ease_in" => Ok( Curve::EaseIn ),
+			"ease_out" => Ok( Curve::EaseOut ),
+			"smooth" => Ok( Curve::Smooth ),
+			"step" => Ok( Curve::Step ),
+			other => Err( format!("unknown curve '{}', use linear, ease_in, ease_out, smooth or step", other) ),
+		}
+	}
+
+	/// t, from 0.0 to 1.0, shaped
+	fn apply( &self, t: f32 ) -> f32 {
+		match self {
+			Curve::Linear => t,
+			Curve::EaseIn => t * t,
+			Curve::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+			Curve::Smooth => t * t * (3.0 - 2.0 * t),
+			Curve::Step => if t < 0.5 { 0.0 } else { 1.0 },
+		}
+	}
+}
+
+/* ******************************************************************************* */
+/// One axis, moving, turning, sizing or colouring a layer
+#[derive(Clone, Debug, PartialEq)]
+pub struct Drive {
+	pub transform: Transform,
+	pub axis: &'static str,
+	pub range: Option<(f32, f32)>,		// None: as a stick's image moves (see poses)
+	pub colours: ((u8, u8, u8), (u8, u8, u8)),	// for a tint: the colours at each end
+	pub input: (f32, f32),				// the part of the axis' travel used
+	pub curve: Curve,
+	pub pivot: Option<(f32, f32)>,		// in place of the layer's
+}
+
+impl Drive {
+	/// a layer's transform key: the axis, then any of range=, input=, curve= and pivot=
+	fn parse( transform: Transform, text: &str ) -> Result<Drive, String> {
+		let mut fields = text.split( ',' ).map( |f| f.trim() );
+		let mut drive = Drive {
+			transform,
+			axis: find_axis( fields.next().unwrap_or( "" ) )?,
+			range: None,
+			colours: (WHITE, (0, 255, 0)),
+			input: (0.0, 1.0),
+			curve: Curve::Linear,
+			pivot: None,
+		};
+		for field in fields.filter( |f| !f.is_empty() ) {
+			let Some( (name, value) ) = field.split_once( '=' ) else {
+				return Err( format!("'{}' should be name=value", field) );
+			};
+			let value = value.trim();
+			match name.trim().to_lowercase().as_str() {
+				"range" if transform == Transform::Tint => {
+					let (from, to) = value.split_once( ".." ).ok_or( "range should be two colours, e.g. #ffffff..#ff0000" )?;
+					drive.colours = (colour( from )?, colour( to )?);
+				}
+				"range" => {	drive.range = Some( span( "range", value )? );	}
+				"input" => {
+					drive.input = span( "input", value )?;
+					if drive.input.0 == drive.input.1 {
+						return Err( "input should be two different numbers".to_string() );
+					}
+				}
+				"curve" => {	drive.curve = Curve::from_name( value )?;	}
+				"pivot" => {
+					let Some( (x, y) ) = value.split_once( ' ' ) else {
+						return Err( "pivot should be two numbers, separated by a space".to_string() );
+					};
+					drive.pivot = Some( (number( "pivot", x )?, number( "pivot", y )?) );
+				}
+				other => {	return Err( format!("unknown field '{}'", other) );	}
+			}
+		}
+		Ok( drive )
+	}
+
+	/// how far along its range the drive is, 0.0 to 1.0, for an axis' value of 0.0 to 1.0
+	fn amount( &self, value: f32 ) -> f32 {
+		let t = ((value - self.input.0) / (self.input.1 - self.input.0)).clamp( 0.0, 1.0 );
+		self.curve.apply( t )
+	}
+}
+
+/* ******************************************************************************* */
+/// A button or hat, as a layer's condition
+#[derive(Clone, Debug, PartialEq)]
+pub enum Control {
+	Button( usize ),
+	Hat( usize, HatWay ),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HatWay {
+	Any,						// pushed any way
+	Centred,
+	Towards( u8 ),				// eighths of a turn, clockwise from up
+}
+
+impl Control {
+	/// e.g. "button 3", "hat 1 up", "hat 2 down_left", "hat 1 any", "hat 1 centred"
+	fn parse( text: &str ) -> Result<Control, String> {
+		let words: Vec<String> = text.split_whitespace().map( |w| w.to_lowercase() ).collect();
+		let whole = |w: &str| w.parse::<usize>().map_err( |e| format!("'{}' {}", text.trim(), e) );
+		match words.iter().map( |w| w.as_str() ).collect::<Vec<&str>>().as_slice() {
+			["button", n] => Ok( Control::Button( whole( n )? ) ),
+			["hat", n, way] => {
+				let way = match *way {
+					"any" => HatWay::Any,
+					"centred" | "centered" => HatWay::Centred,
+					"up" | "n" => HatWay::Towards( 0 ),
+					"up_right" | "ne" => HatWay::Towards( 1 ),
+					"right" | "e" => HatWay::Towards( 2 ),
+					"down_right" | "se" => HatWay::Towards( 3 ),
+					"down" | "s" => HatWay::Towards( 4 ),
+					"down_left" | "sw" => HatWay::Towards( 5 ),
+					"left" | "w" => HatWay::Towards( 6 ),
+					"up_left" | "nw" => HatWay::Towards( 7 ),
+					other => {	return Err( format!("unknown hat way '{}'", other) );	}
+				};
+				Ok( Control::Hat( whole( n )?, way ) )
+			}
+			_ => Err( format!("'{}' should be button <n> or hat <n> <way>", text.trim()) ),
+		}
+	}
+
+	fn is_on( &self, buttons: &[Button], hats: &[Hat] ) -> bool {
+		match self {
+			Control::Button( n ) => buttons.iter().any( |b| b.number == *n && b.pressed ),
+			Control::Hat( n, way ) => hats.iter().filter( |h| h.number == *n ).any( |h| match way {
+				HatWay::Any => h.position.is_some(),
+				HatWay::Centred => h.position.is_none(),
+				HatWay::Towards( p ) => h.position == Some( *p ),
+			}),
+		}
+	}
+}
+
+/// any of a list of controls, separated by ;
+fn controls( text: &str ) -> Result<Vec<Control>, String> {
+	text.split( ';' ).filter( |c| !c.trim().is_empty() ).map( Control::parse ).collect()
+}
+
+/// A layer's look while any of its controls is on
+#[derive(Clone, Debug, PartialEq)]
+pub struct Highlight {
+	pub when: Vec<Control>,
+	pub tint: (u8, u8, u8),
+	pub opacity: f32,
+}
+
+impl Highlight {
+	/// the controls, separated by ;, then any of tint= and opacity=
+	fn parse( text: &str ) -> Result<Highlight, String> {
+		let mut fields = text.split( ',' ).map( |f| f.trim() );
+		let mut highlight = Highlight { when: controls( fields.next().unwrap_or( "" ) )?, tint: HIGHLIGHT, opacity: 1.0 };
+		for field in fields.filter( |f| !f.is_empty() ) {
+			let Some( (name, value) ) = field.split_once( '=' ) else {
+				return Err( format!("'{}' should be name=value", field) );
+			};
+			match name.trim().to_lowercase().as_str() {
+				"tint" => {	highlight.tint = colour( value )?;	}
+				"opacity" => {	highlight.opacity = number( "opacity", value )?.clamp( 0.0, 1.0 );	}
+				other => {	return Err( format!("unknown field '{}'", other) );	}
+			}
+		}
+		if highlight.when.is_empty() {
+			return Err( "needs a button or hat".to_string() );
+		}
+		Ok( highlight )
+	}
+}
+
+/* ******************************************************************************* */
+/// One image of a skin, and the axes, buttons and hats that change it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layer {
+	pub name: String,
+	pub image: String,					// by name, in the skin's [Images]
+	pub at: Option<(f32, f32)>,			// in the skin's box; None, its centre
+	pub anchor: Anchor,
+	pub pivot: (f32, f32),				// fractions of the image's width and height
+	pub angle: f32,						// radians, clockwise, before any rotate
+	pub z: i32,
+	pub drives: Vec<Drive>,
+	pub show_when: Vec<Control>,		// shown only while any is on; empty, always
+	pub hide_when: Vec<Control>,		// hidden while any is on
+	pub highlight: Option<Highlight>,
+}
+
+impl Layer {
+	/// a layer of one image, not moved
+	pub fn new( name: &str, image: &str ) -> Layer {
+		Layer {
+			name: name.to_string(),
+			image: image.to_string(),
+			at: None,
+			anchor: Anchor::Centre,
+			pivot: (0.5, 0.5),
+			angle: 0.0,
+			z: 0,
+			drives: Vec::new(),
+			show_when: Vec::new(),
+			hide_when: Vec::new(),
+			highlight: None,
+		}
+	}
+
+	fn parse( name: &str, values: HashMap<String, Option<String>> ) -> Result<Layer, String> {
+		let mut layer = Layer::new( name, "" );
+		for (key, value) in values {
+			let value = value.unwrap_or_default();
+			let value = value.trim();
+			let key = key.trim().to_lowercase();
+			if let Some( transform ) = Transform::from_key( &key ) {
+				layer.drives.push( Drive::parse( transform, value ).map_err( |e| format!("{} {}", key, e) )? );
+				continue;
+			}
+			match key.as_str() {
+				"comment" => {}
+				"image" => {	layer.image = value.to_lowercase();	}
+				"at" => {	layer.at = Some( pair( "at", value )? );	}
+				"anchor" => {	layer.anchor = Anchor::from_name( value )?;	}
+				"pivot" => {	layer.pivot = pair( "pivot", value )?;	}
+				"angle" => {	layer.angle = number( "angle", value )?.to_radians();	}
+				"z" => {	layer.z = value.parse::<i32>().map_err( |e| format!("z {}", e) )?;	}
+				"show_when" => {	layer.show_when = controls( value ).map_err( |e| format!("show_when {}", e) )?;	}
+				"hide_when" => {	layer.hide_when = controls( value ).map_err( |e| format!("hide_when {}", e) )?;	}
+				"highlight" => {	layer.highlight = Some( Highlight::parse( value ).map_err( |e| format!("highlight {}", e) )? );	}
+				other => {	return Err( format!("unknown key '{}'", other) );	}
+			}
+		}
+		if layer.image.is_empty() {
+			return Err( "needs an image".to_string() );
+		}
+		// the order keys are read in is not the file's, have drives in a set order
+		layer.drives.sort_by_key( |d| d.transform as u8 );
+		Ok( layer )
+	}
+}
+
+/* ******************************************************************************* */
+/// A layer, where and how it is to be drawn for one report
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pose {
+	pub image: String,					// by name, in the skin's [Images], else img/images.ini
+	pub at: (f32, f32),					// where its anchor goes, in the skin's box
+	pub anchor: Anchor,
+	pub pivot: (f32, f32),				// fractions of the image's width and height
+	pub angle: f32,						// radians, clockwise, about the pivot
+	pub scale: (f32, f32),				// across and down, about the pivot
+	pub opacity: f32,					// 0.0 (not seen) to 1.0
+	pub tint: (u8, u8, u8),				// the image's colours are multiplied by this
+}
+
+/* ******************************************************************************* */
+/// A skin, as read from its directory
+#[derive(Clone, Debug, PartialEq)]
+pub struct Skin {
+	pub name: String,					// its directory's name, as the skin key gives it
+	pub title: String,					// the name in [Skin], else the directory's
+	pub author: Option<String>,
+	pub size: (f32, f32),
+	pub images: Manifest,
+	pub layers: Vec<Layer>,				// lowest z first, the same z in name order
+}
+
+impl Skin {
+	/// read skins/<name>/skin.ini
+	/// returns the skin, and an error for each line that could not be used
+	pub fn load( name: &str ) -> Result<(Skin, Vec<String>), String> {
+		let dir = Path::new( SKINS_DIR ).join( name );
+		let path = dir.join( SKIN_FILE );
+		if !path.is_file() {
+			return Err( format!("skin '{}': no {}", name, path.display()) );
+		}
+		let mut config = ini_file::parser();
+		let sections = config.load( &path ).map_err( |err| format!("skin '{}': {}", name, err) )?;
+		Ok( Skin::from_config( name, &dir, sections ) )
+	}
+
+	/// a skin from its skin.ini's sections, its images' files found from dir
+	pub fn from_config( name: &str, dir: &Path, mut config: HashMap<String, HashMap<String, Option<String>>> ) -> (Skin, Vec<String>) {
+		let mut errors: Vec<String> = Vec::new();
+		let (images, more) = Manifest::from_config( dir, config.remove( "images" ).unwrap_or_default() );
+		errors.extend( more.into_iter().map( |e| format!("skin '{}': {}", name, e) ) );
+		let mut skin = Skin {
+			name: name.to_string(),
+			title: name.to_string(),
+			author: None,
+			size: DEFAULT_SIZE,
+			images,
+			layers: Vec::new(),
+		};
+		for (key, value) in config.remove( "skin" ).unwrap_or_default() {
+			let value = value.unwrap_or_default().trim().to_string();
+			match key.trim().to_lowercase().as_str() {
+				"comment" => {}
+				"name" => {	skin.title = value;	}
+				"author" => {	skin.author = Some( value ).filter( |v| !v.is_empty() );	}
+				"size" => {
+					match pair( "size", &value ) {
+						Ok( size ) if size.0 > 0.0 && size.1 > 0.0 => {	skin.size = size;	}
+						Ok( _ ) => {	errors.push( format!("skin '{}': [Skin] size should be more than 0", name) );	}
+						Err( err ) => {	errors.push( format!("skin '{}': [Skin] {}", name, err) );	}
+					}
+				}
+				other => {	errors.push( format!("skin '{}': [Skin] unknown key '{}'", name, other) );	}
+			}
+		}
+		for (section, values) in config {
+			if section == "comment" {
+				continue;
+			}
+			let Some( layer_name ) = section.strip_prefix( "layer" ).map( |l| l.trim() ).filter( |l| !l.is_empty() ) else {
+				errors.push( format!("skin '{}': unknown section [{}]", name, section) );
+				continue;
+			};
+			match Layer::parse( layer_name, values ) {
+				Ok( layer ) if !skin.images.contains( &layer.image ) => {
+					errors.push( format!("skin '{}': [Layer {}] no image '{}' in its [Images]", name, layer_name, layer.image) );
+				}
+				Ok( layer ) => {	skin.layers.push( layer );	}
+				Err( err ) => {	errors.push( format!("skin '{}': [Layer {}] {}", name, layer_name, err) );	}
+			}
+		}
+		skin.layers.sort_by( |a, b| a.z.cmp( &b.z ).then_with( || a.name.cmp( &b.name ) ) );
+		(skin, errors)
+	}
+
+	/// a stick's image, moved by x and y and turned by z, with its centre image over it:
+	/// how a device without a skin is drawn.  Its images are by name in img/images.ini
+	/// calibrate: the x, y (pixels) and z (radians) calibrate fields, moving the image
+	pub fn stick( body: &str, centre: &str, size: (f32, f32), calibrate: (f32, f32, f32) ) -> Skin {
+		let mut stick = Layer::new( "stick", body );
+		// a little right of, and below, the centre, as it always has been
+		stick.at = Some( (size.0 * 0.525 + calibrate.0, size.1 * 0.525 + calibrate.1) );
+		stick.angle = calibrate.2;
+		for (transform, axis) in [ (Transform::TranslateX, "x"), (Transform::TranslateY, "y"), (Transform::Rotate, "z") ] {
+			stick.drives.push( Drive {
+				transform,
+				axis,
+				range: None,
+				colours: (WHITE, WHITE),
+				input: (0.0, 1.0),
+				curve: Curve::Linear,
+				pivot: None,
+			});
+		}
+		let mut centre = Layer::new( "centre", centre );
+		centre.z = 1;
+		Skin {
+			name: String::new(),
+			title: "stick".to_string(),
+			author: None,
+			size,
+			images: Manifest::empty(),
+			layers: vec![ stick, centre ],
+		}
+	}
+
+	/// every layer shown, moved, turned, sized and coloured by the report's axes, buttons
+	/// and hats, in the order to draw them
+	pub fn poses( &self, report: &DeviceReport ) -> Vec<Pose> {
+		let on = |controls: &[Control]| controls.iter().any( |c| c.is_on( &report.buttons, &report.hats ) );
+		self.layers.iter()
+			.filter( |layer| layer.show_when.is_empty() || on( &layer.show_when ) )
+			.filter( |layer| !on( &layer.hide_when ) )
+			.map( |layer| {
+				let mut pose = Pose {
+					image: layer.image.clone(),
+					at: layer.at.unwrap_or( (self.size.0 * 0.5, self.size.1 * 0.5) ),
+					anchor: layer.anchor,
+					pivot: layer.pivot,
+					angle: layer.angle,
+					scale: (1.0, 1.0),
+					opacity: 1.0,
+					tint: WHITE,
+				};
+				for drive in &layer.drives {
+					let t = drive.amount( report.axis_f32( drive.axis ).unwrap_or( 0.5 ).clamp( 0.0, 1.0 ) );
+					// the default ranges: as a stick's image moves
+					let (from, to) = match (drive.range, drive.transform) {
+						(Some( range ), Transform::Rotate) => (range.0.to_radians(), range.1.to_radians()),
+						(Some( range ), _) => range,
+						(None, Transform::TranslateX) => (self.size.0 * -0.25, self.size.0 * 0.25),
+						(None, Transform::TranslateY) => (self.size.1 * -0.25, self.size.1 * 0.25),
+						(None, Transform::Rotate) => (-1.0, 1.0),
+						(None, Transform::Opacity) => (0.0, 1.0),
+						(None, _) => (1.0, 1.0),
+					};
+					let value = from + (to - from) * t;
+					match drive.transform {
+						Transform::TranslateX => {	pose.at.0 += value;	}
+						Transform::TranslateY => {	pose.at.1 += value;	}
+						Transform::Rotate => {	pose.angle += value;	}
+						Transform::Scale => {	pose.scale = (pose.scale.0 * value, pose.scale.1 * value);	}
+						Transform::ScaleX => {	pose.scale.0 *= value;	}
+						Transform::ScaleY => {	pose.scale.1 *= value;	}
+						Transform::Opacity => {	pose.opacity *= value.clamp( 0.0, 1.0 );	}
+						Transform::Tint => {	pose.tint = multiply( pose.tint, blend( drive.colours, t ) );	}
+					}
+					if let Some( pivot ) = drive.pivot {
+						pose.pivot = pivot;
+					}
+				}
+				if let Some( highlight ) = layer.highlight.as_ref().filter( |h| on( &h.when ) ) {
+					pose.tint = highlight.tint;
+					pose.opacity = highlight.opacity;
+				}
+				pose
+			}).collect()
+	}
+}
+
+/* ******************************************************************************* */
+
+/// two numbers, separated by a comma
+fn pair( name: &str, value: &str ) -> Result<(f32, f32), String> {
+	let Some( (a, b) ) = value.split_once( ',' ) else {
+		return Err( format!("{} should be two numbers, separated by a comma", name) );
+	};
+	Ok( (number( name, a )?, number( name, b )?) )
+}
+
+/// two numbers, separated by .., e.g. -30..30
+fn span( name: &str, value: &str ) -> Result<(f32, f32), String> {
+	let Some( (a, b) ) = value.split_once( ".." ) else {
+		return Err( format!("{} should be two numbers, separated by .., e.g. 0..1", name) );
+	};
+	Ok( (number( name, a )?, number( name, b )?) )
+}
+
+fn number( name: &str, value: &str ) -> Result<f32, String> {
+	value.trim().parse::<f32>().map_err( |e| format!("{} {}", name, e) )
+}
+
+/// #rrggbb
+fn colour( value: &str ) -> Result<(u8, u8, u8), String> {
+	let value = value.trim();
+	let hex = value.strip_prefix( '#' ).filter( |h| h.len() == 6 && h.is_ascii() )
+		.ok_or( format!("'{}' should be a colour, #rrggbb", value) )?;
+	let part = |n: usize| u8::from_str_radix( &hex[ n..n + 2 ], 16 ).map_err( |e| format!("'{}' {}", value, e) );
+	Ok( (part( 0 )?, part( 2 )?, part( 4 )?) )
+}
+
+/// the colour t of the way from one colour to the other
+fn blend( (from, to): ((u8, u8, u8), (u8, u8, u8)), t: f32 ) -> (u8, u8, u8) {
+	let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+	(mix( from.0, to.0 ), mix( from.1, to.1 ), mix( from.2, to.2 ))
+}
+
+/// one tint over another
+fn multiply( a: (u8, u8, u8), b: (u8, u8, u8) ) -> (u8, u8, u8) {
+	let mul = |x: u8, y: u8| ((x as u16 * y as u16) / 255) as u8;
+	(mul( a.0, b.0 ), mul( a.1, b.1 ), mul( a.2, b.2 ))
+}
+
+/* ******************************************************************************* */
+/* Tests */
+/* ******************************************************************************* */
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::device::tests::virtual_report;
+	use crate::device::JS_MAX;
+
+	/// a skin from skin.ini text, its images those of the classic skin
+	fn read( ini: &str ) -> (Skin, Vec<String>) {
+		let config = ini_file::parser().read( ini.to_string() ).unwrap();
+		Skin::from_config( "test", &Path::new( SKINS_DIR ).join( "classic" ), config )
+	}
+
+	const IMAGES: &str = "[Images]\nstick = stick.svg\ncentre = centre.svg\n";
+
+	#[test]
+	fn shipped() {
+		let (skin, errors) = Skin::load( "classic" ).unwrap();
+		assert!( errors.is_empty(), "{:?}", errors );
+		assert_eq!( skin.title, "Classic" );
+		assert_eq!( skin.size, (240.0, 240.0) );
+		let layers: Vec<&str> = skin.layers.iter().map( |l| l.name.as_str() ).collect();
+		assert_eq!( layers, vec![ "stick", "centre" ] );
+		let axes: Vec<&str> = skin.layers[ 0 ].drives.iter().map( |d| d.axis ).collect();
+		assert_eq!( axes, vec![ "x", "y", "z" ] );
+		assert!( Skin::load( "no such skin" ).is_err() );
+	}
+
+	#[test]
+	fn from_config() {
+		let text = format!( "[Skin]\nname = Test\nauthor = \nsize = 100, 50\n{}\
+			[Layer b]\nimage = Stick\nz = 2\n[Layer a]\nimage = centre\nz = 2\n[Layer c]\nimage = centre\nz = -1\n", IMAGES );
+		let (skin, errors) = read( &text );
+		assert!( errors.is_empty(), "{:?}", errors );
+		assert_eq!( (skin.title.as_str(), skin.author.clone(), skin.size), ("Test", None, (100.0, 50.0)) );
+		let layers: Vec<(&str, &str)> = skin.layers.iter().map( |l| (l.name.as_str(), l.image.as_str()) ).collect();
+		assert_eq!( layers, vec![ ("c", "centre"), ("a", "centre"), ("b", "stick") ] );
+
+		// each bad line is an error, the rest is used
+		let text = format!( "[Skin]\nsize = 0, 10\ncolour = red\n{}[Layer a]\nimage = grip\n[Layer b]\nz = 1\n\
+			[Layer c]\nimage = stick\nrotate = w\n[Layer d]\nimage = stick\n[Layer]\nimage = stick\n[Grip]\n", IMAGES );
+		let (skin, errors) = read( &text );
+		assert_eq!( skin.size, DEFAULT_SIZE );
+		assert_eq!( skin.layers.len(), 1 );
+		for error in [ "skin 'test': [Skin] size should be more than 0",
+					   "skin 'test': [Skin] unknown key 'colour'",
+					   "skin 'test': [Layer a] no image 'grip' in its [Images]",
+					   "skin 'test': [Layer b] needs an image",
+					   "skin 'test': [Layer c] rotate unknown axis 'w'",
+					   "skin 'test': unknown section [layer]",
+					   "skin 'test': unknown section [grip]" ] {
+			assert!( errors.contains( &error.to_string() ), "{} not in {:?}", error, errors );
+		}
+		assert_eq!( errors.len(), 7, "{:?}", errors );
+	}
+
+	#[test]
+	fn stick() {
+		let skin = Skin::stick( "stick", "centre", (200.0, 100.0), (2.0, -2.0, 0.5) );
+		let mut report = virtual_report( "[Stick]\n" );
+		let poses = skin.poses( &report );
+		let (at, angle) = (poses[ 0 ].at, poses[ 0 ].angle);
+		assert!( (at.0 - 107.0).abs() < 0.01 && (at.1 - 50.5).abs() < 0.01 && (angle - 0.5).abs() < 0.01, "{:?} {}", at, angle );
+		assert_eq!( (poses[ 1 ].image.as_str(), poses[ 1 ].at), ("centre", (100.0, 50.0)) );
+
+		// a quarter of the size, and a radian, either way
+		(report.x, report.y, report.z) = (JS_MAX, 0, 0);
+		let pose = &skin.poses( &report )[ 0 ];
+		assert!( (pose.at.0 - 157.0).abs() < 0.01 && (pose.at.1 - 25.5).abs() < 0.01 && (pose.angle + 0.5).abs() < 0.01, "{:?}", pose );
+	}
+
+	#[test]
+	fn curves() {
+		assert!( Curve::from_name( "bounce" ).is_err() );
+		for (name, half) in [ ("linear", 0.5), (" Ease_In ", 0.25), ("ease_out", 0.75), ("smooth", 0.5), ("step", 1.0) ] {
+			let curve = Curve::from_name( name ).unwrap();
+			assert_eq!( (curve.apply( 0.0 ), curve.apply( 0.5 ), curve.apply( 1.0 )), (0.0, half, 1.0), "{}", name );
+		}
+		assert_eq!( Curve::Step.apply( 0.49 ), 0.0 );
+		assert!( Curve::Smooth.apply( 0.1 ) < 0.1 && Curve::EaseOut.apply( 0.1 ) > 0.1 );
+	}
+
+	#[test]
+	fn drives() {
+		let drive = Drive::parse( Transform::Rotate, "Z, range=-30..30, input=0.5..1, curve=ease_in, pivot=0.5 0.9" ).unwrap();
+		assert_eq!( (drive.axis, drive.range, drive.input, drive.curve, drive.pivot),
+					("z", Some( (-30.0, 30.0) ), (0.5, 1.0), Curve::EaseIn, Some( (0.5, 0.9) )) );
+		assert_eq!( (drive.amount( 0.25 ), drive.amount( 0.75 ), drive.amount( 1.0 )), (0.0, 0.25, 1.0) );
+
+		let tint = Drive::parse( Transform::Tint, "slider_0, range=#000000..#ff8000" ).unwrap();
+		assert_eq!( (tint.range, tint.colours), (None, ((0, 0, 0), (255, 128, 0))) );
+		for (transform, text) in [ (Transform::Rotate, "w"), (Transform::Rotate, "z, range=30"), (Transform::Rotate, "z, input=1..1"),
+								   (Transform::Rotate, "z, curve=bounce"), (Transform::Rotate, "z, pivot=0.5,0.5"),
+								   (Transform::Rotate, "z, speed=2"), (Transform::Rotate, "z, smooth"),
+								   (Transform::Tint, "z, range=0..1"), (Transform::Tint, "z, range=#fff..#000000") ] {
+			assert!( Drive::parse( transform, text ).is_err(), "{}", text );
+		}
+	}
+
+	#[test]
+	fn controls_and_highlights() {
+		assert_eq!( controls( "button 3; Hat 1 up_left;hat 2 centered; hat 1 any" ),
+					Ok( vec![ Control::Button( 3 ), Control::Hat( 1, HatWay::Towards( 7 ) ), Control::Hat( 2, HatWay::Centred ), Control::Hat( 1, HatWay::Any ) ] ) );
+		for text in [ "button", "button x", "hat 1", "hat 1 sideways", "axis 1" ] {
+			assert!( Control::parse( text ).is_err(), "{}", text );
+		}
+
+		let buttons = vec![ Button { number: 3, name: None, group: String::new(), pressed: true } ];
+		let hats = vec![ Hat { number: 1, name: None, position: Some( 2 ) }, Hat { number: 2, name: None, position: None } ];
+		for (text, on) in [ ("button 3", true), ("button 4", false), ("hat 1 right", true), ("hat 1 up", false),
+							("hat 1 any", true), ("hat 2 any", false), ("hat 2 centred", true), ("hat 3 centred", false) ] {
+			assert_eq!( Control::parse( text ).unwrap().is_on( &buttons, &hats ), on, "{}", text );
+		}
+
+		assert_eq!( Highlight::parse( "button 1" ), Ok( Highlight { when: vec![ Control::Button( 1 ) ], tint: HIGHLIGHT, opacity: 1.0 } ) );
+		let highlight = Highlight::parse( "button 1; hat 1 up, tint=#ff0000, opacity=2" ).unwrap();
+		assert_eq!( (highlight.when.len(), highlight.tint, highlight.opacity), (2, (255, 0, 0), 1.0) );
+		for text in [ "", ", tint=#ff0000", "button 1, tint=red", "button 1, glow=1", "button 1, opacity" ] {
+			assert!( Highlight::parse( text ).is_err(), "{}", text );
+		}
+	}
+
+	#[test]
+	fn colours() {
+		assert_eq!( colour( " #FF8000 " ), Ok( (255, 128, 0) ) );
+		for text in [ "ff8000", "#ff80", "#gg8000", "#ff80é" ] {
+			assert!( colour( text ).is_err(), "{}", text );
+		}
+		assert_eq!( blend( ((0, 0, 0), (255, 100, 10)), 0.5 ), (128, 50, 5) );
+		assert_eq!( multiply( (255, 128, 0), (128, 255, 255) ), (128, 128, 0) );
+	}
+
+	#[test]
+	fn poses() {
+		let text = format!( "{}[Layer grip]\nimage = stick\nat = 100, 100\nangle = 90\n\
+			translate_x = x, range=-10..10\nrotate = z, range=0..90\nscale = rx, range=1..2\nscale_y = ry, range=1..0.5\n\
+			opacity = slider_0\ntint = slider_1, range=#ffffff..#000000\n\
+			[Layer pulled]\nimage = centre\nz = 1\nshow_when = button 1; hat 1 up\n\
+			[Layer hidden]\nimage = centre\nz = 1\nhide_when = button 1\nhighlight = hat 1 any, tint=#00ff00, opacity=0.5\n", IMAGES );
+		let (skin, errors) = read( &text );
+		assert!( errors.is_empty(), "{:?}", errors );
+
+		let mut report = virtual_report( "[Stick]\n" );
+		(report.x, report.z, report.rx, report.ry, report.slider_0, report.slider_1) = (JS_MAX, JS_MAX, JS_MAX, JS_MAX, JS_MAX / 2, JS_MAX);
+		report.buttons = vec![ Button { number: 1, name: None, group: String::new(), pressed: false } ];
+		report.hats = vec![ Hat { number: 1, name: None, position: None } ];
+		let poses = skin.poses( &report );
+		let images: Vec<&str> = poses.iter().map( |p| p.image.as_str() ).collect();
+		assert_eq!( images, vec![ "stick", "centre" ] );
+		let grip = &poses[ 0 ];
+		assert_eq!( (grip.at, grip.scale, grip.tint), ((110.0, 100.0), (2.0, 1.0), (0, 0, 0)) );
+		assert!( (grip.angle - std::f32::consts::PI).abs() < 0.0001 && (grip.opacity - 0.5).abs() < 0.0001, "{:?}", grip );
+		assert_eq!( (poses[ 1 ].tint, poses[ 1 ].opacity), (WHITE, 1.0) );
+
+		// shown, hidden and highlighted by the buttons and hats
+		report.buttons[ 0 ].pressed = true;
+		assert_eq!( skin.poses( &report ).len(), 2 );
+		report.buttons[ 0 ].pressed = false;
+		report.hats[ 0 ].position = Some( 0 );
+		let poses = skin.poses( &report );
+		assert_eq!( poses.len(), 3 );
+		let hidden = poses.iter().find( |p| p.tint != WHITE && p.image == "centre" ).unwrap();
+		assert_eq!( (hidden.tint, hidden.opacity), ((0, 255, 0), 0.5) );
+	}
+}
+
+/* ******************************************************************************* *
+ *		*** End ***
+ * ******************************************************************************* */
@@ -0,0 +1,54 @@
+/* ******************************************************************************* */
+/* Test files: a directory of its own for each test, removed when the test is done */
+/* ******************************************************************************* */
+/*
+ * Tests run side by side in one process, so each gets a directory named for the test,
+ * the process and a count, in the system's temporary directory.  It is removed when
+ * dropped, whether the test passed or an assert failed.
+ */
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering, };
+
+static COUNT: AtomicUsize = AtomicUsize::new( 0 );
+
+/* ******************************************************************************* */
+/* Structures */
+/* ******************************************************************************* */
+
+/// A directory for one test's files
+pub struct TestDir {
+	path: PathBuf,
+}
+
+impl TestDir {
+	/// a new, empty directory; name says which test it is for
+	pub fn new( name: &str ) -> TestDir {
+		let count = COUNT.fetch_add( 1, Ordering::Relaxed );
+		let path = std::env::temp_dir().join( format!("jsm_{}_{}_{}", name, std::process::id(), count) );
+		let _ = std::fs::remove_dir_all( &path );
+		std::fs::create_dir_all( &path ).unwrap();
+		TestDir { path }
+	}
+
+	/// a file in the directory, as the functions taking a path as text want it
+	pub fn file( &self, name: &str ) -> String {
+		self.path.join( name ).to_string_lossy().to_string()
+	}
+
+	/// a file in the directory, holding text
+	pub fn write( &self, name: &str, text: &str ) -> String {
+		let path = self.file( name );
+		std::fs::write( &path, text ).unwrap();
+		path
+	}
+}
+
+impl Drop for TestDir {
+	fn drop( &mut self ) {
+		let _ = std::fs::remove_dir_all( &self.path );
+	}
+}
+
+/* ******************************************************************************* *
+ *		*** End ***
+ * ******************************************************************************* */